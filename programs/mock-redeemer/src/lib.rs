@@ -0,0 +1,46 @@
+use anchor_lang::{prelude::*, system_program};
+
+declare_id!("8sgEtBvh6UdxqeCwEHUwQR2cuYF23T5qLs7GbxrbvvNi");
+
+/// The seed of the PDA redeeming swaps on behalf of this program
+pub const REDEEMER_SEED: &[u8] = b"redeemer";
+
+/// A mock of a program redeeming swaps into a PDA it controls, which cannot sign a transaction.
+/// It is only deployed by the integration tests of `solana-native-swaps`.
+#[program]
+pub mod mock_redeemer {
+    use super::*;
+
+    /// Spends `amount` lamports of the redeemer PDA, e.g. the payout of a swap redeemed into it,
+    /// by transferring them to the `recipient`. The transfer is signed through the PDA seeds.
+    pub fn spend(ctx: Context<Spend>, amount: u64) -> Result<()> {
+        let seeds: &[&[u8]] = &[REDEEMER_SEED, &[ctx.bumps.redeemer]];
+        let transfer = system_program::Transfer {
+            from: ctx.accounts.redeemer.to_account_info(),
+            to: ctx.accounts.recipient.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                transfer,
+                &[seeds],
+            ),
+            amount,
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct Spend<'info> {
+    /// The PDA redeeming swaps on behalf of this program.
+    /// It holds no data and is owned by the System Program, as any account credited by a redeem
+    /// before being initialized, so that its lamports are spent through a system transfer.
+    #[account(mut, seeds = [REDEEMER_SEED], bump)]
+    pub redeemer: SystemAccount<'info>,
+
+    /// CHECK: Any account receiving the spent lamports
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
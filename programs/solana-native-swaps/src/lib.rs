@@ -1,10 +1,265 @@
-use anchor_lang::{prelude::*, solana_program::hash, system_program};
+// The CPI client generated by `#[program]` takes the arguments of each instruction, beyond
+// the reach of the allowances on the handlers themselves
+#![cfg_attr(feature = "cpi", allow(clippy::too_many_arguments))]
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        bpf_loader_upgradeable, hash,
+        instruction::{AccountMeta, Instruction},
+        program::{invoke, set_return_data},
+    },
+    system_program, InstructionData,
+};
+use solana_curve25519::{
+    edwards::{multiply_edwards, validate_edwards, PodEdwardsPoint},
+    scalar::PodScalar,
+};
 
 declare_id!("6eksgdCnSjUaGQWZ6iYvauv1qzvYPF33RTGTM1ZuyENx");
 
 /// The size of Anchor's internal discriminator in a PDA's memory
 const ANCHOR_DISCRIMINATOR: usize = 8;
 
+/// The size of the account holding a `SwapAccount`
+pub const SWAP_ACCOUNT_SIZE: usize = ANCHOR_DISCRIMINATOR + SwapAccount::INIT_SPACE;
+
+/// The size of the account holding a `SwapAccount` of the layout the program was first
+/// deployed with. Refer `BaselineSwapAccount`.
+pub const BASELINE_SWAP_ACCOUNT_SIZE: usize =
+    ANCHOR_DISCRIMINATOR + BaselineSwapAccount::INIT_SPACE;
+
+/// The size of the account holding a `SwapVault`
+pub const SWAP_VAULT_SIZE: usize = ANCHOR_DISCRIMINATOR + SwapVault::INIT_SPACE;
+
+/// The seed prefix of the vault PDA escrowing the funds of a swap
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// The seed of the singleton PDA holding the `ProtocolConfig`
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// The seed of the singleton PDA holding the `Stats`
+pub const STATS_SEED: &[u8] = b"stats";
+
+/// The seed prefix of the per-redeemer PDA holding a `RateLimit`
+pub const RATE_LIMIT_SEED: &[u8] = b"rate_limit";
+
+/// The seed prefix of the per-secret-hash PDA holding a `RevealedSecret`
+pub const REVEALED_SECRET_SEED: &[u8] = b"revealed_secret";
+
+/// The seed prefix of the per-redeemer PDA holding a `WithdrawalEscrow`
+pub const WITHDRAWAL_ESCROW_SEED: &[u8] = b"withdrawal_escrow";
+
+/// The seed of the singleton PDA holding the `RefundQueue`
+pub const REFUND_QUEUE_SEED: &[u8] = b"refund_queue";
+
+/// The seed prefix of the per-swap-account PDA holding a `RedeemReceipt`
+pub const REDEEM_RECEIPT_SEED: &[u8] = b"redeem_receipt";
+
+/// The size of the account holding a `RedeemReceipt`
+pub const REDEEM_RECEIPT_SIZE: usize = ANCHOR_DISCRIMINATOR + RedeemReceipt::INIT_SPACE;
+
+/// The semantic version of the program, emitted by `program_version`. This follows the version
+/// of the package, whose minor version is bumped upon each feature addition.
+pub const PROGRAM_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The flag of the `bucketed-seeds` feature in `FEATURE_FLAGS`
+pub const FEATURE_BUCKETED_SEEDS: u64 = 1 << 0;
+
+/// The flag of the `verbose` feature in `FEATURE_FLAGS`
+pub const FEATURE_VERBOSE: u64 = 1 << 1;
+
+/// The flag in `FEATURE_FLAGS` of builds without the `instant-refund` feature, which is
+/// enabled by default. Such deployments lack `instant_refund`.
+pub const FEATURE_NO_INSTANT_REFUND: u64 = 1 << 2;
+
+/// The flag of the `bench` feature in `FEATURE_FLAGS`, so that a profiling build deployed by
+/// mistake can be told apart from a production one.
+pub const FEATURE_BENCH: u64 = 1 << 3;
+
+/// The flag of the `lite-redeem` feature in `FEATURE_FLAGS`, so that clients can tell whether
+/// the deployment accepts discriminator-free redeems. Refer `lite_redeem`.
+pub const FEATURE_LITE_REDEEM: u64 = 1 << 4;
+
+/// The byte discriminator-free redeems start with, followed by the 32-byte secret, so that the
+/// fallback of the program never takes any other data for a lite redeem. Refer `lite_redeem`.
+pub const LITE_REDEEM_TAG: u8 = 0x4c;
+
+/// The bitmask of the optional features the program was built with, emitted by
+/// `program_version`. Only the features altering the behavior of a deployment are flagged.
+pub const FEATURE_FLAGS: u64 = (if cfg!(feature = "bucketed-seeds") {
+    FEATURE_BUCKETED_SEEDS
+} else {
+    0
+}) | (if cfg!(feature = "verbose") {
+    FEATURE_VERBOSE
+} else {
+    0
+}) | (if cfg!(feature = "instant-refund") {
+    0
+} else {
+    FEATURE_NO_INSTANT_REFUND
+}) | (if cfg!(feature = "bench") {
+    FEATURE_BENCH
+} else {
+    0
+}) | (if cfg!(feature = "lite-redeem") {
+    FEATURE_LITE_REDEEM
+} else {
+    0
+});
+
+/// The nominal duration of a slot in milliseconds, which actual slots may deviate from
+pub const SLOT_DURATION_MS: i64 = 400;
+
+/// The number of slots before the expiry slot of a swap (~1 minute), from which
+/// `signal_expiry_approaching` is accepted
+pub const EXPIRY_SIGNAL_WINDOW: u64 = 150;
+
+/// The number of slots past the expiry slot (~1 day) during which only the refund trigger of a
+/// swap can trigger its refund, if restricted. Past these, anyone can, so that the funds are not
+/// locked should the trigger lose its key or refuse to act.
+pub const REFUND_TRIGGER_GRACE_SLOTS: u64 = 216_000;
+
+/// The maximum number of secret hashes a swap can require preimages of, besides its
+/// secret hash. This bounds the size of the swap account.
+pub const MAX_ADDITIONAL_SECRET_HASHES: usize = 3;
+
+/// The maximum length in bytes of the memo attached to a redeem
+pub const MAX_MEMO_LEN: usize = 64;
+
+/// The maximum number of recipients a redeem can be split across. This bounds the size of
+/// the swap account.
+pub const MAX_RECIPIENTS: usize = 4;
+
+/// The maximum number of sibling hashes in the Merkle proof of `redeem_merkle`, i.e. the
+/// depth of the tree, which thus holds at most 2^16 secrets. This bounds the compute of a redeem.
+pub const MAX_MERKLE_PROOF_LEN: usize = 16;
+
+/// The maximum number of child swaps a swap can be split into through `split_swap`. This bounds
+/// the accounts and the compute of a split.
+pub const MAX_SPLITS: usize = 4;
+
+/// The maximum number of swaps the `RefundQueue` can hold at once. This bounds the size of
+/// the queue, which is allocated in full upon creation.
+pub const MAX_REFUND_QUEUE_LEN: usize = 64;
+
+/// The maximum keeper reward of a swap, in basis points of its swap amount
+pub const MAX_KEEPER_REWARD_BPS: u16 = 100;
+
+/// The maximum penalty of an instant refund, in basis points of the swap amount
+pub const MAX_PENALTY_BPS: u16 = 1_000;
+
+/// The minimum timelock of a swap, in basis points of the timelock of its counterparty leg.
+/// Refer `assert_safe_timelock`.
+pub const MIN_SAFE_TIMELOCK_BPS: u16 = 15_000;
+
+/// The total of the basis points of the shares of a split redeem
+pub const TOTAL_BPS: u16 = 10_000;
+
+/// The address of the Pyth receiver program, owning the price update accounts read as oracles
+pub const PYTH_RECEIVER_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+/// The maximum age in seconds of an oracle price used to redeem a value-pegged swap
+pub const MAX_ORACLE_AGE: i64 = 60;
+
+/// The maximum confidence interval of an oracle price used to redeem a value-pegged swap,
+/// in basis points of the price
+pub const MAX_ORACLE_CONFIDENCE_BPS: u16 = 200;
+
+/// The number of decimals of the `target_value` of value-pegged swaps, e.g. micro-USD
+pub const TARGET_VALUE_DECIMALS: u32 = 6;
+
+/// The address of the SPL token program
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// The mint of wrapped SOL (wSOL) under the SPL token program
+pub const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
+/// The compressed base point of the Ed25519 curve
+const ED25519_BASEPOINT: [u8; 32] = [
+    0x58, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+    0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66,
+];
+
+/// The 8-byte discriminators prefixing the data of each instruction, as derived by Anchor
+/// from `sha256("global:<instruction name>")`. These are exported for clients and tooling
+/// which build instructions without the IDL, such as the Trident fuzz harness.
+pub const INITIATE_DISCRIMINATOR: [u8; 8] = [5, 63, 123, 113, 153, 75, 148, 14];
+pub const REDEEM_DISCRIMINATOR: [u8; 8] = [184, 12, 86, 149, 70, 196, 97, 225];
+pub const REFUND_DISCRIMINATOR: [u8; 8] = [2, 96, 183, 251, 63, 208, 46, 46];
+pub const INSTANT_REFUND_DISCRIMINATOR: [u8; 8] = [211, 202, 103, 41, 183, 147, 59, 251];
+
+/// The 8-byte discriminators prefixing the data of each event, as derived by Anchor from
+/// `sha256("event:<event name>")`. These are exported for clients filtering the program logs
+/// without the IDL. Every event of a swap holds its redeemer right after the discriminator,
+/// except `SwapSnapshot`, which holds it at `SNAPSHOT_REDEEMER_OFFSET`.
+pub const INITIATED_EVENT_DISCRIMINATOR: [u8; 8] = [6, 108, 212, 91, 67, 60, 207, 221];
+pub const REDEEMED_EVENT_DISCRIMINATOR: [u8; 8] = [14, 29, 183, 71, 31, 165, 107, 38];
+pub const SETTLED_EVENT_DISCRIMINATOR: [u8; 8] = [232, 210, 40, 17, 142, 124, 145, 238];
+pub const REFUNDED_EVENT_DISCRIMINATOR: [u8; 8] = [35, 103, 149, 246, 196, 123, 221, 99];
+pub const SWEPT_EVENT_DISCRIMINATOR: [u8; 8] = [254, 138, 9, 198, 192, 61, 165, 135];
+pub const TIMELOCK_EXTENDED_EVENT_DISCRIMINATOR: [u8; 8] = [208, 102, 194, 51, 223, 182, 20, 129];
+pub const REBATE_COMMITTED_EVENT_DISCRIMINATOR: [u8; 8] = [184, 230, 116, 201, 13, 13, 241, 244];
+pub const REDEEM_RIGHTS_TRANSFERRED_EVENT_DISCRIMINATOR: [u8; 8] =
+    [70, 130, 64, 224, 229, 39, 73, 238];
+pub const REALLOCED_EVENT_DISCRIMINATOR: [u8; 8] = [223, 75, 93, 180, 148, 254, 241, 194];
+pub const EXPIRY_APPROACHING_EVENT_DISCRIMINATOR: [u8; 8] = [255, 104, 230, 230, 84, 164, 229, 148];
+pub const SWAP_SNAPSHOT_EVENT_DISCRIMINATOR: [u8; 8] = [74, 180, 105, 236, 117, 150, 89, 125];
+pub const VERSION_EVENT_DISCRIMINATOR: [u8; 8] = [118, 124, 118, 122, 110, 115, 247, 164];
+pub const WITHDRAWAL_SCHEDULED_EVENT_DISCRIMINATOR: [u8; 8] = [198, 83, 120, 149, 80, 52, 108, 17];
+pub const SECRET_REVEALED_EVENT_DISCRIMINATOR: [u8; 8] = [164, 226, 145, 231, 240, 31, 44, 142];
+pub const CANCELED_EVENT_DISCRIMINATOR: [u8; 8] = [171, 91, 217, 19, 119, 196, 120, 190];
+pub const INSTANT_REFUNDED_EVENT_DISCRIMINATOR: [u8; 8] = [220, 50, 18, 207, 183, 232, 218, 25];
+pub const DECLINED_EVENT_DISCRIMINATOR: [u8; 8] = [59, 213, 198, 187, 88, 218, 236, 20];
+pub const MERGED_EVENT_DISCRIMINATOR: [u8; 8] = [57, 129, 241, 73, 156, 41, 126, 251];
+
+/// The offset of the redeemer in the data of a `SwapSnapshot`, following the discriminator,
+/// the swap account, and the expiry slot and bump of the swap
+pub const SNAPSHOT_REDEEMER_OFFSET: usize = 49;
+
+/// Processes a raw, undecoded instruction exactly like the on-chain entrypoint does.
+/// This is exposed with the `raw-entrypoint` feature, so that the program can be linked natively
+/// and fuzzed at the level of raw instruction bytes.
+#[cfg(feature = "raw-entrypoint")]
+pub fn process_instruction<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    data: &[u8],
+) -> anchor_lang::solana_program::entrypoint::ProgramResult {
+    entry(program_id, accounts, data)
+}
+
+/// Logs the remaining compute units upon creation and once dropped, so that the compute units
+/// consumed in between can be read from the logs. Refer `log_compute_units!`.
+#[cfg(feature = "bench")]
+pub struct ComputeUnitsLog;
+
+#[cfg(feature = "bench")]
+impl ComputeUnitsLog {
+    pub fn start() -> Self {
+        anchor_lang::solana_program::log::sol_log_compute_units();
+        Self
+    }
+}
+
+#[cfg(feature = "bench")]
+impl Drop for ComputeUnitsLog {
+    fn drop(&mut self) {
+        anchor_lang::solana_program::log::sol_log_compute_units();
+    }
+}
+
+/// Logs the remaining compute units at the entry of the enclosing handler and at its exit,
+/// whether it succeeds or fails, with the `bench` feature. It expands to nothing otherwise.
+/// Handlers delegating to another handler log around both.
+macro_rules! log_compute_units {
+    () => {
+        #[cfg(feature = "bench")]
+        let _compute_units_log = ComputeUnitsLog::start();
+    };
+}
+
 #[program]
 pub mod solana_native_swaps {
     use super::*;
@@ -14,11 +269,13 @@ pub mod solana_native_swaps {
     /// through this atomic swap in base units (aka lamports).  
     /// E.g: A quantity of 1 SOL must be provided as 1,000,000,000.
     /// `timelock` represents the number of slots (1 slot = 400ms) after
-    /// which (non-instant) refunds are allowed.
+    /// which (non-instant) refunds are allowed. It must be positive, so that the swap does not
+    /// expire in the slot it is initiated in.
     /// `destination_data` is an optional field, intended to hold information regarding the
     /// destination chain in the atomic swap.
+    /// The swap has the default `SwapOptions`. Refer `initiate_with_options` for others.
     pub fn initiate(
-        ctx: Context<Initiate>,
+        mut ctx: Context<Initiate>,
         redeemer: Pubkey,
         refundee: Pubkey,
         secret_hash: [u8; 32],
@@ -26,69 +283,644 @@ pub mod solana_native_swaps {
         timelock: u64,
         destination_data: Option<Vec<u8>>,
     ) -> Result<()> {
-        let transfer_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(),
-            system_program::Transfer {
-                from: ctx.accounts.funder.to_account_info(),
-                to: ctx.accounts.swap_account.to_account_info(),
-            },
+        log_compute_units!();
+        initiate_swap(
+            &mut ctx,
+            redeemer,
+            refundee,
+            secret_hash,
+            swap_amount,
+            Expiry::Timelock(timelock),
+            destination_data,
+            None,
+            None,
+            SwapOptions::default(),
+        )
+    }
+
+    /// Initiates the atomic swap exactly like `initiate`, with `options` as its optional
+    /// settings. Refer `SwapOptions`. This is a separate instruction, so that the wire format
+    /// of `initiate` stays that of its first deployment.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initiate_with_options(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+        destination_data: Option<Vec<u8>>,
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
+        initiate_swap(
+            &mut ctx,
+            redeemer,
+            refundee,
+            secret_hash,
+            swap_amount,
+            Expiry::Timelock(timelock),
+            destination_data,
+            None,
+            None,
+            options,
+        )
+    }
+
+    /// Initiates the atomic swap exactly like `initiate_with_options`, failing with
+    /// `SwapError::SwapAlreadyActive` if an active swap already exists at the derived PDA,
+    /// rather than with the system program error upon its creation. The check and the initiate
+    /// happen within this instruction, the latter through a CPI into this program, so that no
+    /// swap can be initiated at the PDA in between. This takes the accounts of `initiate`,
+    /// along with this program as `swap_program`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_initiate(
+        ctx: Context<TryInitiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+        destination_data: Option<Vec<u8>>,
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
+        // Swap accounts are owned by this program from initiation until they are closed
+        require!(
+            ctx.accounts.swap_account.owner != &crate::ID,
+            SwapError::SwapAlreadyActive
         );
-        system_program::transfer(transfer_context, swap_amount)?;
 
-        let expiry_slot = Clock::get()?
-            .slot
-            .checked_add(timelock)
-            .expect("timelock should not cause an overflow");
-        *ctx.accounts.swap_account = SwapAccount {
-            expiry_slot,
-            bump: ctx.bumps.swap_account,
+        let initiate_accounts = accounts::Initiate {
+            swap_account: ctx.accounts.swap_account.key(),
+            vault: ctx.accounts.vault.key(),
+            funder: ctx.accounts.funder.key(),
             rent_sponsor: ctx.accounts.rent_sponsor.key(),
+            system_program: system_program::ID,
+            stats: ctx.accounts.stats.as_ref().map(|stats| stats.key()),
+            fee_collector: ctx
+                .accounts
+                .fee_collector
+                .as_ref()
+                .map(|fee_collector| fee_collector.key()),
+            wsol_account: None,
+            token_program: None,
+            old_swap_account: None,
+            old_vault: None,
+            refund_queue: ctx
+                .accounts
+                .refund_queue
+                .as_ref()
+                .map(|refund_queue| refund_queue.key()),
+            protocol_config: ctx
+                .accounts
+                .protocol_config
+                .as_ref()
+                .map(|protocol_config| protocol_config.key()),
+        };
+        // The protocol config is forwarded as writable only if passed as such, as it need
+        // only be while a TVL cap is configured
+        let mut initiate_metas = initiate_accounts.to_account_metas(None);
+        if let Some(protocol_config) = &ctx.accounts.protocol_config {
+            for meta in &mut initiate_metas {
+                if meta.pubkey == protocol_config.key() {
+                    meta.is_writable = protocol_config.is_writable;
+                }
+            }
+        }
+        let initiate = Instruction {
+            program_id: crate::ID,
+            accounts: initiate_metas,
+            data: instruction::InitiateWithOptions {
+                redeemer,
+                refundee,
+                secret_hash,
+                swap_amount,
+                timelock,
+                destination_data,
+                options,
+            }
+            .data(),
+        };
+        invoke(&initiate, &ctx.accounts.to_account_infos())?;
+
+        Ok(())
+    }
+
+    /// Initiates the atomic swap exactly like `initiate`, with only the required parameters.
+    /// The swap has no destination data and the default `SwapOptions`, which keeps the
+    /// instruction as small as possible. The swap account is derived identically.
+    pub fn initiate_minimal(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+    ) -> Result<()> {
+        log_compute_units!();
+        initiate_swap(
+            &mut ctx,
+            redeemer,
             refundee,
+            secret_hash,
+            swap_amount,
+            Expiry::Timelock(timelock),
+            None,
+            None,
+            None,
+            SwapOptions::default(),
+        )
+    }
+
+    /// Initiates the atomic swap exactly like `initiate`, except that only a commitment
+    /// to the destination data is provided, in place of the data itself.
+    /// `destination_commitment` is the SHA-256 hash of the destination data, which is
+    /// stored in the swap account. The full data is carried off-chain and can be verified
+    /// against the commitment with `verify_destination_commitment`.
+    /// This keeps the transaction and the emitted event small for large destination payloads.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initiate_with_commitment(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+        destination_commitment: [u8; 32],
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
+        initiate_swap(
+            &mut ctx,
             redeemer,
+            refundee,
             secret_hash,
             swap_amount,
-            timelock,
-        };
+            Expiry::Timelock(timelock),
+            None,
+            Some(destination_commitment),
+            None,
+            options,
+        )
+    }
 
-        emit!(Initiated {
+    /// Initiates the atomic swap exactly like `initiate`, funded from the wSOL token account
+    /// of the funder rather than their native SOL, saving them a manual unwrap. The token
+    /// account, passed as `wsol_account` along with the `token_program`, is closed to the
+    /// funder, and `swap_amount` of the unwrapped lamports are then escrowed as usual.
+    /// Any remaining lamports, including the rent of the token account, are left to the funder.
+    /// It fails with `SwapError::InvalidWsolAccount` if the token account is not a wSOL
+    /// account of the funder, and with `SwapError::InsufficientWsolBalance` if it holds less
+    /// than `swap_amount`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initiate_from_wsol(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+        destination_data: Option<Vec<u8>>,
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
+        unwrap_wsol(ctx.accounts, swap_amount)?;
+
+        initiate_swap(
+            &mut ctx,
             redeemer,
             refundee,
             secret_hash,
             swap_amount,
-            timelock,
+            Expiry::Timelock(timelock),
             destination_data,
-            funder: ctx.accounts.funder.key(),
-        });
-
-        Ok(())
+            None,
+            None,
+            options,
+        )
     }
 
-    /// Funds are transferred to the redeemer. This instruction does not require any signatures.
-    pub fn redeem(ctx: Context<Redeem>, secret: [u8; 32]) -> Result<()> {
-        let SwapAccount {
+    /// Amends the terms of a funder-cancelable swap, by canceling it and initiating a swap with
+    /// the new terms exactly like `initiate`, in the same instruction. The swap being amended is
+    /// passed as `old_swap_account` along with its `old_vault`, and the funder must be its
+    /// cancel authority. Its swap amount is returned to the funder, who then funds the new swap,
+    /// and its rent is returned to the rent sponsor, who must also sponsor the new swap.
+    /// Since redeems close the swap account, a swap can only be amended before any redeem.
+    /// `Refunded` is emitted for the amended swap, and `Initiated` for the new one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn amend(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+        destination_data: Option<Vec<u8>>,
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
+        cancel_amended_swap(ctx.accounts)?;
+
+        initiate_swap(
+            &mut ctx,
+            redeemer,
             refundee,
+            secret_hash,
+            swap_amount,
+            Expiry::Timelock(timelock),
+            destination_data,
+            None,
+            None,
+            options,
+        )
+    }
+
+    /// Initiates the atomic swap exactly like `initiate`, except that it expires at the absolute
+    /// `expiry_slot` rather than after a timelock, sparing clients that coordinate on a deadline
+    /// from deriving the timelock off the current slot. `expiry_slot` must be in the future,
+    /// otherwise this fails with `SwapError::ExpiryNotInFuture`. The timelock of the swap is
+    /// derived as the number of slots from initiation to `expiry_slot`, while `expiry_slot` takes
+    /// its place in the PDA seeds and the swap id, which must be known ahead of initiation.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initiate_at(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        expiry_slot: u64,
+        destination_data: Option<Vec<u8>>,
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
+        initiate_swap(
+            &mut ctx,
             redeemer,
+            refundee,
             secret_hash,
             swap_amount,
-            timelock,
-            ..
-        } = *ctx.accounts.swap_account;
+            Expiry::Slot(expiry_slot),
+            destination_data,
+            None,
+            None,
+            options,
+        )
+    }
 
+    /// Initiates an adaptor swap, for scriptless cross-chain swaps. This behaves exactly
+    /// like `initiate`, except that the swap is locked by `adaptor_point` in place of a
+    /// secret hash. `adaptor_point` is a compressed Ed25519 point `T = t·G`, and redeeming
+    /// the swap through `redeem_adaptor` requires revealing the scalar `t`.
+    /// The adaptor point takes the place of the secret hash in the PDA seeds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initiate_adaptor(
+        mut ctx: Context<Initiate>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        adaptor_point: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+        destination_data: Option<Vec<u8>>,
+        options: SwapOptions,
+    ) -> Result<()> {
+        log_compute_units!();
         require!(
-            hash::hash(&secret).to_bytes() == secret_hash,
-            SwapError::InvalidSecret
+            validate_edwards(&PodEdwardsPoint(adaptor_point)),
+            SwapError::InvalidAdaptorPoint
         );
 
-        ctx.accounts.swap_account.sub_lamports(swap_amount)?;
-        ctx.accounts.redeemer.add_lamports(swap_amount)?;
-
-        emit!(Redeemed {
+        initiate_swap(
+            &mut ctx,
             redeemer,
             refundee,
-            secret,
+            adaptor_point,
             swap_amount,
-            timelock,
-        });
+            Expiry::Timelock(timelock),
+            destination_data,
+            None,
+            Some(adaptor_point),
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    /// Funds are transferred to the redeemer. This instruction does not require any signatures,
+    /// unless the swap was initiated with a `relayer`, in which case only that relayer can
+    /// trigger it and must sign this transaction.
+    /// The redeemer may also be the rent sponsor of the swap. Such aliasing is supported, as the
+    /// rent is only refunded when the swap account is closed after this handler, on top of
+    /// the already credited swap amount. The redeemer then receives `swap_amount + rent`.
+    /// For swaps initiated with a `distribution`, the swap amount is split across its
+    /// recipients in place of the redeemer. These must be passed as remaining accounts,
+    /// writable and in the order of the distribution.
+    /// The swap account, the swap amount and the secret are set as return data, for programs
+    /// redeeming through CPI to read back. Refer `redeem_return_data`.
+    pub fn redeem(ctx: Context<Redeem>, secret: [u8; 32]) -> Result<()> {
+        log_compute_units!();
+        redeem_swap(ctx, secret, None)
+    }
+
+    /// Redeems the swap exactly like `redeem`, along with `memo`, a note of at most
+    /// `MAX_MEMO_LEN` bytes, e.g. an off-chain order id, which is emitted in `Redeemed` but not
+    /// stored. This is a separate instruction, so that the wire format of `redeem` stays that
+    /// of its first deployment.
+    pub fn redeem_with_memo(ctx: Context<Redeem>, secret: [u8; 32], memo: String) -> Result<()> {
+        log_compute_units!();
+        redeem_swap(ctx, secret, Some(memo))
+    }
+
+    /// Redeems the swap exactly like `redeem`, except that the secret is passed as
+    /// `secret_hex`, its hex encoding, for clients holding the secret as a hex string.
+    /// It is decoded on-chain before being verified, failing with
+    /// `SwapError::InvalidSecretEncoding` unless it holds exactly 64 hex digits.
+    /// Refer `decode_secret_hex`.
+    pub fn redeem_hex(ctx: Context<Redeem>, secret_hex: String) -> Result<()> {
+        log_compute_units!();
+        let secret = decode_secret_hex(&secret_hex)?;
+        redeem(ctx, secret)
+    }
+
+    /// Redeems the swap exactly like `redeem`, recording the secret in a
+    /// `RedeemReceipt` at `[REDEEM_RECEIPT_SEED, swap_account]`, so that duplicate submissions
+    /// of this redeem succeed rather than fail on the closed swap account. Once the swap is
+    /// closed, this only checks that `secret` matches the one recorded, doing nothing else,
+    /// and fails with `SwapError::SwapNotRedeemed` if no receipt was recorded, e.g. for a
+    /// refunded swap. The redeem happens through a CPI into this program, and only takes
+    /// the named accounts of `redeem`, along with its `relayer`. Swaps requiring any other
+    /// optional account of `redeem` must be redeemed through it instead.
+    /// `payer` pays the rent of the receipt, which it can reclaim through
+    /// `close_redeem_receipt` once duplicates are no longer expected.
+    pub fn redeem_idempotent(ctx: Context<RedeemIdempotent>, secret: [u8; 32]) -> Result<()> {
+        log_compute_units!();
+        let swap_key = ctx.accounts.swap_account.key();
+        let receipt_info = ctx.accounts.redeem_receipt.to_account_info();
+        // Swap accounts are owned by this program from initiation until they are closed
+        if ctx.accounts.swap_account.owner != &crate::ID {
+            require!(receipt_info.owner == &crate::ID, SwapError::SwapNotRedeemed);
+            let receipt = RedeemReceipt::try_deserialize(&mut &receipt_info.data.borrow()[..])?;
+            require!(receipt.secret == secret, SwapError::InvalidSecret);
+            return Ok(());
+        }
+
+        let redeem_accounts = accounts::Redeem {
+            swap_account: swap_key,
+            vault: ctx.accounts.vault.key(),
+            redeemer: ctx.accounts.redeemer.key(),
+            rent_sponsor: ctx.accounts.rent_sponsor.key(),
+            protocol_config: ctx
+                .accounts
+                .protocol_config
+                .as_ref()
+                .map(|protocol_config| protocol_config.key()),
+            stats: ctx.accounts.stats.as_ref().map(|stats| stats.key()),
+            relayer: ctx.accounts.relayer.as_ref().map(|relayer| relayer.key()),
+            rate_limit: None,
+            price_oracle: None,
+            refundee: None,
+            rebate_recipient: None,
+            withdrawal_escrow: None,
+            funder: None,
+            refund_queue: None,
+        };
+        let redeem = Instruction {
+            program_id: crate::ID,
+            accounts: redeem_accounts.to_account_metas(None),
+            data: instruction::Redeem { secret }.data(),
+        };
+        invoke(&redeem, &ctx.accounts.to_account_infos())?;
+
+        // A receipt outlives its swap, and is overwritten by the redeem of a swap initiated
+        // anew at the same address, keeping the payer that created it
+        let payer = if receipt_info.owner == &crate::ID {
+            RedeemReceipt::try_deserialize(&mut &receipt_info.data.borrow()[..])?.payer
+        } else {
+            create_pda(
+                &ctx.accounts.payer.to_account_info(),
+                &receipt_info,
+                &ctx.accounts.system_program.to_account_info(),
+                REDEEM_RECEIPT_SIZE,
+                &[
+                    REDEEM_RECEIPT_SEED,
+                    swap_key.as_ref(),
+                    &[ctx.bumps.redeem_receipt],
+                ],
+            )?;
+            ctx.accounts.payer.key()
+        };
+        let receipt = RedeemReceipt {
+            secret,
+            redeemed_slot: current_clock()?.slot,
+            payer,
+            bump: ctx.bumps.redeem_receipt,
+        };
+        receipt.try_serialize(&mut &mut receipt_info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+
+    /// Closes a `RedeemReceipt`, returning its rent to the payer that created it, who must sign.
+    /// Duplicate submissions of `redeem_idempotent` then fail with `SwapError::SwapNotRedeemed`.
+    pub fn close_redeem_receipt(_ctx: Context<CloseRedeemReceipt>) -> Result<()> {
+        log_compute_units!();
+        Ok(())
+    }
+
+    /// Redeems the swap exactly like `redeem`, except that the funds of the redeemer are held in
+    /// their `WithdrawalEscrow`, passed as `withdrawal_escrow`, rather than paid out to them.
+    /// They can then be withdrawn by the redeemer through `withdraw`, once the withdrawal delay
+    /// of the escrow has passed since this redeem. `WithdrawalScheduled` is emitted along with
+    /// `Redeemed`. Swaps initiated with a `distribution` cannot be redeemed into an escrow.
+    pub fn redeem_to_escrow(
+        ctx: Context<Redeem>,
+        secret: [u8; 32],
+        memo: Option<String>,
+    ) -> Result<()> {
+        log_compute_units!();
+        ctx.accounts
+            .swap_account
+            .check_hash_kind(HashKind::Sha256)?;
+        verify_secret(&secret, &ctx.accounts.swap_account.secret_hash)?;
+        require!(
+            memo.as_ref().is_none_or(|memo| memo.len() <= MAX_MEMO_LEN),
+            SwapError::MemoTooLong
+        );
+
+        ctx.accounts
+            .pay_out(ctx.remaining_accounts, secret, vec![], memo, true)
+    }
+
+    /// Redeems a swap requiring multiple secrets, by revealing all of them at once.
+    /// `secrets` holds the preimage of the secret hash of the swap, followed by the preimages
+    /// of its `additional_secret_hashes` in order. Every secret must match its hash.
+    /// Otherwise, this behaves exactly like `redeem`, with all secrets emitted in `Redeemed`.
+    pub fn redeem_multi(ctx: Context<Redeem>, secrets: Vec<[u8; 32]>) -> Result<()> {
+        log_compute_units!();
+        let (secret, additional_secrets) = secrets.split_first().ok_or(SwapError::InvalidSecret)?;
+        ctx.accounts
+            .swap_account
+            .check_hash_kind(HashKind::Sha256)?;
+        verify_secret(secret, &ctx.accounts.swap_account.secret_hash)?;
+
+        ctx.accounts.pay_out(
+            ctx.remaining_accounts,
+            *secret,
+            additional_secrets.to_vec(),
+            None,
+            false,
+        )
+    }
+
+    /// Redeems sibling swaps sharing one secret hash, e.g. the children of a `split_swap`, by
+    /// revealing their secret once. Each sibling is passed as remaining accounts, in groups of
+    /// its swap account, vault, redeemer and rent sponsor, and is paid out to its redeemer
+    /// exactly like `redeem`, with `Redeemed` emitted for each. This fails as a whole if the
+    /// secret hash of any sibling differs. At most `MAX_SPLITS` siblings can be redeemed at once,
+    /// and only plain swaps, i.e. without a relayer, additional secret hashes, a distribution,
+    /// a price oracle, a rebate or vesting, initiated while no rate limit was in force. Any
+    /// other swap must be redeemed on its own, and fails with `SwapError::InvalidSiblings`.
+    pub fn redeem_siblings<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemSiblings<'info>>,
+        secret: [u8; 32],
+    ) -> Result<()> {
+        log_compute_units!();
+        let siblings = ctx.remaining_accounts.chunks_exact(4);
+        require!(
+            siblings.remainder().is_empty() && (1..=MAX_SPLITS).contains(&siblings.len()),
+            SwapError::InvalidSiblings
+        );
+        let secret_hash = hash::hash(&secret).to_bytes();
+        let current_slot = current_clock()?.slot;
+
+        for sibling in siblings {
+            let swap_account = Account::<SwapAccount>::try_from(&sibling[0])?;
+            let vault = Account::<SwapVault>::try_from(&sibling[1])?;
+            let (redeemer, rent_sponsor) = (&sibling[2], &sibling[3]);
+            require!(
+                swap_account.secret_hash == secret_hash,
+                SwapError::InvalidSecret
+            );
+            swap_account.check_sibling(swap_account.key(), vault.key(), current_slot)?;
+            require_keys_eq!(
+                redeemer.key(),
+                swap_account.redeemer,
+                SwapError::InvalidRedeemer
+            );
+            require!(redeemer.is_writable, SwapError::RedeemerNotWritable);
+            require_keys_eq!(
+                rent_sponsor.key(),
+                swap_account.rent_sponsor,
+                SwapError::InvalidRentSponsor
+            );
+
+            let SwapAccount {
+                refundee,
+                redeemer,
+                swap_amount,
+                timelock,
+                suppress_events,
+                swap_id,
+                ..
+            } = *swap_account;
+            vault.sub_lamports(swap_amount)?;
+            sibling[2].add_lamports(swap_amount)?;
+            release_tvl(ctx.accounts.protocol_config.as_deref_mut(), &swap_account)?;
+            if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+                stats.redeemed = stats.redeemed.saturating_add(1);
+            }
+            swap_account.close(rent_sponsor.clone())?;
+            vault.close(rent_sponsor.clone())?;
+
+            if !suppress_events {
+                emit!(Redeemed {
+                    redeemer,
+                    refundee,
+                    secret,
+                    swap_amount,
+                    timelock,
+                    relayer: None,
+                    additional_secrets: vec![],
+                    memo: None,
+                    distribution: vec![],
+                    rebate: 0,
+                    redeemer_amount: swap_amount,
+                    unvested: 0,
+                    swap_id,
+                });
+                let receipt = SettlementReceipt {
+                    redeemer,
+                    swap_amount,
+                    secret,
+                    slot: current_slot,
+                };
+                emit!(Settled {
+                    receipt_hash: receipt.hash(),
+                    receipt,
+                    swap_id,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Redeems an adaptor swap by revealing the scalar `adaptor_secret` (`t`), such that
+    /// `t·G` equals the adaptor point of the swap. Otherwise, this behaves exactly like `redeem`,
+    /// with `adaptor_secret` being emitted as the secret in `Redeemed`.
+    pub fn redeem_adaptor(ctx: Context<Redeem>, adaptor_secret: [u8; 32]) -> Result<()> {
+        log_compute_units!();
+        let adaptor_point = ctx
+            .accounts
+            .swap_account
+            .adaptor_point
+            .ok_or(SwapError::NotAnAdaptorSwap)?;
+        require!(
+            verify_adaptor_secret(&adaptor_point, &adaptor_secret),
+            SwapError::InvalidSecret
+        );
+
+        ctx.accounts
+            .pay_out(ctx.remaining_accounts, adaptor_secret, vec![], None, false)
+    }
+
+    /// Redeems a swap locked by a `secret_merkle_root` by revealing `secret`, along with the
+    /// Merkle `proof` that its SHA-256 hash is a leaf of the tree. Refer `verify_merkle_proof`.
+    /// The proof may hold at most `MAX_MERKLE_PROOF_LEN` hashes, failing with
+    /// `SwapError::MerkleProofTooLong` otherwise, and with `SwapError::InvalidSecret` unless it
+    /// leads to the root. Otherwise, this behaves exactly like `redeem`,
+    /// closing the swap upon the first valid secret, so that the other leaves can no longer
+    /// redeem it.
+    pub fn redeem_merkle(
+        ctx: Context<Redeem>,
+        secret: [u8; 32],
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        log_compute_units!();
+        ctx.accounts
+            .swap_account
+            .check_hash_kind(HashKind::MerkleSha256)?;
+        require!(
+            proof.len() <= MAX_MERKLE_PROOF_LEN,
+            SwapError::MerkleProofTooLong
+        );
+        let secret_merkle_root = ctx
+            .accounts
+            .swap_account
+            .secret_merkle_root
+            .ok_or(SwapError::WrongHashKind)?;
+        require!(
+            verify_merkle_proof(hash::hash(&secret).to_bytes(), &proof, &secret_merkle_root),
+            SwapError::InvalidSecret
+        );
+
+        ctx.accounts
+            .pay_out(ctx.remaining_accounts, secret, vec![], None, false)?;
+        set_return_data(&redeem_return_data(
+            &ctx.accounts.swap_account.key(),
+            ctx.accounts.swap_account.swap_amount,
+            &secret,
+        )?);
 
         Ok(())
     }
@@ -96,271 +928,6590 @@ pub mod solana_native_swaps {
     /// The refundee obtains the funds as a refund, given that no redeems have occured
     /// and the expiry slot has been reached.
     /// This instruction does not require any signatures.
+    /// For swaps initiated with a `keeper_reward_lamports`, whoever triggers the refund may sign
+    /// as `keeper` to receive the reward out of the swap amount, the refundee receiving the rest.
+    /// For funder-cancelable swaps, the funder may instead sign as `funder` to cancel the swap
+    /// at any slot, in which case the funds are returned to the funder.
     pub fn refund(ctx: Context<Refund>) -> Result<()> {
+        log_compute_units!();
         let SwapAccount {
-            expiry_slot,
             refundee,
             redeemer,
             secret_hash,
             swap_amount,
             timelock,
+            cancel_authority,
+            sink,
+            suppress_events,
+            swap_id,
+            keeper_reward_lamports,
             ..
         } = *ctx.accounts.swap_account;
 
-        let current_slot = Clock::get()?.slot;
-        require!(current_slot > expiry_slot, SwapError::RefundBeforeExpiry);
+        require_keys_eq!(
+            ctx.accounts.swap_account.derive_address()?,
+            ctx.accounts.swap_account.key(),
+            SwapError::SeedMismatch
+        );
+        check_swap_account_info(&ctx.accounts.swap_account.to_account_info())?;
+        let current_slot = current_clock()?.slot;
+        ctx.accounts.swap_account.check_clock(current_slot)?;
+        // Either way the swap is settled by this, canceled or refunded
+        let swap_key = ctx.accounts.swap_account.key();
+        if let Some(refund_queue) = &mut ctx.accounts.refund_queue {
+            refund_queue.deregister(&swap_key);
+        }
 
-        ctx.accounts.swap_account.sub_lamports(swap_amount)?;
-        ctx.accounts.refundee.add_lamports(swap_amount)?;
+        if let Some(funder) = &ctx.accounts.funder {
+            require!(
+                cancel_authority == Some(funder.key()),
+                SwapError::InvalidCancelAuthority
+            );
+            ctx.accounts.vault.sub_lamports(swap_amount)?;
+            funder.add_lamports(swap_amount)?;
+            release_tvl(
+                ctx.accounts.protocol_config.as_deref_mut(),
+                &ctx.accounts.swap_account,
+            )?;
+            if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+                stats.refunded = stats.refunded.saturating_add(1);
+            }
 
-        emit!(Refunded {
-            redeemer,
-            refundee,
-            secret_hash,
-            swap_amount,
-            timelock,
-        });
+            if !suppress_events {
+                emit!(Canceled {
+                    redeemer,
+                    refundee,
+                    secret_hash,
+                    swap_amount,
+                    timelock,
+                    funder: funder.key(),
+                    swap_id,
+                });
+            }
+
+            return Ok(());
+        }
+
+        ctx.accounts.swap_account.check_refund_trigger(
+            ctx.accounts
+                .refund_trigger
+                .as_ref()
+                .map(|signer| signer.key()),
+            current_slot,
+        )?;
+        require!(
+            ctx.accounts.swap_account.is_expired(current_slot),
+            SwapError::RefundBeforeExpiry
+        );
+
+        let keeper = ctx.accounts.keeper.as_ref().map(|keeper| keeper.key());
+        let keeper_reward = match &ctx.accounts.keeper {
+            Some(keeper) => {
+                keeper.add_lamports(keeper_reward_lamports)?;
+                keeper_reward_lamports
+            }
+            None => 0,
+        };
+        ctx.accounts.vault.sub_lamports(swap_amount)?;
+        ctx.accounts
+            .refundee
+            .add_lamports(swap_amount - keeper_reward)?;
+        release_tvl(
+            ctx.accounts.protocol_config.as_deref_mut(),
+            &ctx.accounts.swap_account,
+        )?;
+        if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+            stats.refunded = stats.refunded.saturating_add(1);
+        }
+
+        if !suppress_events {
+            if sink {
+                emit!(Swept {
+                    redeemer,
+                    sink: refundee,
+                    secret_hash,
+                    swap_amount,
+                    timelock,
+                    keeper,
+                    keeper_reward,
+                    swap_id,
+                });
+            } else {
+                emit!(Refunded {
+                    redeemer,
+                    refundee,
+                    secret_hash,
+                    swap_amount,
+                    timelock,
+                    keeper,
+                    keeper_reward,
+                    swap_id,
+                });
+            }
+        }
 
         Ok(())
     }
 
-    /// Funds are refunded to the refundee, with the redeemer's consent.
-    /// As such, the redeemer's signature is required for this instruction.
-    /// This allows for refunds before the expiry slot.
+    /// Funds are refunded to the refundee, with the consent of the instant refund authority,
+    /// which is the redeemer unless chosen otherwise at initiation.
+    /// As such, the authority's signature is required for this instruction.
+    /// This allows for refunds before the expiry slot, and only then: past it, `refund` is
+    /// available without any consent, and requiring the authority's signature would only give
+    /// leverage to coerce them. It fails with `SwapError::UseRegularRefund` once expired.
+    /// Conversely, it fails with `SwapError::InstantRefundTooEarly` within the instant refund
+    /// cliff chosen at initiation, sparing the authority from being pressured to consent
+    /// right away.
+    /// This is only available with the `instant-refund` feature, enabled by default, so that
+    /// deployments can exclude any refund bypassing the timelock.
+    #[cfg(feature = "instant-refund")]
     pub fn instant_refund(ctx: Context<InstantRefund>) -> Result<()> {
+        log_compute_units!();
+        instant_refund_swap(ctx, 0)
+    }
+
+    /// Refunds the swap exactly like `instant_refund`, paying a penalty of `penalty_bps` basis
+    /// points of the swap amount to the redeemer, passed as `redeemer`, for the trouble of a
+    /// canceled swap, the refundee receiving the rest. It is bounded by `MAX_PENALTY_BPS`,
+    /// failing with `SwapError::InvalidPenalty` otherwise. As it is paid out of the refund,
+    /// the refundee must sign this transaction as well, consenting to the penalty along with
+    /// the authority. This is a separate instruction, so that the wire format of
+    /// `instant_refund` stays that of its first deployment.
+    #[cfg(feature = "instant-refund")]
+    pub fn instant_refund_with_penalty(
+        ctx: Context<InstantRefund>,
+        penalty_bps: u16,
+    ) -> Result<()> {
+        log_compute_units!();
+        instant_refund_swap(ctx, penalty_bps)
+    }
+
+    /// The redeemer declines the swap, returning the funds to the funder rather than the
+    /// refundee, without waiting for the expiry slot. As such, the redeemer's signature is
+    /// required for this instruction. Like instant refunds, this is only accepted before the
+    /// expiry slot, and fails with `SwapError::UseRegularRefund` once expired.
+    pub fn decline(ctx: Context<Decline>) -> Result<()> {
+        log_compute_units!();
         let SwapAccount {
             refundee,
             redeemer,
             secret_hash,
             swap_amount,
             timelock,
+            funder,
+            suppress_events,
+            swap_id,
             ..
         } = *ctx.accounts.swap_account;
+        let current_slot = current_clock()?.slot;
+        ctx.accounts
+            .swap_account
+            .check_instant_refund(current_slot)?;
+        check_swap_account_info(&ctx.accounts.swap_account.to_account_info())?;
 
-        ctx.accounts.swap_account.sub_lamports(swap_amount)?;
-        ctx.accounts.refundee.add_lamports(swap_amount)?;
+        ctx.accounts.vault.sub_lamports(swap_amount)?;
+        ctx.accounts.funder.add_lamports(swap_amount)?;
+        release_tvl(
+            ctx.accounts.protocol_config.as_deref_mut(),
+            &ctx.accounts.swap_account,
+        )?;
+        if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+            stats.refunded = stats.refunded.saturating_add(1);
+        }
 
-        emit!(InstantRefunded {
-            redeemer,
-            refundee,
-            secret_hash,
-            swap_amount,
-            timelock,
+        if !suppress_events {
+            emit!(Declined {
+                redeemer,
+                refundee,
+                secret_hash,
+                swap_amount,
+                timelock,
+                funder,
+                swap_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Splits the swap into child swaps, e.g. to route it across several counterparties, each of
+    /// `splits` taking over part of the swap amount with its own redeemer. The amounts of the
+    /// splits must total the swap amount, and there can be at most `MAX_SPLITS` of them.
+    /// The children keep the secret hash, the timelock, the expiry slot and the other terms of
+    /// the swap, except for any rebate committed to by its redeemer. The swap account and the
+    /// vault of each child are passed as remaining accounts, in pairs, in the order of `splits`.
+    /// As this redirects the funds, only the funder of a `funder_cancelable` swap can split it,
+    /// and since redeems close the swap, only before it is redeemed. The rent sponsor of the
+    /// swap pays the rent of the children, receiving that of the swap, and as such must sign.
+    /// Swaps with a `distribution` or a `price_oracle` cannot be split.
+    /// `Initiated` is emitted for each child.
+    pub fn split_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SplitSwap<'info>>,
+        splits: Vec<Split>,
+    ) -> Result<()> {
+        log_compute_units!();
+        let swap = (*ctx.accounts.swap_account).clone();
+        validate_splits(&splits, swap.swap_amount)?;
+        require!(
+            swap.distribution.is_empty() && swap.price_oracle.is_none(),
+            SwapError::InvalidSplits
+        );
+        require_eq!(
+            ctx.remaining_accounts.len(),
+            2 * splits.len(),
+            SwapError::InvalidSplits
+        );
+        check_swap_account_info(&ctx.accounts.swap_account.to_account_info())?;
+        swap.check_clock(current_clock()?.slot)?;
+
+        let rent_sponsor = ctx.accounts.rent_sponsor.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let timelock_seed = swap.timelock_seed().to_le_bytes();
+        for (index, (split, accounts)) in splits
+            .iter()
+            .zip(ctx.remaining_accounts.chunks_exact(2))
+            .enumerate()
+        {
+            let (child_account, child_vault) = (&accounts[0], &accounts[1]);
+            let amount_seed = swap.seed_amount_for(split.swap_amount).to_le_bytes();
+            let seeds = [
+                split.redeemer.as_ref(),
+                swap.refundee.as_ref(),
+                &swap.secret_hash,
+                &amount_seed,
+                &timelock_seed,
+            ];
+            let (child_key, bump) = Pubkey::find_program_address(&seeds, &crate::ID);
+            require_keys_eq!(child_key, child_account.key(), SwapError::SeedMismatch);
+            let (vault_key, vault_bump) =
+                Pubkey::find_program_address(&[VAULT_SEED, child_key.as_ref()], &crate::ID);
+            require_keys_eq!(vault_key, child_vault.key(), SwapError::SeedMismatch);
+
+            create_pda(
+                &rent_sponsor,
+                child_account,
+                &system_program,
+                SWAP_ACCOUNT_SIZE,
+                &[&seeds[..], &[&[bump]]].concat(),
+            )?;
+            create_pda(
+                &rent_sponsor,
+                child_vault,
+                &system_program,
+                SWAP_VAULT_SIZE,
+                &[VAULT_SEED, child_key.as_ref(), &[vault_bump]],
+            )?;
+            let child = swap.child(split, bump, vault_bump, index as u64);
+            child.try_serialize(&mut &mut child_account.try_borrow_mut_data()?[..])?;
+            SwapVault {}.try_serialize(&mut &mut child_vault.try_borrow_mut_data()?[..])?;
+            ctx.accounts.vault.sub_lamports(split.swap_amount)?;
+            child_vault.add_lamports(split.swap_amount)?;
+
+            if !child.suppress_events {
+                emit!(Initiated {
+                    redeemer: child.redeemer,
+                    refundee: child.refundee,
+                    secret_hash: child.secret_hash,
+                    swap_amount: child.swap_amount,
+                    timelock: child.timelock,
+                    destination_data: None,
+                    destination_commitment: child.destination_commitment,
+                    funder: child.funder,
+                    init_fee: 0,
+                    hash_kind: child.hash_kind(),
+                    swap_id: child.swap_id,
+                });
+            }
+        }
+        // The children take the place of the swap, which is neither redeemed nor refunded
+        if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+            stats.initiated = stats.initiated.saturating_add(splits.len() as u64 - 1);
+        }
+
+        Ok(())
+    }
+
+    /// Merges two swaps sharing their secret hash, redeemer, refundee and timelock into one,
+    /// e.g. to consolidate positions, failing with `SwapError::IncompatibleSwaps` otherwise.
+    /// As the swap amount is one of the PDA seeds, the merged swap is created at the PDA derived
+    /// from the total of their swap amounts, passed as `merged_swap_account` and `merged_vault`,
+    /// and both swaps are closed. The merged swap keeps the terms of `swap_account`, expiring
+    /// at the later of their expiry slots. Refer `SwapAccount::merged`.
+    /// As this redirects the funds, only the funder of both swaps, as their cancel authority,
+    /// can merge them, and since redeems close the swaps, only before either is redeemed.
+    /// The rent sponsor of `swap_account` pays the rent of the merged swap, and as such must sign.
+    /// Swaps with a `distribution` or a `price_oracle` cannot be merged. `Merged` is emitted.
+    /// If the `RefundQueue` is passed as `refund_queue`, both swaps are removed from it, and the
+    /// merged swap is registered in their place if either was registered.
+    pub fn merge_swaps(ctx: Context<MergeSwaps>) -> Result<()> {
+        log_compute_units!();
+        let swap = &ctx.accounts.swap_account;
+        let other = &ctx.accounts.other_swap_account;
+        let current_slot = current_clock()?.slot;
+        swap.check_clock(current_slot)?;
+        other.check_clock(current_slot)?;
+
+        let swap_amount = swap
+            .swap_amount
+            .checked_add(other.swap_amount)
+            .ok_or(SwapError::IncompatibleSwaps)?;
+        let amount_seed = swap.seed_amount_for(swap_amount).to_le_bytes();
+        let timelock_seed = swap.timelock_seed().to_le_bytes();
+        let seeds = [
+            swap.redeemer.as_ref(),
+            swap.refundee.as_ref(),
+            &swap.secret_hash,
+            &amount_seed,
+            &timelock_seed,
+        ];
+        let (merged_key, bump) = Pubkey::find_program_address(&seeds, &crate::ID);
+        require_keys_eq!(
+            merged_key,
+            ctx.accounts.merged_swap_account.key(),
+            SwapError::SeedMismatch
+        );
+        let (vault_key, vault_bump) =
+            Pubkey::find_program_address(&[VAULT_SEED, merged_key.as_ref()], &crate::ID);
+        require_keys_eq!(
+            vault_key,
+            ctx.accounts.merged_vault.key(),
+            SwapError::SeedMismatch
+        );
+        let merged = swap.merged(other, bump, vault_bump)?;
+
+        let rent_sponsor = ctx.accounts.rent_sponsor.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let merged_swap_account = ctx.accounts.merged_swap_account.to_account_info();
+        let merged_vault = ctx.accounts.merged_vault.to_account_info();
+        create_pda(
+            &rent_sponsor,
+            &merged_swap_account,
+            &system_program,
+            SWAP_ACCOUNT_SIZE,
+            &[&seeds[..], &[&[bump]]].concat(),
+        )?;
+        create_pda(
+            &rent_sponsor,
+            &merged_vault,
+            &system_program,
+            SWAP_VAULT_SIZE,
+            &[VAULT_SEED, merged_key.as_ref(), &[vault_bump]],
+        )?;
+        merged.try_serialize(&mut &mut merged_swap_account.try_borrow_mut_data()?[..])?;
+        SwapVault {}.try_serialize(&mut &mut merged_vault.try_borrow_mut_data()?[..])?;
+        ctx.accounts
+            .vault
+            .sub_lamports(ctx.accounts.swap_account.swap_amount)?;
+        ctx.accounts
+            .other_vault
+            .sub_lamports(ctx.accounts.other_swap_account.swap_amount)?;
+        merged_vault.add_lamports(swap_amount)?;
+        if let Some(refund_queue) = ctx.accounts.refund_queue.as_deref_mut() {
+            let swap_registered = refund_queue.deregister(&ctx.accounts.swap_account.key());
+            let other_registered = refund_queue.deregister(&ctx.accounts.other_swap_account.key());
+            if swap_registered || other_registered {
+                refund_queue.register(merged_key, merged.expiry_slot)?;
+            }
+        }
+        // The merged swap takes the place of both swaps, which are neither redeemed nor refunded
+        if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+            stats.initiated = stats.initiated.saturating_sub(1);
+        }
+
+        if !merged.suppress_events {
+            emit!(Merged {
+                redeemer: merged.redeemer,
+                refundee: merged.refundee,
+                secret_hash: merged.secret_hash,
+                swap_amount,
+                timelock: merged.timelock,
+                merged_swap_ids: [
+                    ctx.accounts.swap_account.swap_id,
+                    ctx.accounts.other_swap_account.swap_id,
+                ],
+                swap_id: merged.swap_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Pushes the expiry slot of the swap back by `extension` slots, with the consent of both
+    /// the redeemer and the refundee. As such, both their signatures are required.
+    /// Extensions are capped at the `max_extension_slots` chosen at initiation, counted from
+    /// the original expiry slot, and fail with `SwapError::ExtensionCapExceeded` beyond it.
+    /// The `timelock` of the swap, being one of its seeds, is left unchanged.
+    pub fn extend_timelock(ctx: Context<ExtendTimelock>, extension: u64) -> Result<()> {
+        log_compute_units!();
+        let swap_account = &mut ctx.accounts.swap_account;
+        swap_account.expiry_slot = swap_account.extended_expiry_slot(extension)?;
+
+        if !swap_account.suppress_events {
+            emit!(TimelockExtended {
+                redeemer: swap_account.redeemer,
+                refundee: swap_account.refundee,
+                secret_hash: swap_account.secret_hash,
+                swap_amount: swap_account.swap_amount,
+                timelock: swap_account.timelock,
+                expiry_slot: swap_account.expiry_slot,
+                swap_id: swap_account.swap_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The redeemer commits to rebating `rebate_bps` basis points of the payout of the swap to
+    /// `rebate_recipient`, e.g. to win the swap in a solver competition. As such, the
+    /// redeemer's signature is required. The recipient must be the funder of the swap, and
+    /// the rebate is paid out to them on redeem, with the rest going to the redeemer.
+    /// As a commitment, the rebate can only ever be raised, up to `TOTAL_BPS`, otherwise
+    /// this fails with `SwapError::InvalidRebate`.
+    pub fn commit_rebate(
+        ctx: Context<CommitRebate>,
+        rebate_bps: u16,
+        rebate_recipient: Pubkey,
+    ) -> Result<()> {
+        log_compute_units!();
+        let swap_account = &mut ctx.accounts.swap_account;
+        require_keys_eq!(
+            rebate_recipient,
+            swap_account.funder,
+            SwapError::InvalidFunder
+        );
+        require!(
+            (swap_account.rebate_bps..=TOTAL_BPS).contains(&rebate_bps),
+            SwapError::InvalidRebate
+        );
+        swap_account.rebate_bps = rebate_bps;
+        swap_account.rebate_recipient = Some(rebate_recipient);
+
+        if !swap_account.suppress_events {
+            emit!(RebateCommitted {
+                redeemer: swap_account.redeemer,
+                rebate_bps,
+                rebate_recipient,
+                swap_id: swap_account.swap_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Transfers the redeem rights of the swap to `new_redeemer`, who will then receive the
+    /// funds upon redeem. As such, the signature of the current redeemer is required.
+    /// If the current redeemer is also the instant refund authority, that authority is
+    /// transferred along, so that the previous redeemer retains no say over the swap.
+    /// The address of the swap remains derived from its original redeemer.
+    pub fn transfer_redeem_rights(
+        ctx: Context<TransferRedeemRights>,
+        new_redeemer: Pubkey,
+    ) -> Result<()> {
+        log_compute_units!();
+        let swap_account = &mut ctx.accounts.swap_account;
+        let old_redeemer = swap_account.redeemer;
+        if swap_account.instant_refund_authority == old_redeemer {
+            swap_account.instant_refund_authority = new_redeemer;
+        }
+        swap_account.redeemer = new_redeemer;
+
+        if !swap_account.suppress_events {
+            emit!(RedeemRightsTransferred {
+                old_redeemer,
+                new_redeemer,
+                refundee: swap_account.refundee,
+                secret_hash: swap_account.secret_hash,
+                swap_amount: swap_account.swap_amount,
+                timelock: swap_account.timelock,
+                swap_id: swap_account.swap_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Grows a swap account initialized with an older, smaller layout of `SwapAccount` to the
+    /// current `SWAP_ACCOUNT_SIZE`, so that it can be used with the current program.
+    /// The fields added since are initialized as described in `migrate_swap_account`.
+    /// Swaps initiated before vaults existed escrow the swap amount in the swap account itself,
+    /// so their vault is created, and the swap amount moved into it.
+    /// Anyone can migrate a swap, e.g. its redeemer or refundee, so that neither depends on
+    /// the rent sponsor to reach their funds. The payer, who signs, covers the additional rent,
+    /// which is returned to the rent sponsor along with the rest of the rent on completion.
+    /// It fails with `SwapError::InvalidRealloc` for accounts already of the current size.
+    pub fn realloc_swap(ctx: Context<ReallocSwap>) -> Result<()> {
+        log_compute_units!();
+        let swap_account = ctx.accounts.swap_account.to_account_info();
+        check_swap_account_info(&swap_account)?;
+
+        let old_size = swap_account.data_len();
+        let new_size = SWAP_ACCOUNT_SIZE;
+        require_gt!(new_size, old_size, SwapError::InvalidRealloc);
+        let mut swap = migrate_swap_account(&swap_account.try_borrow_data()?)?;
+        require_keys_eq!(
+            swap.derive_address()?,
+            swap_account.key(),
+            SwapError::SeedMismatch
+        );
+
+        let vault = ctx.accounts.vault.to_account_info();
+        if vault.owner != &crate::ID {
+            create_pda(
+                &ctx.accounts.payer,
+                &vault,
+                &ctx.accounts.system_program,
+                SWAP_VAULT_SIZE,
+                &[VAULT_SEED, swap_account.key.as_ref(), &[ctx.bumps.vault]],
+            )?;
+            SwapVault {}.try_serialize(&mut &mut vault.try_borrow_mut_data()?[..])?;
+            swap_account.sub_lamports(swap.swap_amount)?;
+            vault.add_lamports(swap.swap_amount)?;
+        }
+        swap.vault_bump = ctx.bumps.vault;
+
+        let rent_delta = Rent::get()?
+            .minimum_balance(new_size)
+            .saturating_sub(swap_account.lamports());
+        if rent_delta > 0 {
+            let transfer_context = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: swap_account.clone(),
+                },
+            );
+            system_program::transfer(transfer_context, rent_delta)?;
+        }
+        swap_account.realloc(new_size, true)?;
+        swap.try_serialize(&mut &mut swap_account.try_borrow_mut_data()?[..])?;
+
+        if !swap.suppress_events {
+            emit!(Realloced {
+                redeemer: swap.redeemer,
+                refundee: swap.refundee,
+                secret_hash: swap.secret_hash,
+                swap_amount: swap.swap_amount,
+                timelock: swap.timelock,
+                old_size: old_size as u64,
+                new_size: new_size as u64,
+                swap_id: swap.swap_id,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Verifies that `secret` is the preimage of the secret hash of this swap, without any
+    /// state changes. This is intended to be simulated by clients before submitting a redeem,
+    /// to avoid failed redeems. It fails with `SwapError::InvalidSecret` otherwise.
+    pub fn check_secret(ctx: Context<CheckSecret>, secret: [u8; 32]) -> Result<()> {
+        log_compute_units!();
+        ctx.accounts
+            .swap_account
+            .check_hash_kind(HashKind::Sha256)?;
+        verify_secret(&secret, &ctx.accounts.swap_account.secret_hash)?;
+
+        Ok(())
+    }
+
+    /// Verifies that `secret` is the preimage of the secret hash of this swap, and records it
+    /// in the `RevealedSecret` PDA of the secret hash, emitting `SecretRevealed`. No funds are
+    /// moved and the swap stays active, so that relayers completing the opposite leg of a swap
+    /// can propagate its secret without being its redeemer. Anyone can call it, paying the
+    /// rent of the `RevealedSecret`. It fails with `SwapError::InvalidSecret` for other secrets.
+    pub fn record_secret(ctx: Context<RecordSecret>, secret: [u8; 32]) -> Result<()> {
+        log_compute_units!();
+        let swap_account = &ctx.accounts.swap_account;
+        swap_account.check_hash_kind(HashKind::Sha256)?;
+        verify_secret(&secret, &swap_account.secret_hash)?;
+
+        let revealed_slot = current_clock()?.slot;
+        *ctx.accounts.revealed_secret = RevealedSecret {
+            secret,
+            revealed_slot,
+            bump: ctx.bumps.revealed_secret,
+        };
+
+        emit!(SecretRevealed {
+            redeemer: swap_account.redeemer,
+            refundee: swap_account.refundee,
+            secret_hash: swap_account.secret_hash,
+            secret,
+            swap_amount: swap_account.swap_amount,
+            timelock: swap_account.timelock,
+            swap_id: swap_account.swap_id,
         });
 
         Ok(())
     }
+
+    /// Emits `ExpiryApproaching` for a swap within `EXPIRY_SIGNAL_WINDOW` slots of its
+    /// expiry slot, or past it. This instruction does not require any signatures, so that
+    /// keepers triggering refunds at expiry can broadcast their intent through it.
+    /// It fails with `SwapError::TooEarlyForSignal` before the window.
+    pub fn signal_expiry_approaching(ctx: Context<SignalExpiryApproaching>) -> Result<()> {
+        log_compute_units!();
+        let swap_account = &ctx.accounts.swap_account;
+        let current_slot = current_clock()?.slot;
+        require!(
+            swap_account.is_expiry_approaching(current_slot),
+            SwapError::TooEarlyForSignal
+        );
+
+        emit!(ExpiryApproaching {
+            redeemer: swap_account.redeemer,
+            refundee: swap_account.refundee,
+            secret_hash: swap_account.secret_hash,
+            swap_amount: swap_account.swap_amount,
+            timelock: swap_account.timelock,
+            expiry_slot: swap_account.expiry_slot,
+            swap_id: swap_account.swap_id,
+        });
+
+        Ok(())
+    }
+
+    /// Emits `SwapSnapshot`, holding the full state of the swap along with the current slot,
+    /// for archival, e.g. to rebuild indexes by replaying snapshots rather than scanning
+    /// accounts. This instruction does not require any signatures, and leaves the swap as is.
+    pub fn snapshot(ctx: Context<Snapshot>) -> Result<()> {
+        log_compute_units!();
+        emit!(SwapSnapshot {
+            swap_account: ctx.accounts.swap_account.key(),
+            swap: (*ctx.accounts.swap_account).clone(),
+            slot: current_clock()?.slot,
+        });
+
+        Ok(())
+    }
+
+    /// Emits `Version`, holding the version of the program and the flags of the optional
+    /// features it was built with, so that clients can adapt to the deployment they talk to.
+    /// This instruction takes no accounts besides the system program, nor any signatures.
+    pub fn program_version(_ctx: Context<ProgramVersion>) -> Result<()> {
+        log_compute_units!();
+        emit!(Version {
+            version: PROGRAM_VERSION.to_string(),
+            feature_flags: FEATURE_FLAGS,
+        });
+
+        Ok(())
+    }
+
+    /// Succeeds only if no swap has been initiated with the given parameters, i.e. if the
+    /// PDA derived from them holds no account. This is intended as a guard within composed
+    /// transactions, so that the absence of the swap is asserted atomically with the actions
+    /// depending on it, rather than checked beforehand by the client.
+    #[allow(unused_variables)]
+    pub fn assert_not_initialized(
+        ctx: Context<AssertNotInitialized>,
+        redeemer: Pubkey,
+        refundee: Pubkey,
+        secret_hash: [u8; 32],
+        swap_amount: u64,
+        timelock: u64,
+    ) -> Result<()> {
+        log_compute_units!();
+        let swap_account = &ctx.accounts.swap_account;
+        require!(
+            swap_account.data_is_empty() && swap_account.owner == &system_program::ID,
+            SwapError::SwapAlreadyInitialized
+        );
+
+        Ok(())
+    }
+
+    /// Creates the `ProtocolConfig`, with `max_tvl_lamports` as the cap on the total value
+    /// locked across all active swaps. This can only be called once, and only by the upgrade
+    /// authority of the program, who becomes the admin of the config. Until then, swaps are
+    /// initiated without a cap nor a rate limit. A `max_tvl_lamports` of `u64::MAX` disables
+    /// the cap, under which initiates only read the config, and swaps are completed without it.
+    pub fn init_protocol_config(
+        ctx: Context<InitProtocolConfig>,
+        max_tvl_lamports: u64,
+    ) -> Result<()> {
+        log_compute_units!();
+        *ctx.accounts.protocol_config = ProtocolConfig {
+            admin: ctx.accounts.admin.key(),
+            max_tvl_lamports,
+            current_tvl: 0,
+            bump: ctx.bumps.protocol_config,
+            ..Default::default()
+        };
+
+        Ok(())
+    }
+
+    /// Updates the cap on the total value locked across all active swaps. This requires the
+    /// signature of the admin of the `ProtocolConfig`. A cap below the current total value
+    /// locked blocks new initiates, while redeems and refunds of active swaps are unaffected.
+    /// Only swaps initiated under a cap are counted, such that swaps initiated before one was
+    /// configured, or without passing the config, never are.
+    pub fn set_max_tvl(ctx: Context<UpdateProtocolConfig>, max_tvl_lamports: u64) -> Result<()> {
+        log_compute_units!();
+        ctx.accounts.protocol_config.max_tvl_lamports = max_tvl_lamports;
+
+        Ok(())
+    }
+
+    /// Limits each redeemer to `max_redeems_per_window` redeems within any window of
+    /// `window_slots` slots, tracked through their `RateLimit`. This requires the signature of
+    /// the admin of the `ProtocolConfig`. A `max_redeems_per_window` of 0 disables the limit,
+    /// which is the default. The limit in force on initiation is recorded by each swap, whose
+    /// redeem must then pass the `RateLimit` of the redeemer if signed by the redeemer.
+    /// As redeems are permissionless, only those the redeemer signed are counted and limited,
+    /// so that no one can use up the window of a redeemer, e.g. by redeeming dust swaps to them,
+    /// and block the redeem of their swaps until expiry, once the secret is out. Redeems
+    /// triggered by others, e.g. relayers, are never limited.
+    pub fn set_rate_limit(
+        ctx: Context<UpdateProtocolConfig>,
+        max_redeems_per_window: u64,
+        window_slots: u64,
+    ) -> Result<()> {
+        log_compute_units!();
+        let protocol_config = &mut ctx.accounts.protocol_config;
+        protocol_config.max_redeems_per_window = max_redeems_per_window;
+        protocol_config.rate_limit_window_slots = window_slots;
+
+        Ok(())
+    }
+
+    /// Creates the `RateLimit` of `redeemer`, which must exist before swaps can be redeemed
+    /// to them while a rate limit is in force. Anyone can call it, paying its rent.
+    pub fn init_rate_limit(ctx: Context<InitRateLimit>, redeemer: Pubkey) -> Result<()> {
+        log_compute_units!();
+        let rate_limit = &mut ctx.accounts.rate_limit;
+        rate_limit.redeemer = redeemer;
+        rate_limit.bump = ctx.bumps.rate_limit;
+
+        Ok(())
+    }
+
+    /// Creates the `WithdrawalEscrow` of the redeemer, holding the funds of swaps redeemed to
+    /// them through `redeem_to_escrow` for `delay_slots` slots after each such redeem.
+    /// As such, the redeemer's signature is required, and they pay its rent.
+    pub fn init_withdrawal_escrow(
+        ctx: Context<InitWithdrawalEscrow>,
+        delay_slots: u64,
+    ) -> Result<()> {
+        log_compute_units!();
+        let withdrawal_escrow = &mut ctx.accounts.withdrawal_escrow;
+        withdrawal_escrow.redeemer = ctx.accounts.redeemer.key();
+        withdrawal_escrow.delay_slots = delay_slots;
+        withdrawal_escrow.bump = ctx.bumps.withdrawal_escrow;
+
+        Ok(())
+    }
+
+    /// Pays out all the funds held in the `WithdrawalEscrow` of the redeemer to them, once the
+    /// withdrawal delay has passed since the last redeem into it. As such, the redeemer's
+    /// signature is required. It fails with `SwapError::WithdrawalTooEarly` before then.
+    pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+        log_compute_units!();
+        let amount = ctx
+            .accounts
+            .withdrawal_escrow
+            .withdraw(current_clock()?.slot)?;
+        ctx.accounts.withdrawal_escrow.sub_lamports(amount)?;
+        ctx.accounts.redeemer.add_lamports(amount)?;
+
+        Ok(())
+    }
+
+    /// Creates the `Stats`, counting swaps from then on, through the instructions passing it.
+    /// Anyone can call it once, paying its rent.
+    pub fn init_stats(ctx: Context<InitStats>) -> Result<()> {
+        log_compute_units!();
+        ctx.accounts.stats.bump = ctx.bumps.stats;
+
+        Ok(())
+    }
+
+    /// Creates the `RefundQueue`, which swaps initiated with `queue_refund` are registered into.
+    /// This must be called once before any such swap can be initiated. Anyone can call it,
+    /// paying its rent.
+    pub fn init_refund_queue(ctx: Context<InitRefundQueue>) -> Result<()> {
+        log_compute_units!();
+        ctx.accounts.refund_queue.bump = ctx.bumps.refund_queue;
+
+        Ok(())
+    }
+
+    /// Removes a swap which is no longer active from the `RefundQueue`. Swaps redeemed or
+    /// refunded, or merged, with the queue passed are removed right away, whereas those settled
+    /// otherwise, e.g. instantly refunded, declined or split, remain until removed through this.
+    /// Anyone can call it, e.g. keepers coming across such swaps when scanning the queue.
+    /// It fails with `SwapError::SwapAlreadyActive` if the swap is still active, and does
+    /// nothing if it is not in the queue.
+    pub fn deregister(ctx: Context<Deregister>) -> Result<()> {
+        log_compute_units!();
+        // Swap accounts are owned by this program from initiation until they are closed
+        require!(
+            ctx.accounts.swap_account.owner != &crate::ID,
+            SwapError::SwapAlreadyActive
+        );
+        ctx.accounts
+            .refund_queue
+            .deregister(&ctx.accounts.swap_account.key());
+
+        Ok(())
+    }
+
+    /// Processes the instructions matching no discriminator. With the `lite-redeem` feature,
+    /// an instruction holding exactly `LITE_REDEEM_TAG` followed by 32 bytes is taken as a
+    /// discriminator-free redeem with those as the secret. Refer `lite_redeem`. Such data only
+    /// gets dispatched elsewhere if it starts with the discriminator of an instruction, which is
+    /// as unlikely as guessing the secret. Anything else fails with
+    /// `ErrorCode::InstructionFallbackNotFound`, as without a fallback.
+    pub fn fallback<'info>(
+        program_id: &Pubkey,
+        accounts: &'info [AccountInfo<'info>],
+        data: &[u8],
+    ) -> Result<()> {
+        #[cfg(feature = "lite-redeem")]
+        if let Some((&LITE_REDEEM_TAG, secret)) = data.split_first() {
+            if let Ok(secret) = <[u8; 32]>::try_from(secret) {
+                return lite_redeem(program_id, accounts, secret);
+            }
+        }
+        #[cfg(not(feature = "lite-redeem"))]
+        let _ = (program_id, accounts, data);
+        err!(anchor_lang::error::ErrorCode::InstructionFallbackNotFound)
+    }
 }
 
-/// Stores the state information of the atomic swap on-chain
-#[account]
-#[derive(InitSpace)]
-pub struct SwapAccount {
-    /// The exact slot after which (non-instant) refunds are allowed
-    expiry_slot: u64,
-    /// The bump that was used by the program to derive this PDA.
-    /// Storing this makes later verifications less expensive.
-    bump: u8,
+/// Redeems the swap exactly like `redeem` from a discriminator-free instruction holding
+/// nothing but `LITE_REDEEM_TAG` and the 32-byte secret. This is exposed with the `lite-redeem`
+/// feature, for redeemers bound by tight compute budgets, and is dispatched through the
+/// fallback of the program. Refer `solana_native_swaps::fallback`.
+/// `accounts` must be exactly the swap account, the vault, the redeemer and the rent sponsor,
+/// which are validated like the `Redeem` accounts, except that the seeds of the swap account
+/// are left to `Redeem::pay_out`, which checks them anyway. This saves the instruction
+/// decoding and a PDA derivation over `redeem`. Swaps requiring any optional account of
+/// `Redeem` fail as a `redeem` without that account would.
+#[cfg(feature = "lite-redeem")]
+fn lite_redeem<'info>(
+    program_id: &Pubkey,
+    accounts: &'info [AccountInfo<'info>],
+    secret: [u8; 32],
+) -> Result<()> {
+    log_compute_units!();
+    let [swap_account, vault, redeemer, rent_sponsor] = accounts else {
+        return err!(anchor_lang::error::ErrorCode::AccountNotEnoughKeys);
+    };
+    let swap_account = Account::<SwapAccount>::try_from(swap_account)?;
+    let vault = Account::<SwapVault>::try_from(vault)?;
+    require!(
+        swap_account.to_account_info().is_writable && vault.to_account_info().is_writable,
+        anchor_lang::error::ErrorCode::ConstraintMut
+    );
+    require!(redeemer.is_writable, SwapError::RedeemerNotWritable);
+    require_keys_eq!(
+        redeemer.key(),
+        swap_account.redeemer,
+        SwapError::InvalidRedeemer
+    );
+    require!(rent_sponsor.is_writable, SwapError::RentSponsorNotWritable);
+    require_keys_eq!(
+        rent_sponsor.key(),
+        swap_account.rent_sponsor,
+        SwapError::InvalidRentSponsor
+    );
+    let expected_vault = Pubkey::create_program_address(
+        &[
+            VAULT_SEED,
+            swap_account.key().as_ref(),
+            &[swap_account.vault_bump],
+        ],
+        program_id,
+    )
+    .map_err(|_| error!(SwapError::SeedMismatch))?;
+    require_keys_eq!(vault.key(), expected_vault, SwapError::SeedMismatch);
 
-    /// The redeemer of the atomic swap
-    redeemer: Pubkey,
-    /// The entity that is eligible to receive a refund in the atomic swap
-    refundee: Pubkey,
-    /// The secret hash associated with the atomic swap
-    secret_hash: [u8; 32],
-    /// The quantity of native SOL to be transferred through this atomic swap in base units (aka lamports)
-    swap_amount: u64,
-    /// The entity that paid the rent fees for the creation of this PDA.
-    /// This will be referenced during the refund of the same upon closing this PDA.
-    rent_sponsor: Pubkey,
-    /// The number of slots after which (non-instant) refunds are allowed.
-    /// This is stored so that it can later be verified through events.
-    timelock: u64,
+    let mut redeem = Redeem {
+        swap_account,
+        vault,
+        redeemer: redeemer.clone(),
+        rent_sponsor: rent_sponsor.clone(),
+        protocol_config: None,
+        stats: None,
+        relayer: None,
+        rate_limit: None,
+        price_oracle: None,
+        refundee: None,
+        rebate_recipient: None,
+        withdrawal_escrow: None,
+        funder: None,
+        refund_queue: None,
+    };
+    solana_native_swaps::redeem(
+        Context::new(program_id, &mut redeem, &[], RedeemBumps::default()),
+        secret,
+    )?;
+    // Closes the swap account and the vault to the rent sponsor, as Anchor does for `redeem`
+    redeem.exit(program_id)
 }
 
-#[derive(Accounts)]
-// The parameters must have the exact name and order as specified in the underlying function
-// to avoid "seed constraint violation" errors.
-// Refer: https://www.anchor-lang.com/docs/references/account-constraints#instruction-attribute
-#[instruction(redeemer: Pubkey, refundee: Pubkey, secret_hash: [u8; 32], swap_amount: u64, timelock: u64)]
-pub struct Initiate<'info> {
-    /// A PDA that maintains the on-chain state of the atomic swap throughout its lifecycle.
-    /// It also serves as the "vault" for this swap, by escrowing the SOL involved in this swap.
-    /// The choice of seeds is to make the already expensive possibility of frontrunning, more expensive.
-    /// This PDA will be deleted upon completion of the swap and the resulting rent would be returned
-    /// to the rent sponsor.
-    #[account(
-        init,
-        payer = rent_sponsor,
-        seeds = [
+/// Derives the address of the swap account, along with its bump, for a swap with the given
+/// parameters. Clients can check that no account exists at this address to establish
+/// that such a swap was never initiated, or has since been completed.
+/// For swaps initiated through `initiate_at`, `timelock` is their expiry slot.
+pub fn find_swap_address(
+    redeemer: &Pubkey,
+    refundee: &Pubkey,
+    secret_hash: &[u8; 32],
+    swap_amount: u64,
+    timelock: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
             redeemer.as_ref(),
             refundee.as_ref(),
-            &secret_hash,
-            &swap_amount.to_le_bytes(),
+            secret_hash,
+            &seed_amount(swap_amount).to_le_bytes(),
             &timelock.to_le_bytes(),
         ],
-        bump,
-        space = ANCHOR_DISCRIMINATOR + SwapAccount::INIT_SPACE,
-    )]
-    pub swap_account: Account<'info, SwapAccount>,
+        &crate::ID,
+    )
+}
 
-    /// The party that deposits the funds to be involved in the atomic swap.
-    /// They must sign this transaction.
-    #[account(mut)]
-    pub funder: Signer<'info>,
+/// Checks that `expected`, the address a client is about to pass as the swap account, is the
+/// PDA derived from the stored parameters and bump of `account` under `program_id`.
+/// Clients can run this before submitting a redeem or refund, rather than running into a
+/// seed constraint violation. It fails with `SwapError::SeedMismatch` otherwise.
+pub fn validate_swap_seeds(
+    account: &SwapAccount,
+    expected: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<()> {
+    let (address, bump) = Pubkey::find_program_address(
+        &[
+            account.original_redeemer.as_ref(),
+            account.refundee.as_ref(),
+            &account.secret_hash,
+            &account.amount_seed().to_le_bytes(),
+            &account.timelock_seed().to_le_bytes(),
+        ],
+        program_id,
+    );
+    require_keys_eq!(address, *expected, SwapError::SeedMismatch);
+    require_eq!(bump, account.bump, SwapError::SeedMismatch);
+    Ok(())
+}
 
-    /// Any entity that pays the PDA rent.
-    /// Upon completion of the swap, the PDA rent refund resulting from the
-    /// deletion of `swap_account` will be refunded to this address.
-    #[account(mut)]
-    pub rent_sponsor: Signer<'info>,
+/// A succinct record of a settled (i.e. redeemed) swap, for verification on other chains.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Debug)]
+pub struct SettlementReceipt {
+    /// The redeemer that received the funds
+    pub redeemer: Pubkey,
+    /// The swap amount locked on initiation, as agreed with the counterparty. This is not
+    /// the amount paid out to the redeemer, which differs for e.g. value-pegged, vesting or
+    /// rebated swaps, and is emitted in `Redeemed` as `redeemer_amount`.
+    pub swap_amount: u64,
+    /// The secret revealed by the redeem
+    pub secret: [u8; 32],
+    /// The slot at which the swap was redeemed
+    pub slot: u64,
+}
 
-    pub system_program: Program<'info, System>,
+impl SettlementReceipt {
+    /// The canonical hash of this receipt, which is stable across versions of this program:
+    /// `sha256(redeemer ‖ swap_amount ‖ secret ‖ slot)`, where `redeemer` and `secret` are
+    /// 32 bytes each, and `swap_amount` and `slot` are 8-byte big-endian integers.
+    /// The encoding matches `abi.encodePacked(bytes32, uint64, bytes32, uint64)`, so that
+    /// EVM verifiers can recompute it with the SHA-256 precompile.
+    pub fn hash(&self) -> [u8; 32] {
+        hash::hashv(&[
+            self.redeemer.as_ref(),
+            &self.swap_amount.to_be_bytes(),
+            &self.secret,
+            &self.slot.to_be_bytes(),
+        ])
+        .to_bytes()
+    }
 }
 
-#[derive(Accounts)]
-pub struct Redeem<'info> {
-    /// The PDA holding the state information of the atomic swap.
-    #[account(
-        mut,
-        seeds = [
-            swap_account.redeemer.as_ref(),
-            swap_account.refundee.key().as_ref(),
-            &swap_account.secret_hash,
-            &swap_account.swap_amount.to_le_bytes(),
-            &swap_account.timelock.to_le_bytes(),
-        ],
-        bump = swap_account.bump,
-        close = rent_sponsor,
-    )]
-    pub swap_account: Account<'info, SwapAccount>,
+/// Computes the identifier of a swap, a compact join key across all of its events, which
+/// clients can reproduce before the swap is initiated:
+/// `sha256(redeemer ‖ refundee ‖ secret_hash ‖ swap_amount ‖ timelock ‖ nonce)`, where
+/// `redeemer`, `refundee` and `secret_hash` are 32 bytes each, and `swap_amount`, `timelock`
+/// and `nonce` are 8-byte big-endian integers, as in `SettlementReceipt::hash`.
+/// `redeemer` is the redeemer at initiation, so that the identifier is immutable.
+/// For swaps initiated through `initiate_at`, `timelock` is their expiry slot.
+pub fn compute_swap_id(
+    redeemer: &Pubkey,
+    refundee: &Pubkey,
+    secret_hash: &[u8; 32],
+    swap_amount: u64,
+    timelock: u64,
+    nonce: u64,
+) -> [u8; 32] {
+    hash::hashv(&[
+        redeemer.as_ref(),
+        refundee.as_ref(),
+        secret_hash,
+        &swap_amount.to_be_bytes(),
+        &timelock.to_be_bytes(),
+        &nonce.to_be_bytes(),
+    ])
+    .to_bytes()
+}
 
-    /// CHECK: Verifying the redeemer
-    #[account(mut, address = swap_account.redeemer @ SwapError::InvalidRedeemer)]
-    pub redeemer: AccountInfo<'info>,
+/// Creates the PDA `account` of `space` bytes owned by this program, signed for by `seeds`,
+/// with `payer` paying its rent. Like `init`, this also creates accounts already holding
+/// lamports, topping them up to the rent exemption, so that prefunding cannot block creation.
+fn create_pda<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let signer_seeds = &[seeds];
+    let rent = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        let create_context = CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::CreateAccount {
+                from: payer.clone(),
+                to: account.clone(),
+            },
+            signer_seeds,
+        );
+        return system_program::create_account(create_context, rent, space as u64, &crate::ID);
+    }
 
-    /// CHECK: Rent sponsor's address for refunding PDA rent
-    #[account(mut, address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor)]
-    pub rent_sponsor: AccountInfo<'info>,
+    let top_up = rent.saturating_sub(account.lamports());
+    if top_up > 0 {
+        let transfer_context = CpiContext::new(
+            system_program.clone(),
+            system_program::Transfer {
+                from: payer.clone(),
+                to: account.clone(),
+            },
+        );
+        system_program::transfer(transfer_context, top_up)?;
+    }
+    let allocate_context = CpiContext::new_with_signer(
+        system_program.clone(),
+        system_program::Allocate {
+            account_to_allocate: account.clone(),
+        },
+        signer_seeds,
+    );
+    system_program::allocate(allocate_context, space as u64)?;
+    let assign_context = CpiContext::new_with_signer(
+        system_program.clone(),
+        system_program::Assign {
+            account_to_assign: account.clone(),
+        },
+        signer_seeds,
+    );
+    system_program::assign(assign_context, &crate::ID)
 }
 
-#[derive(Accounts)]
-pub struct Refund<'info> {
-    /// The PDA holding the state information of the atomic swap.
-    #[account(
-        mut,
-        seeds = [
-            swap_account.redeemer.as_ref(),
-            swap_account.refundee.key().as_ref(),
-            &swap_account.secret_hash,
-            &swap_account.swap_amount.to_le_bytes(),
-            &swap_account.timelock.to_le_bytes(),
-        ],
-        bump = swap_account.bump,
-        close = rent_sponsor,
-    )]
-    pub swap_account: Account<'info, SwapAccount>,
+/// Closes the wSOL token account of the funder to the funder, after checking that it holds
+/// at least `swap_amount`, so that the unwrapped lamports can fund the swap.
+fn unwrap_wsol(accounts: &Initiate, swap_amount: u64) -> Result<()> {
+    let funder = accounts.funder.key();
+    let (Some(wsol_account), Some(token_program)) =
+        (&accounts.wsol_account, &accounts.token_program)
+    else {
+        return err!(SwapError::InvalidWsolAccount);
+    };
+    require_keys_eq!(
+        *wsol_account.owner,
+        TOKEN_PROGRAM_ID,
+        SwapError::InvalidWsolAccount
+    );
+    let amount = wsol_amount(&wsol_account.try_borrow_data()?, &funder)
+        .ok_or(SwapError::InvalidWsolAccount)?;
+    require_gte!(amount, swap_amount, SwapError::InsufficientWsolBalance);
 
-    /// CHECK: The refundee of the swap.
-    #[account(mut, address = swap_account.refundee @ SwapError::InvalidRefundee)]
-    pub refundee: AccountInfo<'info>,
+    // `CloseAccount` of the SPL token program, which returns all lamports of a wSOL account,
+    // wrapped ones included, to the destination
+    let close_account = Instruction {
+        program_id: TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(wsol_account.key(), false),
+            AccountMeta::new(funder, false),
+            AccountMeta::new_readonly(funder, true),
+        ],
+        data: vec![9],
+    };
+    invoke(
+        &close_account,
+        &[
+            wsol_account.to_account_info(),
+            accounts.funder.to_account_info(),
+            token_program.to_account_info(),
+        ],
+    )?;
 
-    /// CHECK: Rent sponsor's address for refunding PDA rent
-    #[account(mut, address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor)]
-    pub rent_sponsor: AccountInfo<'info>,
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct InstantRefund<'info> {
-    /// The PDA holding the state information of the atomic swap.
-    #[account(
-        mut,
-        seeds = [
-            swap_account.redeemer.as_ref(),
-            swap_account.refundee.key().as_ref(),
-            &swap_account.secret_hash,
-            &swap_account.swap_amount.to_le_bytes(),
-            &swap_account.timelock.to_le_bytes(),
+/// Cancels the swap being amended on behalf of the funder, returning its swap amount to the
+/// funder and closing its accounts to the rent sponsor, and emits `Refunded` for it.
+fn cancel_amended_swap(accounts: &mut Initiate) -> Result<()> {
+    let (Some(old_swap_account), Some(old_vault)) =
+        (&accounts.old_swap_account, &accounts.old_vault)
+    else {
+        return err!(SwapError::InvalidAmendedSwap);
+    };
+    require_keys_eq!(
+        old_swap_account.derive_address()?,
+        old_swap_account.key(),
+        SwapError::SeedMismatch
+    );
+    check_swap_account_info(&old_swap_account.to_account_info())?;
+    old_swap_account.check_clock(current_clock()?.slot)?;
+    let vault = Pubkey::create_program_address(
+        &[
+            VAULT_SEED,
+            old_swap_account.key().as_ref(),
+            &[old_swap_account.vault_bump],
         ],
-        bump = swap_account.bump,
-        close = rent_sponsor,
-    )]
-    pub swap_account: Account<'info, SwapAccount>,
+        &crate::ID,
+    )
+    .map_err(|_| error!(SwapError::InvalidAmendedSwap))?;
+    require_keys_eq!(vault, old_vault.key(), SwapError::InvalidAmendedSwap);
+    require!(
+        old_swap_account.cancel_authority == Some(accounts.funder.key()),
+        SwapError::InvalidCancelAuthority
+    );
+    require_keys_eq!(
+        old_swap_account.rent_sponsor,
+        accounts.rent_sponsor.key(),
+        SwapError::InvalidRentSponsor
+    );
 
-    /// CHECK: The refundee of the swap.
-    #[account(mut, address = swap_account.refundee @ SwapError::InvalidRefundee)]
-    pub refundee: AccountInfo<'info>,
+    let SwapAccount {
+        refundee,
+        redeemer,
+        secret_hash,
+        swap_amount,
+        timelock,
+        suppress_events,
+        swap_id,
+        ..
+    } = **old_swap_account;
+    old_vault.sub_lamports(swap_amount)?;
+    accounts.funder.add_lamports(swap_amount)?;
+    if old_swap_account.tvl_locked {
+        let info = accounts.protocol_config.as_deref();
+        let mut protocol_config =
+            load_protocol_config(info)?.ok_or(SwapError::MissingProtocolConfig)?;
+        protocol_config.release(swap_amount);
+        store_protocol_config(info, &protocol_config)?;
+    }
+    if let Some(stats) = accounts.stats.as_deref_mut() {
+        stats.refunded = stats.refunded.saturating_add(1);
+    }
+    old_swap_account.close(accounts.rent_sponsor.to_account_info())?;
+    old_vault.close(accounts.rent_sponsor.to_account_info())?;
 
-    /// CHECK: The redeemer of the swap. They must sign this transaction.
-    #[account(address = swap_account.redeemer @ SwapError::InvalidRedeemer)]
-    pub redeemer: Signer<'info>,
+    if !suppress_events {
+        emit!(Refunded {
+            redeemer,
+            refundee,
+            secret_hash,
+            swap_amount,
+            timelock,
+            keeper: None,
+            keeper_reward: 0,
+            swap_id,
+        });
+    }
 
-    /// CHECK: Rent sponsor's address for PDA rent refund
-    #[account(mut, address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor)]
-    pub rent_sponsor: AccountInfo<'info>,
+    Ok(())
 }
 
-/// Represents the initiated state of the swap where the funder has deposited funds into the vault
-#[event]
-pub struct Initiated {
+/// Returns the amount held by an initialized SPL token account of wSOL owned by `owner`,
+/// given its data, or `None` for any other data.
+pub fn wsol_amount(data: &[u8], owner: &Pubkey) -> Option<u64> {
+    // The packed layout of an SPL token account: mint (32 bytes), owner (32 bytes),
+    // amount (8 bytes), delegate (36 bytes), state (1 byte) and further fields
+    if data.len() != 165 || data[108] != 1 {
+        return None;
+    }
+    let mint = Pubkey::try_from(&data[..32]).ok()?;
+    let account_owner = Pubkey::try_from(&data[32..64]).ok()?;
+    if mint != NATIVE_MINT || account_owner != *owner {
+        return None;
+    }
+    Some(u64::from_le_bytes(data[64..72].try_into().ok()?))
+}
+
+/// When a newly initiated swap expires
+enum Expiry {
+    /// After this many slots from initiation
+    Timelock(u64),
+    /// At this absolute slot, as initiated through `initiate_at`
+    Slot(u64),
+}
+
+/// Escrows the swap amount from the funder, records the state of a newly initiated swap
+/// and emits `Initiated`. This is shared by all the variants of `initiate`.
+#[allow(clippy::too_many_arguments)]
+fn initiate_swap(
+    ctx: &mut Context<Initiate>,
+    redeemer: Pubkey,
+    refundee: Pubkey,
+    secret_hash: [u8; 32],
+    swap_amount: u64,
+    expiry: Expiry,
+    destination_data: Option<Vec<u8>>,
+    destination_commitment: Option<[u8; 32]>,
+    adaptor_point: Option<[u8; 32]>,
+    options: SwapOptions,
+) -> Result<()> {
+    let funder = ctx.accounts.funder.key();
+    require!(
+        options.additional_secret_hashes.len() <= MAX_ADDITIONAL_SECRET_HASHES,
+        SwapError::TooManySecretHashes
+    );
+    validate_distribution(&options.distribution)?;
+    require!(
+        options.price_oracle.is_some() == options.price_feed_id.is_some(),
+        SwapError::InvalidOracle
+    );
+    require!(
+        adaptor_point.is_none() || options.secret_merkle_root.is_none(),
+        SwapError::ConflictingLocks
+    );
+    require_gte!(
+        max_keeper_reward(swap_amount),
+        options.keeper_reward_lamports,
+        SwapError::InvalidKeeperReward
+    );
+    // Checked ahead of the transfers, which would otherwise fail with a system program error.
+    // By now, the funder has already paid the transaction fees and, as the rent sponsor,
+    // the rent of the swap account and the vault. A funder sponsoring the rent is thus checked
+    // against the swap amount, the rent and the fees combined, without estimating either.
+    // Only a funder short of the rent itself fails earlier, upon `init`.
+    require!(
+        funder_balance_suffices(
+            ctx.accounts.funder.lamports(),
+            swap_amount.saturating_add(options.init_fee),
+            &Rent::get()?
+        ),
+        SwapError::InsufficientFunderBalance
+    );
+    let protocol_config = load_protocol_config(ctx.accounts.protocol_config.as_deref())?;
+    let tvl_locked = match protocol_config.clone() {
+        Some(mut protocol_config) if protocol_config.is_capped() => {
+            protocol_config.lock(swap_amount)?;
+            store_protocol_config(ctx.accounts.protocol_config.as_deref(), &protocol_config)?;
+            true
+        }
+        _ => false,
+    };
+    let (max_redeems_per_window, rate_limit_window_slots) = protocol_config
+        .map(|protocol_config| {
+            (
+                protocol_config.max_redeems_per_window,
+                protocol_config.rate_limit_window_slots,
+            )
+        })
+        .unwrap_or_default();
+    if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+        stats.initiated = stats.initiated.saturating_add(1);
+    }
+    if options.init_fee > 0 {
+        let fee_collector = ctx
+            .accounts
+            .fee_collector
+            .as_ref()
+            .ok_or(SwapError::MissingFeeCollector)?;
+        let fee_context = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.funder.to_account_info(),
+                to: fee_collector.to_account_info(),
+            },
+        );
+        system_program::transfer(fee_context, options.init_fee)?;
+    }
+
+    // The swap account and the vault have already been created by `init`, before this handler
+    // runs, so that a duplicate initiate fails on their creation ahead of this transfer. Either
+    // way, the transaction is atomic, and no lamports move if any of its instructions fails.
+    //
+    // The addresses of the swap account and the vault are known ahead of initiation, so either
+    // may already hold lamports. These are not rejected, as anyone could then block a swap by
+    // prefunding its addresses, nor are they counted towards the swap amount: exactly
+    // `swap_amount` is ever paid out of the vault, and any prefunded lamports are returned
+    // to the rent sponsor along with the rent when the accounts are closed.
+    let transfer_context = CpiContext::new(
+        ctx.accounts.system_program.to_account_info(),
+        system_program::Transfer {
+            from: ctx.accounts.funder.to_account_info(),
+            to: ctx.accounts.vault.to_account_info(),
+        },
+    );
+    system_program::transfer(transfer_context, swap_amount)?;
+
+    let initiated_slot = current_clock()?.slot;
+    let (timelock, expiry_slot, absolute_expiry) = match expiry {
+        Expiry::Timelock(timelock) => (
+            timelock,
+            compute_expiry_slot(initiated_slot, timelock)?,
+            false,
+        ),
+        Expiry::Slot(expiry_slot) => (
+            expiry_slot.saturating_sub(initiated_slot),
+            expiry_slot,
+            true,
+        ),
+    };
+    // Guarantees a non-empty redeem window, regardless of how the expiry slot is computed
+    require_gt!(expiry_slot, initiated_slot, SwapError::ExpiryNotInFuture);
+    if options.counterparty_timelock > 0 {
+        assert_safe_timelock(timelock, options.counterparty_timelock)?;
+    }
+    if options.queue_refund {
+        let refund_queue = ctx
+            .accounts
+            .refund_queue
+            .as_mut()
+            .ok_or(SwapError::MissingRefundQueue)?;
+        refund_queue.register(ctx.accounts.swap_account.key(), expiry_slot)?;
+    }
+    let swap_id = compute_swap_id(
+        &redeemer,
+        &refundee,
+        &secret_hash,
+        swap_amount,
+        if absolute_expiry {
+            expiry_slot
+        } else {
+            timelock
+        },
+        options.nonce,
+    );
+    *ctx.accounts.swap_account = SwapAccount {
+        expiry_slot,
+        bump: ctx.bumps.swap_account,
+        vault_bump: ctx.bumps.vault,
+        rent_sponsor: ctx.accounts.rent_sponsor.key(),
+        refundee,
+        redeemer,
+        secret_hash,
+        swap_amount,
+        timelock,
+        destination_commitment,
+        relayer: options.relayer,
+        adaptor_point,
+        cancel_authority: options.funder_cancelable.then_some(funder),
+        original_expiry_slot: expiry_slot,
+        max_extension_slots: options.max_extension_slots,
+        additional_secret_hashes: options.additional_secret_hashes,
+        instant_refund_authority: options.instant_refund_authority.unwrap_or(redeemer),
+        initiated_slot,
+        original_redeemer: redeemer,
+        single_boundary: options.single_boundary,
+        sink: options.sink,
+        suppress_events: options.suppress_events,
+        refund_trigger: options.refund_trigger,
+        swap_id,
+        distribution: options.distribution,
+        price_oracle: options.price_oracle,
+        target_value: options.target_value,
+        funder,
+        rebate_bps: 0,
+        rebate_recipient: None,
+        absolute_expiry,
+        instant_refund_cliff_slots: options.instant_refund_cliff_slots,
+        vesting: options.vesting,
+        hash_kind: HashKind::of(adaptor_point, options.secret_merkle_root),
+        keeper_reward_lamports: options.keeper_reward_lamports,
+        counterparty_timelock: options.counterparty_timelock,
+        secret_merkle_root: options.secret_merkle_root,
+        tvl_locked,
+        max_redeems_per_window,
+        rate_limit_window_slots,
+        price_feed_id: options.price_feed_id,
+        bucketed_seeds: cfg!(feature = "bucketed-seeds"),
+    };
+
+    #[cfg(feature = "verbose")]
+    msg!(
+        "{}",
+        initiation_log(&secret_hash, &ctx.accounts.swap_account.key())
+    );
+
+    if !options.suppress_events {
+        emit!(Initiated {
+            redeemer,
+            refundee,
+            secret_hash,
+            swap_amount,
+            timelock,
+            destination_data,
+            destination_commitment,
+            funder,
+            init_fee: options.init_fee,
+            hash_kind: HashKind::of(adaptor_point, options.secret_merkle_root),
+            swap_id,
+        });
+    }
+
+    Ok(())
+}
+
+/// Redeems the swap with `secret`, emitting `memo` in `Redeemed` if any, which must then be
+/// at most `MAX_MEMO_LEN` bytes long. This is shared by `redeem` and `redeem_with_memo`.
+fn redeem_swap(ctx: Context<Redeem>, secret: [u8; 32], memo: Option<String>) -> Result<()> {
+    ctx.accounts
+        .swap_account
+        .check_hash_kind(HashKind::Sha256)?;
+    verify_secret(&secret, &ctx.accounts.swap_account.secret_hash)?;
+    require!(
+        memo.as_ref().is_none_or(|memo| memo.len() <= MAX_MEMO_LEN),
+        SwapError::MemoTooLong
+    );
+
+    ctx.accounts
+        .pay_out(ctx.remaining_accounts, secret, vec![], memo, false)?;
+    set_return_data(&redeem_return_data(
+        &ctx.accounts.swap_account.key(),
+        ctx.accounts.swap_account.swap_amount,
+        &secret,
+    )?);
+
+    Ok(())
+}
+
+/// Refunds the swap before its expiry slot, paying a penalty of `penalty_bps` basis points of
+/// the swap amount to the redeemer. This is shared by `instant_refund` and
+/// `instant_refund_with_penalty`.
+#[cfg(feature = "instant-refund")]
+fn instant_refund_swap(ctx: Context<InstantRefund>, penalty_bps: u16) -> Result<()> {
+    let SwapAccount {
+        refundee,
+        redeemer,
+        secret_hash,
+        swap_amount,
+        timelock,
+        instant_refund_authority,
+        suppress_events,
+        swap_id,
+        ..
+    } = *ctx.accounts.swap_account;
+    let current_slot = current_clock()?.slot;
+    ctx.accounts
+        .swap_account
+        .check_instant_refund(current_slot)?;
+    ctx.accounts
+        .swap_account
+        .check_instant_refund_cliff(current_slot)?;
+    let slots_before_expiry = ctx.accounts.swap_account.slots_before_expiry(current_slot);
+    check_swap_account_info(&ctx.accounts.swap_account.to_account_info())?;
+    require_gte!(MAX_PENALTY_BPS, penalty_bps, SwapError::InvalidPenalty);
+
+    let penalty = penalty_amount(swap_amount, penalty_bps);
+    let refund_amount = swap_amount - penalty;
+    ctx.accounts.vault.sub_lamports(swap_amount)?;
+    if penalty > 0 {
+        require!(
+            ctx.accounts.refundee.is_signer,
+            SwapError::PenaltyNotConsented
+        );
+        let redeemer = ctx
+            .accounts
+            .redeemer
+            .as_ref()
+            .ok_or(SwapError::InvalidRedeemer)?;
+        redeemer.add_lamports(penalty)?;
+    }
+    ctx.accounts.refundee.add_lamports(refund_amount)?;
+    release_tvl(
+        ctx.accounts.protocol_config.as_deref_mut(),
+        &ctx.accounts.swap_account,
+    )?;
+    if let Some(stats) = ctx.accounts.stats.as_deref_mut() {
+        stats.instant_refunded = stats.instant_refunded.saturating_add(1);
+    }
+
+    if !suppress_events {
+        emit!(InstantRefunded {
+            redeemer,
+            refundee,
+            secret_hash,
+            swap_amount,
+            timelock,
+            slots_before_expiry,
+            instant_refund_authority,
+            penalty,
+            refund_amount,
+            swap_id,
+        });
+    }
+
+    Ok(())
+}
+
+/// Fails with `SwapError::TooManyRecipients` if `distribution` has more than `MAX_RECIPIENTS`
+/// shares, and with `SwapError::InvalidDistribution` if its basis points do not total
+/// `TOTAL_BPS`. An empty distribution is valid, and pays out the redeemer in full.
+pub fn validate_distribution(distribution: &[Share]) -> Result<()> {
+    require!(
+        distribution.len() <= MAX_RECIPIENTS,
+        SwapError::TooManyRecipients
+    );
+    let total_bps: u32 = distribution.iter().map(|share| u32::from(share.bps)).sum();
+    require!(
+        distribution.is_empty() || total_bps == u32::from(TOTAL_BPS),
+        SwapError::InvalidDistribution
+    );
+    Ok(())
+}
+
+/// Fails with `SwapError::TooManySplits` if there are more than `MAX_SPLITS` splits, and with
+/// `SwapError::InvalidSplits` unless there is at least one split, none of them empty, and
+/// their amounts total `swap_amount`.
+pub fn validate_splits(splits: &[Split], swap_amount: u64) -> Result<()> {
+    require!(splits.len() <= MAX_SPLITS, SwapError::TooManySplits);
+    let total: u128 = splits
+        .iter()
+        .map(|split| u128::from(split.swap_amount))
+        .sum();
+    require!(
+        !splits.is_empty()
+            && splits.iter().all(|split| split.swap_amount > 0)
+            && total == u128::from(swap_amount),
+        SwapError::InvalidSplits
+    );
+    Ok(())
+}
+
+/// Splits `swap_amount` according to the basis points of each share of `distribution`,
+/// rounding down. The rounding remainder goes to the last recipient, so that the amounts
+/// always total `swap_amount` for a valid distribution.
+pub fn split_amount(swap_amount: u64, distribution: &[Share]) -> Vec<u64> {
+    let mut amounts: Vec<u64> = distribution
+        .iter()
+        .map(|share| {
+            (u128::from(swap_amount) * u128::from(share.bps) / u128::from(TOTAL_BPS)) as u64
+        })
+        .collect();
+    let remainder = swap_amount.saturating_sub(amounts.iter().sum());
+    if let Some(last) = amounts.last_mut() {
+        *last += remainder;
+    }
+    amounts
+}
+
+/// The part of `payout` vested at `current_slot` for a swap initiated at `initiated_slot` with
+/// `timelock`, i.e. `payout · (current_slot - initiated_slot) / timelock`, rounded down.
+/// Nothing is vested at initiation, and all of it once the timelock has elapsed.
+pub fn vested_amount(payout: u64, initiated_slot: u64, timelock: u64, current_slot: u64) -> u64 {
+    let elapsed = current_slot.saturating_sub(initiated_slot);
+    if elapsed >= timelock {
+        return payout;
+    }
+    (u128::from(payout) * u128::from(elapsed) / u128::from(timelock)) as u64
+}
+
+/// Fails with `SwapError::UnsafeTimelock` unless `sol_timelock` is at least
+/// `MIN_SAFE_TIMELOCK_BPS` of `counterparty_timelock`, both in Solana slots.
+///
+/// In a cross-chain HTLC, the party revealing the secret on one leg must still be able to
+/// redeem the other. The Solana leg thus needs to outlast its counterparty leg by a margin,
+/// otherwise its refundee could refund it right after redeeming the counterparty leg.
+/// Clients should call this ahead of initiation, converting the counterparty timelock from
+/// the block time of its chain. Passing it as `counterparty_timelock` has the initiate check it.
+pub fn assert_safe_timelock(sol_timelock: u64, counterparty_timelock: u64) -> Result<()> {
+    require!(
+        u128::from(sol_timelock) * u128::from(TOTAL_BPS)
+            >= u128::from(counterparty_timelock) * u128::from(MIN_SAFE_TIMELOCK_BPS),
+        SwapError::UnsafeTimelock
+    );
+    Ok(())
+}
+
+/// The largest keeper reward a swap of `swap_amount` can be initiated with, namely
+/// `MAX_KEEPER_REWARD_BPS` of it, rounded down.
+pub fn max_keeper_reward(swap_amount: u64) -> u64 {
+    (u128::from(swap_amount) * u128::from(MAX_KEEPER_REWARD_BPS) / u128::from(TOTAL_BPS)) as u64
+}
+
+/// The part of `payout` rebated at `rebate_bps` basis points, rounded down.
+pub fn rebate_amount(payout: u64, rebate_bps: u16) -> u64 {
+    (u128::from(payout) * u128::from(rebate_bps.min(TOTAL_BPS)) / u128::from(TOTAL_BPS)) as u64
+}
+
+/// The penalty paid out of `swap_amount` at `penalty_bps` basis points, rounded down.
+pub fn penalty_amount(swap_amount: u64, penalty_bps: u16) -> u64 {
+    (u128::from(swap_amount) * u128::from(penalty_bps.min(TOTAL_BPS)) / u128::from(TOTAL_BPS))
+        as u64
+}
+
+/// Whether a funder holding `balance` lamports can pay out `amount`, i.e. the swap amount
+/// along with the initiation fee. The funder must either be emptied by this, or remain
+/// rent-exempt, as the runtime rejects transactions leaving a system account with a balance
+/// below its rent exemption.
+pub fn funder_balance_suffices(balance: u64, amount: u64, rent: &Rent) -> bool {
+    balance
+        .checked_sub(amount)
+        .is_some_and(|remaining| remaining == 0 || rent.is_exempt(remaining, 0))
+}
+
+/// A price of SOL read from an oracle, as `price · 10^exponent` units of currency per SOL
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OraclePrice {
+    /// The id of the Pyth price feed the price was published on
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    /// The confidence interval around the price, in the same units
+    pub confidence: u64,
+    pub exponent: i32,
+    /// The unix timestamp at which the price was published
+    pub publish_time: i64,
+}
+
+/// Reads the price of a Pyth price update account, i.e. a `PriceUpdateV2` of the Pyth receiver
+/// program. It fails with `SwapError::InvalidOracle` for any other account, or if the price is
+/// only partially verified, and with `SwapError::OracleFeedMismatch` if it was published on
+/// any feed other than `feed_id`, if given. As anyone can post updates of any feed to
+/// accounts of their own, the account alone does not determine the feed. It fails with
+/// `SwapError::StaleOracle` if the price was published more than `MAX_ORACLE_AGE` seconds
+/// before `now`, and with `SwapError::UncertainOracle` if its confidence interval exceeds
+/// `MAX_ORACLE_CONFIDENCE_BPS` of it.
+pub fn read_oracle_price(
+    oracle: &AccountInfo,
+    feed_id: Option<[u8; 32]>,
+    now: i64,
+) -> Result<OraclePrice> {
+    require_keys_eq!(*oracle.owner, PYTH_RECEIVER_ID, SwapError::InvalidOracle);
+    let price = parse_price_update(&oracle.try_borrow_data()?).ok_or(SwapError::InvalidOracle)?;
+    if let Some(feed_id) = feed_id {
+        require!(price.feed_id == feed_id, SwapError::OracleFeedMismatch);
+    }
+    require!(
+        now.saturating_sub(price.publish_time) <= MAX_ORACLE_AGE,
+        SwapError::StaleOracle
+    );
+    require!(
+        u128::from(price.confidence) * u128::from(TOTAL_BPS)
+            <= u128::from(price.price.unsigned_abs()) * u128::from(MAX_ORACLE_CONFIDENCE_BPS),
+        SwapError::UncertainOracle
+    );
+    Ok(price)
+}
+
+/// Parses the data of a fully verified `PriceUpdateV2`, or returns `None` for any other data.
+pub fn parse_price_update(data: &[u8]) -> Option<OraclePrice> {
+    // The layout of a `PriceUpdateV2`: the Anchor discriminator, the write authority
+    // (32 bytes), the verification level (1 byte for `Full`), then the price message:
+    // the feed id (32 bytes), the price (i64), the confidence (u64), the exponent (i32),
+    // the publish time (i64) and further fields
+    let discriminator = &hash::hash(b"account:PriceUpdateV2").to_bytes()[..ANCHOR_DISCRIMINATOR];
+    if data.get(..ANCHOR_DISCRIMINATOR)? != discriminator || *data.get(40)? != 1 {
+        return None;
+    }
+    let message = data.get(41..)?;
+    Some(OraclePrice {
+        feed_id: message.get(..32)?.try_into().ok()?,
+        price: i64::from_le_bytes(message.get(32..40)?.try_into().ok()?),
+        confidence: u64::from_le_bytes(message.get(40..48)?.try_into().ok()?),
+        exponent: i32::from_le_bytes(message.get(48..52)?.try_into().ok()?),
+        publish_time: i64::from_le_bytes(message.get(52..60)?.try_into().ok()?),
+    })
+}
+
+/// The lamports worth `target_value`, with `TARGET_VALUE_DECIMALS`, at `price`.
+/// Returns `None` for non-positive prices and on overflow.
+pub fn pegged_payout(target_value: u64, price: &OraclePrice) -> Option<u64> {
+    // lamports = target_value · 10^-TARGET_VALUE_DECIMALS / (price · 10^exponent) · 10^9
+    let OraclePrice {
+        price, exponent, ..
+    } = *price;
+    let price = u128::try_from(price).ok().filter(|price| *price > 0)?;
+    let scale = (9 - TARGET_VALUE_DECIMALS as i32).checked_sub(exponent)?;
+    let (numerator, denominator) = if scale >= 0 {
+        (
+            u128::from(target_value).checked_mul(10u128.checked_pow(scale as u32)?)?,
+            price,
+        )
+    } else {
+        (
+            u128::from(target_value),
+            price.checked_mul(10u128.checked_pow(scale.unsigned_abs())?)?,
+        )
+    };
+    u64::try_from(numerator / denominator).ok()
+}
+
+/// The return data of `redeem`, namely the borsh encoding of the tuple
+/// `(swap_account, swap_amount, secret)`, as read by CPI callers through `get_return_data`.
+pub fn redeem_return_data(
+    swap_account: &Pubkey,
+    swap_amount: u64,
+    secret: &[u8; 32],
+) -> Result<Vec<u8>> {
+    Ok((swap_account, swap_amount, secret).try_to_vec()?)
+}
+
+/// Decodes `secret_hex`, the hex encoding of a 32-byte secret, as taken by `redeem_hex`.
+/// Both lowercase and uppercase digits are accepted, without any `0x` prefix. Fails with
+/// `SwapError::InvalidSecretEncoding` unless it holds exactly 64 hex digits.
+pub fn decode_secret_hex(secret_hex: &str) -> Result<[u8; 32]> {
+    let digits = secret_hex.as_bytes();
+    require_eq!(digits.len(), 64, SwapError::InvalidSecretEncoding);
+    let digit = |c: u8| {
+        (c as char)
+            .to_digit(16)
+            .ok_or(SwapError::InvalidSecretEncoding)
+    };
+    let mut secret = [0; 32];
+    for (byte, pair) in secret.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = (digit(pair[0])? << 4 | digit(pair[1])?) as u8;
+    }
+    Ok(secret)
+}
+
+/// Verifies that `secret` is the preimage of `secret_hash`, failing with
+/// `SwapError::InvalidSecret` otherwise. As a hint for a common client mistake, passing the
+/// secret hash itself as the secret fails with `SwapError::SecretLooksLikeHash` instead.
+pub fn verify_secret(secret: &[u8; 32], secret_hash: &[u8; 32]) -> Result<()> {
+    require!(secret != secret_hash, SwapError::SecretLooksLikeHash);
+    require!(
+        hash::hash(secret).to_bytes() == *secret_hash,
+        SwapError::InvalidSecret
+    );
+    Ok(())
+}
+
+/// Verifies that `additional_secrets` are exactly the preimages of `additional_secret_hashes`,
+/// in order. Swaps without additional secret hashes are only redeemed without additional secrets.
+pub fn verify_additional_secrets(
+    additional_secret_hashes: &[[u8; 32]],
+    additional_secrets: &[[u8; 32]],
+) -> bool {
+    additional_secret_hashes.len() == additional_secrets.len()
+        && additional_secret_hashes
+            .iter()
+            .zip(additional_secrets)
+            .all(|(secret_hash, secret)| hash::hash(secret).to_bytes() == *secret_hash)
+}
+
+/// Verifies that `leaf` is a leaf of the Merkle tree with `root`, given `proof`, the sibling
+/// hashes on the path from the leaf up to the root. Each parent is the SHA-256 hash of its two
+/// children concatenated in ascending order, so that the proof needs no leaf index, as in
+/// OpenZeppelin's `MerkleProof`. An empty proof verifies the root as the only leaf.
+pub fn verify_merkle_proof(leaf: [u8; 32], proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        let (left, right) = if node <= *sibling {
+            (node, *sibling)
+        } else {
+            (*sibling, node)
+        };
+        hash::hashv(&[&left, &right]).to_bytes()
+    });
+    computed == *root
+}
+
+/// Asserts that `swap_account` is owned by this program and holds a `SwapAccount`,
+/// failing with `SwapError::InvalidSwapAccount` otherwise. Anchor already enforces this
+/// when deserializing `Account<SwapAccount>`. This restates it explicitly before any
+/// lamports are moved, so that the property does not rest on Anchor's checks alone.
+pub fn check_swap_account_info(swap_account: &AccountInfo) -> Result<()> {
+    require_keys_eq!(
+        *swap_account.owner,
+        crate::ID,
+        SwapError::InvalidSwapAccount
+    );
+    require!(
+        swap_account
+            .try_borrow_data()?
+            .starts_with(SwapAccount::DISCRIMINATOR),
+        SwapError::InvalidSwapAccount
+    );
+    Ok(())
+}
+
+/// The layout of `SwapAccount` the program was first deployed with, before any field was
+/// appended to it. Swaps of this layout escrow their funds in the swap account itself.
+#[derive(AnchorDeserialize, InitSpace)]
+struct BaselineSwapAccount {
+    expiry_slot: u64,
+    bump: u8,
+    redeemer: Pubkey,
+    refundee: Pubkey,
+    secret_hash: [u8; 32],
+    swap_amount: u64,
+    rent_sponsor: Pubkey,
+    timelock: u64,
+}
+
+/// Decodes a `SwapAccount` from the raw data of an account initialized with an older layout.
+/// Accounts of `BASELINE_SWAP_ACCOUNT_SIZE` are decoded as a `BaselineSwapAccount`.
+/// Fields have only been appended to `SwapAccount` since, so other older layouts are a prefix
+/// of the current one, and are zero-extended to the current size instead. Either way, the
+/// fields added since are decoded as empty (`None`, zero, `false` or empty). Fields that must
+/// not be empty are then backfilled: the original redeemer and the instant refund authority
+/// from the redeemer, the original expiry slot from the expiry slot, the swap id with a nonce
+/// of 0, the funder, which older layouts did not record, from the refundee, and the hash kind
+/// from the adaptor point and the secret Merkle root. The vault bump is left to `realloc_swap`,
+/// which creates the vault of swaps predating vaults.
+pub fn migrate_swap_account(data: &[u8]) -> Result<SwapAccount> {
+    let mut swap = if data.len() == BASELINE_SWAP_ACCOUNT_SIZE {
+        require!(
+            data.starts_with(SwapAccount::DISCRIMINATOR),
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        let baseline = BaselineSwapAccount::deserialize(&mut &data[ANCHOR_DISCRIMINATOR..])?;
+        SwapAccount {
+            expiry_slot: baseline.expiry_slot,
+            bump: baseline.bump,
+            redeemer: baseline.redeemer,
+            refundee: baseline.refundee,
+            secret_hash: baseline.secret_hash,
+            swap_amount: baseline.swap_amount,
+            rent_sponsor: baseline.rent_sponsor,
+            timelock: baseline.timelock,
+            ..Default::default()
+        }
+    } else {
+        let mut extended = data.to_vec();
+        extended.resize(extended.len().max(SWAP_ACCOUNT_SIZE), 0);
+        unpack_swap_account(&extended)?
+    };
+
+    if swap.original_redeemer == Pubkey::default() {
+        swap.original_redeemer = swap.redeemer;
+    }
+    if swap.instant_refund_authority == Pubkey::default() {
+        swap.instant_refund_authority = swap.redeemer;
+    }
+    if swap.original_expiry_slot == 0 {
+        swap.original_expiry_slot = swap.expiry_slot;
+    }
+    if swap.swap_id == [0; 32] {
+        swap.swap_id = compute_swap_id(
+            &swap.original_redeemer,
+            &swap.refundee,
+            &swap.secret_hash,
+            swap.swap_amount,
+            swap.timelock,
+            0,
+        );
+    }
+    if swap.funder == Pubkey::default() {
+        swap.funder = swap.refundee;
+    }
+    swap.hash_kind = HashKind::of(swap.adaptor_point, swap.secret_merkle_root);
+    Ok(swap)
+}
+
+/// Decodes a `SwapAccount` from raw account data, verifying and stripping its
+/// 8-byte discriminator. Together with the `no-entrypoint` feature, this allows
+/// services such as indexers to decode swap accounts with this crate as a library.
+pub fn unpack_swap_account(mut data: &[u8]) -> Result<SwapAccount> {
+    SwapAccount::try_deserialize(&mut data)
+}
+
+/// Computes the expiry slot of a swap initiated at `current_slot` with `timelock`.
+/// Timelocks that would push the expiry slot past `u64::MAX` are rejected, rather than
+/// wrapping around into an expiry slot in the past.
+pub fn compute_expiry_slot(current_slot: u64, timelock: u64) -> Result<u64> {
+    current_slot
+        .checked_add(timelock)
+        .ok_or(error!(SwapError::TimelockOverflow))
+}
+
+/// Reads the `Clock` sysvar, failing with `SwapError::ClockUnavailable` rather than an opaque
+/// sysvar error if it cannot be read. On-chain, the runtime serves the clock through a syscall
+/// to any program, including under CPI, so that no clock account needs to be passed. Natively,
+/// e.g. in unit tests, it is only available once stubbed, such as by `SimulatedClock`.
+pub fn current_clock() -> Result<Clock> {
+    Clock::get().map_err(|_| error!(SwapError::ClockUnavailable))
+}
+
+/// Estimates the Unix timestamp of `slot` from `clock`, assuming that slots last exactly
+/// `SLOT_DURATION_MS`, e.g. to project when a swap will expire. This is only a projection:
+/// actual slot durations vary, so the estimate drifts the further `slot` is from the clock.
+/// Slots before the clock's slot are estimated backwards. The result saturates at the
+/// bounds of `i64`.
+pub fn estimate_slot_timestamp(slot: u64, clock: &Clock) -> i64 {
+    let elapsed_ms = (i128::from(slot) - i128::from(clock.slot)) * i128::from(SLOT_DURATION_MS);
+    let timestamp = i128::from(clock.unix_timestamp) + elapsed_ms.div_euclid(1_000);
+    timestamp.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+}
+
+/// The log line describing a newly initiated swap, with its secret hash hex-encoded.
+/// This is only logged with the `verbose` feature, to help diagnose disagreements between
+/// clients and the program on the secret hash bytes.
+#[cfg(feature = "verbose")]
+fn initiation_log(secret_hash: &[u8; 32], swap_account: &Pubkey) -> String {
+    let secret_hash: String = secret_hash.iter().map(|b| format!("{b:02x}")).collect();
+    format!("Initiated swap account {swap_account} with secret hash {secret_hash}")
+}
+
+/// Verifies destination data revealed off-chain against the `destination_commitment`
+/// of a swap initiated through `initiate_with_commitment`.
+pub fn verify_destination_commitment(
+    destination_commitment: &[u8; 32],
+    destination_data: &[u8],
+) -> Result<()> {
+    require!(
+        hash::hash(destination_data).to_bytes() == *destination_commitment,
+        SwapError::InvalidDestinationCommitment
+    );
+    Ok(())
+}
+
+/// Checks whether `adaptor_secret` is the discrete logarithm of `adaptor_point`, i.e. whether
+/// `adaptor_secret·G == adaptor_point`, where `G` is the Ed25519 base point.
+/// `adaptor_secret` must be a canonical little-endian scalar.
+pub fn verify_adaptor_secret(adaptor_point: &[u8; 32], adaptor_secret: &[u8; 32]) -> bool {
+    multiply_edwards(
+        &PodScalar(*adaptor_secret),
+        &PodEdwardsPoint(ED25519_BASEPOINT),
+    )
+    .is_some_and(|point| point.0 == *adaptor_point)
+}
+
+/// Rounds `amount` down into a coarse bucket, namely the largest power of two not exceeding it.
+/// Zero is its own bucket.
+pub fn bucket_amount(amount: u64) -> u64 {
+    match amount {
+        0 => 0,
+        _ => 1 << amount.ilog2(),
+    }
+}
+
+/// The rent paid by the rent sponsor upon initiation of a swap, for its swap account and
+/// its vault, under `rent`. Clients can fund the rent sponsor with exactly this amount,
+/// which is refunded upon completion of the swap.
+pub fn rent_for_swap(rent: &Rent) -> u64 {
+    rent.minimum_balance(SWAP_ACCOUNT_SIZE) + rent.minimum_balance(SWAP_VAULT_SIZE)
+}
+
+/// The amount committed to by the PDA seeds of a swap of `swap_amount` initiated by this build.
+/// With the `bucketed-seeds` feature, only the bucket of the amount is used in the seeds, so
+/// that the address of a swap does not reveal its exact amount to observers deriving
+/// addresses from candidate parameters. The exact amount is still stored in the swap account
+/// and emitted in events. In turn, swaps with otherwise identical parameters whose amounts fall
+/// in the same bucket derive the same PDA, and hence cannot coexist.
+/// Swaps keep the seeds they were initiated with, whether or not a later build has the
+/// feature. Refer `SwapAccount::amount_seed`.
+pub fn seed_amount(swap_amount: u64) -> u64 {
+    if cfg!(feature = "bucketed-seeds") {
+        bucket_amount(swap_amount)
+    } else {
+        swap_amount
+    }
+}
+
+/// Stores the state information of the atomic swap on-chain.
+/// It only ever holds the hashes of the secrets of the swap, never the secrets themselves,
+/// which are only emitted upon redeem, and only persisted on purpose through `record_secret`.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct SwapAccount {
+    /// The exact slot after which (non-instant) refunds are allowed
+    pub expiry_slot: u64,
+    /// The bump that was used by the program to derive this PDA.
+    /// Storing this makes later verifications less expensive.
+    pub bump: u8,
+
+    /// The redeemer of the atomic swap, i.e. the current holder of its redeem rights
     pub redeemer: Pubkey,
+    /// The entity that is eligible to receive a refund in the atomic swap
     pub refundee: Pubkey,
+    /// The secret hash associated with the atomic swap.
+    /// For adaptor swaps, this holds the adaptor point instead.
     pub secret_hash: [u8; 32],
-    /// The quantity of native SOL transferred through this atomic swap in base units (aka lamports).  
-    /// E.g: A quantity of 1 SOL will be represented as 1,000,000,000.
+    /// The quantity of native SOL to be transferred through this atomic swap in base units (aka lamports)
     pub swap_amount: u64,
-    /// `timelock` represents the number of slots (1 slot = 400ms) after which
-    /// (non-instant) refunds are allowed
+    /// The entity that paid the rent fees for the creation of this PDA.
+    /// This will be referenced during the refund of the same upon closing this PDA.
+    pub rent_sponsor: Pubkey,
+    /// The number of slots after which (non-instant) refunds are allowed.
+    /// This is stored so that it can later be verified through events.
     pub timelock: u64,
-    /// Information regarding the destination chain in the atomic swap.
-    pub destination_data: Option<Vec<u8>>,
-    /// The party that deposited the funds for the atomic swap.
+    /// The SHA-256 hash of the destination data, if the swap was initiated with a commitment
+    /// in place of the data itself.
+    pub destination_commitment: Option<[u8; 32]>,
+    /// The only entity allowed to trigger the redeem, if any.
+    pub relayer: Option<Pubkey>,
+    /// The compressed Ed25519 point locking the swap, if this is an adaptor swap.
+    pub adaptor_point: Option<[u8; 32]>,
+    /// The funder, if the swap was initiated as funder-cancelable.
+    pub cancel_authority: Option<Pubkey>,
+    /// The expiry slot set at initiation, prior to any extensions
+    pub original_expiry_slot: u64,
+    /// The maximum number of slots by which the expiry slot can be extended past
+    /// the original expiry slot
+    pub max_extension_slots: u64,
+    /// The secret hashes whose preimages must be revealed on redeem, besides the secret hash
+    #[max_len(MAX_ADDITIONAL_SECRET_HASHES)]
+    pub additional_secret_hashes: Vec<[u8; 32]>,
+    /// The entity whose signature is required for instant refunds, usually the redeemer
+    pub instant_refund_authority: Pubkey,
+    /// The slot at which the swap was initiated
+    pub initiated_slot: u64,
+    /// The redeemer set at initiation, which the address of this PDA is derived from.
+    /// This differs from `redeemer` once the redeem rights have been transferred.
+    pub original_redeemer: Pubkey,
+    /// Whether refunds are allowed from the expiry slot itself, rather than after it
+    pub single_boundary: bool,
+    /// Whether the refundee is a sink, to which unredeemed funds are swept past expiry
+    pub sink: bool,
+    /// Whether events are suppressed for this swap
+    pub suppress_events: bool,
+    /// The only entity allowed to trigger refunds past expiry, if restricted, until
+    /// `REFUND_TRIGGER_GRACE_SLOTS` slots past it
+    pub refund_trigger: Option<Pubkey>,
+    /// The identifier of the swap, emitted in all its events. Refer `compute_swap_id`.
+    pub swap_id: [u8; 32],
+    /// The shares the swap amount is split into on redeem, if any
+    #[max_len(MAX_RECIPIENTS)]
+    pub distribution: Vec<Share>,
+    /// The oracle pricing SOL in the currency of `target_value`, if the swap is value-pegged
+    pub price_oracle: Option<Pubkey>,
+    /// The value paid out on redeem of a value-pegged swap, with `TARGET_VALUE_DECIMALS`
+    pub target_value: u64,
+    /// The party that deposited the funds, to which `decline` returns them
     pub funder: Pubkey,
+    /// The basis points of the payout rebated to `rebate_recipient` on redeem
+    pub rebate_bps: u16,
+    /// The funder, if the redeemer committed to a rebate through `commit_rebate`
+    pub rebate_recipient: Option<Pubkey>,
+    /// Whether the swap was initiated through `initiate_at`, in which case its original expiry
+    /// slot takes the place of its timelock in its seeds. Refer `timelock_seed`.
+    pub absolute_expiry: bool,
+    /// The number of slots after `initiated_slot` before which instant refunds are rejected
+    pub instant_refund_cliff_slots: u64,
+    /// Whether the payout vests linearly over the timelock, the unvested part being refunded
+    /// to the funder on redeem. Refer `vested_amount`.
+    pub vesting: bool,
+    /// The algorithm locking the swap. Refer `hash_kind`.
+    pub hash_kind: HashKind,
+    /// The reward paid out of the swap amount to the keeper triggering its refund past expiry
+    pub keeper_reward_lamports: u64,
+    /// The timelock of the counterparty leg in slots, as declared on initiation.
+    /// This is informational, with zero implying none was declared.
+    pub counterparty_timelock: u64,
+    /// The root of the Merkle tree of secret hashes locking the swap in place of
+    /// `secret_hash`, if any. Refer `redeem_merkle`.
+    pub secret_merkle_root: Option<[u8; 32]>,
+    /// The bump that was used by the program to derive the vault of this swap.
+    /// Swaps initiated before vaults existed escrowed their funds in the swap account itself,
+    /// and are given a vault by `realloc_swap`.
+    pub vault_bump: u8,
+    /// Whether the swap amount is counted in the total value locked of the `ProtocolConfig`,
+    /// i.e. a cap was configured on initiation. Only then is the config required to complete
+    /// the swap, releasing the swap amount.
+    pub tvl_locked: bool,
+    /// The rate limit in force on initiation, which the redeem of the swap is subject to if
+    /// signed by the redeemer, with 0 disabling it. Refer `set_rate_limit`.
+    pub max_redeems_per_window: u64,
+    /// The length of the windows of the rate limit in force on initiation, in slots
+    pub rate_limit_window_slots: u64,
+    /// The id of the Pyth price feed that `price_oracle` must publish, if value-pegged.
+    /// Swaps pegged before it was recorded accept any feed.
+    pub price_feed_id: Option<[u8; 32]>,
+    /// Whether the seeds of the swap hold the bucket of its amount rather than the exact
+    /// amount, i.e. it was initiated by a build with the `bucketed-seeds` feature. This is
+    /// stored so that swaps remain at their address across upgrades toggling the feature.
+    /// Refer `amount_seed`.
+    pub bucketed_seeds: bool,
 }
-/// Represents the redeemed state of the swap, where the redeemer has withdrawn funds from the vault.
-/// Note that the secret is emitted here, in place of the secret hash.
-#[event]
-pub struct Redeemed {
-    pub redeemer: Pubkey,
-    pub refundee: Pubkey,
-    pub secret: [u8; 32],
-    pub swap_amount: u64,
-    pub timelock: u64,
+
+/// The algorithm locking a swap, under which its redeem must prove knowledge of a preimage
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default, InitSpace,
+)]
+pub enum HashKind {
+    /// `secret_hash` is the SHA-256 hash of a 32-byte secret, revealed through `redeem`
+    #[default]
+    Sha256,
+    /// `secret_hash` is the Ed25519 adaptor point `t·G`, whose scalar `t` is revealed
+    /// through `redeem_adaptor`
+    Ed25519Adaptor,
+    /// `secret_merkle_root` is the root of a Merkle tree with the SHA-256 hash of the secret
+    /// as one of its leaves, the secret being revealed through `redeem_merkle`
+    MerkleSha256,
 }
-/// Represents the refund state of the swap, where the funds have been refunded past expiry
-#[event]
-pub struct Refunded {
-    pub redeemer: Pubkey,
-    pub refundee: Pubkey,
-    pub secret_hash: [u8; 32],
-    pub swap_amount: u64,
-    pub timelock: u64,
+
+impl HashKind {
+    /// The kind of a swap locked by `adaptor_point` or `secret_merkle_root`, if any,
+    /// or by a secret hash otherwise
+    pub fn of(adaptor_point: Option<[u8; 32]>, secret_merkle_root: Option<[u8; 32]>) -> Self {
+        match (adaptor_point, secret_merkle_root) {
+            (Some(_), _) => HashKind::Ed25519Adaptor,
+            (None, Some(_)) => HashKind::MerkleSha256,
+            (None, None) => HashKind::Sha256,
+        }
+    }
 }
-/// Represents the instant refund state of the swap, where the funds have been refunded
-/// with the redeemer's consent
-#[event]
-pub struct InstantRefunded {
-    pub redeemer: Pubkey,
-    pub refundee: Pubkey,
-    pub secret_hash: [u8; 32],
-    pub swap_amount: u64,
-    pub timelock: u64,
+
+/// The protocol-wide settings and counters, held in a singleton PDA at `CONFIG_SEED`.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct ProtocolConfig {
+    /// The entity allowed to update the settings, i.e. the upgrade authority at creation
+    pub admin: Pubkey,
+    /// The cap on `current_tvl`, beyond which new initiates are rejected
+    pub max_tvl_lamports: u64,
+    /// The total of the swap amounts of all active swaps
+    pub current_tvl: u64,
+    pub bump: u8,
+    /// The number of redeems allowed per redeemer within a window, with 0 disabling the limit
+    pub max_redeems_per_window: u64,
+    /// The length of the windows of the rate limit, in slots
+    pub rate_limit_window_slots: u64,
 }
 
-#[error_code]
-pub enum SwapError {
-    #[msg("The provided refundee is incorrect")]
-    InvalidRefundee,
+impl ProtocolConfig {
+    /// Accounts for `swap_amount` being locked by a newly initiated swap.
+    /// It fails with `SwapError::TvlCapExceeded` if this would exceed `max_tvl_lamports`.
+    pub fn lock(&mut self, swap_amount: u64) -> Result<()> {
+        self.current_tvl = self
+            .current_tvl
+            .checked_add(swap_amount)
+            .filter(|tvl| *tvl <= self.max_tvl_lamports)
+            .ok_or(error!(SwapError::TvlCapExceeded))?;
+        Ok(())
+    }
 
-    #[msg("The provided redeemer is not the original redeemer of this swap")]
-    InvalidRedeemer,
+    /// Accounts for `swap_amount` being released by a redeemed or refunded swap.
+    /// Swaps initiated before the config was created were never locked, hence the saturation.
+    pub fn release(&mut self, swap_amount: u64) {
+        self.current_tvl = self.current_tvl.saturating_sub(swap_amount);
+    }
 
-    #[msg("The provided secret does not correspond to the secret hash of this swap")]
-    InvalidSecret,
+    /// Whether a cap is configured, i.e. `max_tvl_lamports` is below `u64::MAX`.
+    /// Only then do initiates lock their swap amount.
+    pub fn is_capped(&self) -> bool {
+        self.max_tvl_lamports < u64::MAX
+    }
+}
 
-    #[msg("The provided rent sponsor is incorrect")]
-    InvalidRentSponsor,
+/// Reads the `ProtocolConfig` passed to `initiate`, which is validated against `CONFIG_SEED`
+/// by Anchor, or `None` if it was either omitted or never created.
+fn load_protocol_config(info: Option<&AccountInfo>) -> Result<Option<ProtocolConfig>> {
+    match info {
+        Some(info) if info.owner == &crate::ID => Ok(Some(ProtocolConfig::try_deserialize(
+            &mut &info.data.borrow()[..],
+        )?)),
+        _ => Ok(None),
+    }
+}
 
-    #[msg("Attempt to refund before timelock expiry")]
-    RefundBeforeExpiry,
+/// Writes back the `ProtocolConfig` passed to `initiate`, which must then be writable.
+/// It fails with `SwapError::MissingProtocolConfig` if it was omitted, and with
+/// `SwapError::ProtocolConfigNotWritable` if it is read-only.
+fn store_protocol_config(
+    info: Option<&AccountInfo>,
+    protocol_config: &ProtocolConfig,
+) -> Result<()> {
+    let info = info.ok_or(SwapError::MissingProtocolConfig)?;
+    require!(info.is_writable, SwapError::ProtocolConfigNotWritable);
+    protocol_config.try_serialize(&mut &mut info.data.borrow_mut()[..])
+}
+
+/// Releases the swap amount of a completed swap from the total value locked, if it was locked
+/// on initiation, in which case `protocol_config` is required. It fails with
+/// `SwapError::MissingProtocolConfig` otherwise.
+fn release_tvl(
+    protocol_config: Option<&mut ProtocolConfig>,
+    swap_account: &SwapAccount,
+) -> Result<()> {
+    if swap_account.tvl_locked {
+        protocol_config
+            .ok_or(SwapError::MissingProtocolConfig)?
+            .release(swap_account.swap_amount);
+    }
+    Ok(())
+}
+
+/// Protocol-wide activity counters, held in a singleton PDA at `STATS_SEED`, so that activity
+/// can be read without scanning all events. The counters saturate rather than fail on
+/// overflow, so that they can never block the completion of a swap. As the stats are
+/// an optional account of every instruction counting towards them, lest all swaps contend
+/// for it, the counters only cover the instructions passing it. Exact counts are derived
+/// off-chain from the events instead.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct Stats {
+    /// The number of swaps initiated, through any variant of `initiate`. A swap split through
+    /// `split_swap` counts as its children from then on.
+    pub initiated: u64,
+    /// The number of swaps redeemed, through any variant of `redeem`
+    pub redeemed: u64,
+    /// The number of swaps refunded, including sweeps, cancellations by the funder and
+    /// declines by the redeemer
+    pub refunded: u64,
+    /// The number of swaps instantly refunded
+    pub instant_refunded: u64,
+    pub bump: u8,
+}
+
+/// The redeems signed by a redeemer within the current window, held in a PDA at
+/// `[RATE_LIMIT_SEED, redeemer]`, for enforcing the rate limit of the `ProtocolConfig`.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct RateLimit {
+    /// The redeemer whose redeems are tracked
+    pub redeemer: Pubkey,
+    /// The slot at which the current window started
+    pub window_start_slot: u64,
+    /// The number of redeems within the current window
+    pub count: u64,
+    pub bump: u8,
+}
+
+impl RateLimit {
+    /// Records a redeem at `current_slot`, starting a new window if `window_slots` have
+    /// elapsed since the start of the current one. It fails with `SwapError::RateLimited`
+    /// if `max_redeems` have already been recorded within the current window.
+    pub fn record(&mut self, current_slot: u64, max_redeems: u64, window_slots: u64) -> Result<()> {
+        if current_slot.saturating_sub(self.window_start_slot) >= window_slots {
+            self.window_start_slot = current_slot;
+            self.count = 0;
+        }
+        require!(self.count < max_redeems, SwapError::RateLimited);
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// The funds of a redeemer held back for a withdrawal delay, in a PDA at
+/// `[WITHDRAWAL_ESCROW_SEED, redeemer]`, as redeemed through `redeem_to_escrow`.
+/// The funds are held as the lamports of the escrow in excess of its rent.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct WithdrawalEscrow {
+    /// The redeemer holding the escrow, who alone can withdraw from it
+    pub redeemer: Pubkey,
+    /// The number of slots the funds are held for after each redeem into the escrow
+    pub delay_slots: u64,
+    /// The lamports currently held
+    pub amount: u64,
+    /// The slot after which the held funds can be withdrawn
+    pub withdrawable_after: u64,
+    pub bump: u8,
+}
+
+impl WithdrawalEscrow {
+    /// Records a redeem of `amount` into the escrow at `current_slot`, returning the slot after
+    /// which it can be withdrawn. This holds back all the funds in the escrow until then,
+    /// those of earlier redeems included.
+    pub fn schedule(&mut self, amount: u64, current_slot: u64) -> u64 {
+        self.amount = self.amount.saturating_add(amount);
+        self.withdrawable_after = current_slot.saturating_add(self.delay_slots);
+        self.withdrawable_after
+    }
+
+    /// Empties the escrow at `current_slot`, returning the amount to be paid out. It fails
+    /// with `SwapError::WithdrawalTooEarly` until the slot after `withdrawable_after`.
+    pub fn withdraw(&mut self, current_slot: u64) -> Result<u64> {
+        require_gt!(
+            current_slot,
+            self.withdrawable_after,
+            SwapError::WithdrawalTooEarly
+        );
+        Ok(std::mem::take(&mut self.amount))
+    }
+}
+
+/// A swap registered into the `RefundQueue`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug, InitSpace)]
+pub struct RefundQueueEntry {
+    /// The swap account of the swap
+    pub swap_account: Pubkey,
+    /// The expiry slot of the swap at initiation, after which it can be refunded
+    pub expiry_slot: u64,
+}
+
+/// A registry of swaps to be refunded once expired, held in a singleton PDA at
+/// `REFUND_QUEUE_SEED`, so that keepers can find due refunds by reading this account alone
+/// rather than scanning all swap accounts. Swaps are registered upon initiation with
+/// `queue_refund`, and removed once settled. Refer `deregister`.
+/// The expiry slots are those at initiation, and may since have been extended.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct RefundQueue {
+    /// The registered swaps, in no particular order
+    #[max_len(MAX_REFUND_QUEUE_LEN)]
+    pub entries: Vec<RefundQueueEntry>,
+    pub bump: u8,
+}
+
+impl RefundQueue {
+    /// Registers the swap at `swap_account` expiring at `expiry_slot`.
+    /// It fails with `SwapError::RefundQueueFull` if `MAX_REFUND_QUEUE_LEN` swaps are
+    /// already registered.
+    pub fn register(&mut self, swap_account: Pubkey, expiry_slot: u64) -> Result<()> {
+        require_gt!(
+            MAX_REFUND_QUEUE_LEN,
+            self.entries.len(),
+            SwapError::RefundQueueFull
+        );
+        self.entries.push(RefundQueueEntry {
+            swap_account,
+            expiry_slot,
+        });
+        Ok(())
+    }
+
+    /// Removes the swap at `swap_account`, returning whether it was registered.
+    pub fn deregister(&mut self, swap_account: &Pubkey) -> bool {
+        let len = self.entries.len();
+        self.entries
+            .retain(|entry| entry.swap_account != *swap_account);
+        self.entries.len() < len
+    }
+}
+
+/// The secret a swap was redeemed with through `redeem_idempotent`, held in a PDA at
+/// `[REDEEM_RECEIPT_SEED, swap_account]`, so that duplicates of the redeem can be told apart
+/// from redeems of a closed swap with another secret.
+#[account]
+#[derive(InitSpace)]
+pub struct RedeemReceipt {
+    /// The preimage of the secret hash of the swap
+    pub secret: [u8; 32],
+    /// The slot at which the swap was redeemed
+    pub redeemed_slot: u64,
+    /// The entity that paid the rent of the receipt, to which it is returned upon closing
+    pub payer: Pubkey,
+    pub bump: u8,
+}
+
+/// A secret revealed through `record_secret`, held in a PDA at
+/// `[REVEALED_SECRET_SEED, secret_hash]`, so that it outlives the swap it was verified against.
+#[account]
+#[derive(InitSpace)]
+pub struct RevealedSecret {
+    /// The preimage of the secret hash
+    pub secret: [u8; 32],
+    /// The slot at which the secret was recorded
+    pub revealed_slot: u64,
+    pub bump: u8,
+}
+
+/// Escrows the funds of an atomic swap, apart from its state held by `SwapAccount`.
+/// This holds no data, its lamports in excess of rent being the swap amount.
+#[account]
+#[derive(InitSpace)]
+pub struct SwapVault {}
+
+impl SwapAccount {
+    /// Whether (non-instant) refunds are allowed at `current_slot`, i.e. whether the expiry
+    /// slot has passed. This is well-defined for all slots: a swap expiring at `u64::MAX`
+    /// never expires, and can then only be redeemed or instantly refunded.
+    /// Single-boundary swaps expire at the expiry slot itself instead, including `u64::MAX`.
+    pub fn is_expired(&self, current_slot: u64) -> bool {
+        if self.single_boundary {
+            current_slot >= self.expiry_slot
+        } else {
+            current_slot > self.expiry_slot
+        }
+    }
+
+    /// Checks that `trigger`, the signer triggering the refund of this swap at `current_slot`,
+    /// if any, may trigger it. Swaps with a refund trigger can only be refunded by it for
+    /// `REFUND_TRIGGER_GRACE_SLOTS` slots past the expiry slot, failing with
+    /// `SwapError::InvalidRefundTrigger` otherwise, and by anyone after that.
+    pub fn check_refund_trigger(&self, trigger: Option<Pubkey>, current_slot: u64) -> Result<()> {
+        if let Some(refund_trigger) = self.refund_trigger {
+            let grace_end = self.expiry_slot.saturating_add(REFUND_TRIGGER_GRACE_SLOTS);
+            require!(
+                current_slot > grace_end || trigger == Some(refund_trigger),
+                SwapError::InvalidRefundTrigger
+            );
+        }
+        Ok(())
+    }
+
+    /// The child swap taking over `split` of this swap through `split_swap`, at the PDAs of the
+    /// given bumps. It keeps the terms of this swap, except for the redeemer, the swap amount and
+    /// any committed rebate. An instant refund authority defaulted to the redeemer follows the
+    /// redeemer of the child, and the keeper reward is scaled to its swap amount, rounded down.
+    /// The swap id is computed with the `index` of the child as its nonce.
+    pub fn child(&self, split: &Split, bump: u8, vault_bump: u8, index: u64) -> SwapAccount {
+        let instant_refund_authority = if self.instant_refund_authority == self.redeemer {
+            split.redeemer
+        } else {
+            self.instant_refund_authority
+        };
+        SwapAccount {
+            bump,
+            vault_bump,
+            redeemer: split.redeemer,
+            original_redeemer: split.redeemer,
+            swap_amount: split.swap_amount,
+            instant_refund_authority,
+            rebate_bps: 0,
+            rebate_recipient: None,
+            keeper_reward_lamports: (u128::from(self.keeper_reward_lamports)
+                * u128::from(split.swap_amount)
+                / u128::from(self.swap_amount.max(1))) as u64,
+            swap_id: compute_swap_id(
+                &split.redeemer,
+                &self.refundee,
+                &self.secret_hash,
+                split.swap_amount,
+                self.timelock_seed(),
+                index,
+            ),
+            ..self.clone()
+        }
+    }
+
+    /// The swap resulting from merging `other` into this swap through `merge_swaps`, with the
+    /// given bumps. Both must share their secret hash, redeemer, refundee and timelock, whether
+    /// they are locked under the TVL cap, and all the terms restricting who can redeem, refund
+    /// or extend them and when, e.g. their relayer, additional secret hashes, vesting, refund
+    /// trigger, boundary and sink, so that merging never drops the terms of `other`. Neither
+    /// may have a distribution or a price oracle. This fails with `SwapError::IncompatibleSwaps`
+    /// otherwise. It keeps the terms of this swap, except for the swap amount and the keeper
+    /// reward, which are summed, and the expiry slots, the later of which is kept, so that the
+    /// redeem window of either swap is never shortened. The higher of their rebates is kept.
+    /// The swap id is computed with a nonce of zero.
+    pub fn merged(&self, other: &SwapAccount, bump: u8, vault_bump: u8) -> Result<SwapAccount> {
+        require!(
+            self.secret_hash == other.secret_hash
+                && self.redeemer == other.redeemer
+                && self.refundee == other.refundee
+                && self.timelock == other.timelock
+                && self.timelock_seed() == other.timelock_seed()
+                && self.hash_kind == other.hash_kind
+                && self.secret_merkle_root == other.secret_merkle_root
+                && self.tvl_locked == other.tvl_locked,
+            SwapError::IncompatibleSwaps
+        );
+        require!(
+            self.relayer == other.relayer
+                && self.additional_secret_hashes == other.additional_secret_hashes
+                && self.vesting == other.vesting
+                && self.refund_trigger == other.refund_trigger
+                && self.single_boundary == other.single_boundary
+                && self.sink == other.sink
+                && self.instant_refund_authority == other.instant_refund_authority
+                && self.instant_refund_cliff_slots == other.instant_refund_cliff_slots
+                && self.max_extension_slots == other.max_extension_slots
+                && self.max_redeems_per_window == other.max_redeems_per_window
+                && self.rate_limit_window_slots == other.rate_limit_window_slots,
+            SwapError::IncompatibleSwaps
+        );
+        require!(
+            [self, other]
+                .iter()
+                .all(|swap| swap.distribution.is_empty() && swap.price_oracle.is_none()),
+            SwapError::IncompatibleSwaps
+        );
+        let swap_amount = self
+            .swap_amount
+            .checked_add(other.swap_amount)
+            .ok_or(SwapError::IncompatibleSwaps)?;
+        let (rebate_bps, rebate_recipient) = if other.rebate_bps > self.rebate_bps {
+            (other.rebate_bps, other.rebate_recipient)
+        } else {
+            (self.rebate_bps, self.rebate_recipient)
+        };
+        Ok(SwapAccount {
+            bump,
+            vault_bump,
+            original_redeemer: self.redeemer,
+            swap_amount,
+            expiry_slot: self.expiry_slot.max(other.expiry_slot),
+            original_expiry_slot: self.original_expiry_slot.max(other.original_expiry_slot),
+            keeper_reward_lamports: self
+                .keeper_reward_lamports
+                .saturating_add(other.keeper_reward_lamports),
+            rebate_bps,
+            rebate_recipient,
+            swap_id: compute_swap_id(
+                &self.redeemer,
+                &self.refundee,
+                &self.secret_hash,
+                swap_amount,
+                self.timelock_seed(),
+                0,
+            ),
+            ..self.clone()
+        })
+    }
+
+    /// Checks this swap for being redeemed through `redeem_siblings` at `current_slot`, with
+    /// `swap_account` and `vault` as its accounts. Only plain swaps can be redeemed as siblings,
+    /// failing with `SwapError::InvalidSiblings` otherwise, as their other accounts are not passed.
+    pub fn check_sibling(
+        &self,
+        swap_account: Pubkey,
+        vault: Pubkey,
+        current_slot: u64,
+    ) -> Result<()> {
+        require_keys_eq!(
+            self.derive_address()?,
+            swap_account,
+            SwapError::SeedMismatch
+        );
+        let expected_vault = Pubkey::create_program_address(
+            &[VAULT_SEED, swap_account.as_ref(), &[self.vault_bump]],
+            &crate::ID,
+        )
+        .map_err(|_| error!(SwapError::SeedMismatch))?;
+        require_keys_eq!(vault, expected_vault, SwapError::SeedMismatch);
+        self.check_clock(current_slot)?;
+        require!(
+            self.relayer.is_none()
+                && self.additional_secret_hashes.is_empty()
+                && self.distribution.is_empty()
+                && self.price_oracle.is_none()
+                && self.rebate_bps == 0
+                && !self.vesting
+                && self.max_redeems_per_window == 0,
+            SwapError::InvalidSiblings
+        );
+        self.check_hash_kind(HashKind::Sha256)?;
+        Ok(())
+    }
+
+    /// The algorithm locking the swap, for clients to pick the redeem to reveal its secret
+    /// through. Redeems of another kind fail with `SwapError::WrongHashKind`.
+    pub fn hash_kind(&self) -> HashKind {
+        self.hash_kind
+    }
+
+    /// The slot at which the swap was initiated, for readers to tell how long the swap has
+    /// been open without its `Initiated` event. Refer `slots_open`.
+    pub fn initiated_slot(&self) -> u64 {
+        self.initiated_slot
+    }
+
+    /// The number of slots the swap has been open for at `current_slot`, saturating at zero
+    /// for slots preceding its initiation.
+    pub fn slots_open(&self, current_slot: u64) -> u64 {
+        current_slot.saturating_sub(self.initiated_slot)
+    }
+
+    /// The parties paying for the swap, as `(funder, rent_sponsor)`: the funder deposited the
+    /// swap amount, and the rent sponsor the rent of the swap account and its vault.
+    /// These are often the same party. For swaps decoded from older layouts through
+    /// `migrate_swap_account`, the funder is backfilled from the refundee.
+    pub fn sponsors(&self) -> (Pubkey, Pubkey) {
+        (self.funder, self.rent_sponsor)
+    }
+
+    /// Fails with `SwapError::WrongHashKind` unless the swap is locked by `hash_kind`.
+    pub fn check_hash_kind(&self, hash_kind: HashKind) -> Result<()> {
+        require!(self.hash_kind == hash_kind, SwapError::WrongHashKind);
+        Ok(())
+    }
+
+    /// Fails with `SwapError::ClockAnomaly` if `current_slot` precedes the slot at which
+    /// the swap was initiated. Slots never go backwards, so this indicates a stale clock.
+    pub fn check_clock(&self, current_slot: u64) -> Result<()> {
+        require_gte!(current_slot, self.initiated_slot, SwapError::ClockAnomaly);
+        Ok(())
+    }
+
+    /// Whether the swap can be redeemed at `current_slot`, e.g. for clients to enable redeeming.
+    /// Swaps have no redeem deadline: they can be redeemed from their initiation until they are
+    /// closed, past the expiry slot included, as long as they have not been refunded. As both
+    /// close the swap account, any `SwapAccount` still held on-chain is active.
+    pub fn can_redeem(&self, current_slot: u64) -> bool {
+        current_slot >= self.initiated_slot
+    }
+
+    /// Fails with `SwapError::UseRegularRefund` if the swap has expired at `current_slot`,
+    /// from which it can only be refunded through `refund`. This guards both `instant_refund`
+    /// and `decline`.
+    pub fn check_instant_refund(&self, current_slot: u64) -> Result<()> {
+        require!(!self.is_expired(current_slot), SwapError::UseRegularRefund);
+        Ok(())
+    }
+
+    /// Fails with `SwapError::InstantRefundTooEarly` if `current_slot` precedes the end of the
+    /// instant refund cliff, i.e. `initiated_slot + instant_refund_cliff_slots`.
+    pub fn check_instant_refund_cliff(&self, current_slot: u64) -> Result<()> {
+        require_gte!(
+            current_slot,
+            self.initiated_slot
+                .saturating_add(self.instant_refund_cliff_slots),
+            SwapError::InstantRefundTooEarly
+        );
+        Ok(())
+    }
+
+    /// Whether `current_slot` is within `EXPIRY_SIGNAL_WINDOW` slots of the expiry slot,
+    /// or past it.
+    pub fn is_expiry_approaching(&self, current_slot: u64) -> bool {
+        current_slot.saturating_add(EXPIRY_SIGNAL_WINDOW) >= self.expiry_slot
+    }
+
+    /// The number of slots remaining until the expiry slot at `current_slot`, i.e.
+    /// `expiry_slot - current_slot`. This is negative once the expiry slot has passed,
+    /// and saturates at the bounds of `i64`.
+    pub fn slots_before_expiry(&self, current_slot: u64) -> i64 {
+        let slots = i128::from(self.expiry_slot) - i128::from(current_slot);
+        slots.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+    }
+
+    /// The expiry slot resulting from extending the current one by `extension` slots.
+    /// This fails if it exceeds the cap of `original_expiry_slot + max_extension_slots`.
+    pub fn extended_expiry_slot(&self, extension: u64) -> Result<u64> {
+        let expiry_slot = compute_expiry_slot(self.expiry_slot, extension)?;
+        require!(
+            expiry_slot
+                <= self
+                    .original_expiry_slot
+                    .saturating_add(self.max_extension_slots),
+            SwapError::ExtensionCapExceeded
+        );
+        Ok(expiry_slot)
+    }
+
+    /// The value in place of the timelock in the seeds and the id of the swap: its original
+    /// expiry slot if it was initiated through `initiate_at`, or its timelock otherwise.
+    pub fn timelock_seed(&self) -> u64 {
+        if self.absolute_expiry {
+            self.original_expiry_slot
+        } else {
+            self.timelock
+        }
+    }
+
+    /// The value in place of the swap amount in the seeds of this swap. Refer `seed_amount_for`.
+    pub fn amount_seed(&self) -> u64 {
+        self.seed_amount_for(self.swap_amount)
+    }
+
+    /// The value in place of `swap_amount` in the seeds of a swap seeded like this one, such as
+    /// its children and merged swap: the bucket of the amount if the seeds of this swap are
+    /// bucketed, or the exact amount otherwise. Refer `bucket_amount`.
+    pub fn seed_amount_for(&self, swap_amount: u64) -> u64 {
+        if self.bucketed_seeds {
+            bucket_amount(swap_amount)
+        } else {
+            swap_amount
+        }
+    }
+
+    /// Re-derives the address of this PDA from its stored parameters and bump.
+    /// This reproduces the seeds used in `initiate`, so a mismatch with the address of the
+    /// account actually passed indicates that its data does not correspond to its seeds.
+    pub fn derive_address(&self) -> Result<Pubkey> {
+        Pubkey::create_program_address(
+            &[
+                self.original_redeemer.as_ref(),
+                self.refundee.as_ref(),
+                &self.secret_hash,
+                &self.amount_seed().to_le_bytes(),
+                &self.timelock_seed().to_le_bytes(),
+                &[self.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(SwapError::SeedMismatch))
+    }
+}
+
+/// The optional settings of an atomic swap, chosen at initiation.
+/// Each of these retains the default behaviour of the swap when unset.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct SwapOptions {
+    /// Restricts who can trigger the redeem to this relayer, who must then sign the redeem.
+    /// If unset, anyone can trigger the redeem.
+    pub relayer: Option<Pubkey>,
+    /// A fee in lamports charged to the funder upon initiation, on top of the swap amount.
+    /// This is transferred to the `fee_collector` account of `initiate`. Zero implies no fee.
+    pub init_fee: u64,
+    /// Allows the funder to cancel the swap at any slot before it is redeemed, taking the
+    /// swap amount back. This gives the funder unilateral control over the swap until
+    /// it is redeemed, so the redeemer must only act upon such swaps knowingly.
+    pub funder_cancelable: bool,
+    /// The maximum number of slots by which the expiry slot can be extended through
+    /// `extend_timelock`, in total. Zero implies that the expiry slot cannot be extended.
+    pub max_extension_slots: u64,
+    /// The secret hashes whose preimages must all be revealed through `redeem_multi`,
+    /// on top of the preimage of the secret hash of the swap.
+    /// At most `MAX_ADDITIONAL_SECRET_HASHES` are allowed.
+    pub additional_secret_hashes: Vec<[u8; 32]>,
+    /// The entity whose consent is required for instant refunds, in place of the redeemer.
+    /// If unset, the redeemer remains the instant refund authority.
+    pub instant_refund_authority: Option<Pubkey>,
+    /// Allows refunds from the expiry slot itself, rather than only after it, so that the
+    /// expiry slot is the single boundary at which the swap becomes refundable.
+    /// Note that redeems are not bounded by the expiry slot, for any swap. They remain
+    /// possible until the swap is refunded.
+    pub single_boundary: bool,
+    /// Makes the refundee a sink, such as a burn or treasury address, rather than a party
+    /// to the swap. Such swaps are redeem-only in intent: if unredeemed past expiry, anyone
+    /// can sweep the funds to the sink through `refund`, which emits `Swept` in place of
+    /// `Refunded`. Refunds never require the refundee's signature, sink or not.
+    pub sink: bool,
+    /// Suppresses the events of the swap, for swaps indexed through account state rather
+    /// than logs. Each suppressed event saves its serialization and the `sol_log_data`
+    /// syscall, which is charged per byte logged. `ExpiryApproaching` is still emitted,
+    /// being the sole purpose of `signal_expiry_approaching`. Note that the secret is then
+    /// only revealed through the instruction data of the redeem, not through `Redeemed`.
+    pub suppress_events: bool,
+    /// Restricts who can trigger refunds past expiry to this keeper, who must then sign the
+    /// refund. The funds still go to the refundee. If unset, anyone can trigger refunds.
+    /// Anyone can regardless from `REFUND_TRIGGER_GRACE_SLOTS` slots past expiry, so that the
+    /// funds are not locked should the keeper become unavailable.
+    /// Cancellations by the funder of funder-cancelable swaps are not restricted.
+    pub refund_trigger: Option<Pubkey>,
+    /// A value chosen by the client to derive the identifier of the swap from, along with
+    /// its parameters. Refer `compute_swap_id`.
+    pub nonce: u64,
+    /// Splits the swap amount across these recipients on redeem, in place of paying out the
+    /// redeemer, e.g. for payout aggregation. The basis points of the shares must total
+    /// `TOTAL_BPS`, and at most `MAX_RECIPIENTS` are allowed. If empty, the redeemer is paid.
+    pub distribution: Vec<Share>,
+    /// Pegs the swap to `target_value` as priced by this oracle, a Pyth price update account
+    /// pricing SOL. On redeem, only the lamports worth `target_value` at the current price are
+    /// paid out, bounded by the swap amount, and the surplus is refunded to the refundee.
+    /// The price must be fully verified, published on `price_feed_id`, at most
+    /// `MAX_ORACLE_AGE` seconds old, and within a confidence interval of
+    /// `MAX_ORACLE_CONFIDENCE_BPS`. If unset, the swap amount is paid out in full.
+    pub price_oracle: Option<Pubkey>,
+    /// The value paid out on redeem of a value-pegged swap, in the currency the oracle prices
+    /// SOL in, with `TARGET_VALUE_DECIMALS`. E.g: $100 must be provided as 100,000,000.
+    pub target_value: u64,
+    /// The number of slots after initiation before which instant refunds are rejected, giving
+    /// the instant refund authority a guaranteed window to consider the swap.
+    /// If zero, instant refunds are allowed right away.
+    pub instant_refund_cliff_slots: u64,
+    /// Vests the payout linearly from initiation over the timelock, e.g. for incentive swaps.
+    /// On redeem, only the vested part is paid out, and the unvested part is refunded to the
+    /// funder. Swaps redeemed past the timelock are paid out in full. Their redeems must be
+    /// signed by the redeemer, or by the relayer if any.
+    pub vesting: bool,
+    /// A reward paid out of the swap amount to the keeper triggering the refund of the swap
+    /// past expiry, so that expired swaps do not linger. The refundee receives the rest.
+    /// It is bounded by `MAX_KEEPER_REWARD_BPS` of the swap amount. Zero implies no reward.
+    pub keeper_reward_lamports: u64,
+    /// The timelock of the counterparty leg on the other chain, converted to Solana slots.
+    /// If set, the initiate fails unless the timelock of the swap is safe against it, as per
+    /// `assert_safe_timelock`. Zero skips the check.
+    pub counterparty_timelock: u64,
+    /// Registers the swap into the `RefundQueue` along with its expiry slot, so that keepers
+    /// can find it once due for refund by scanning the queue alone. The queue must then be
+    /// passed as `refund_queue`, and fails the initiate once full.
+    pub queue_refund: bool,
+    /// Locks the swap by the root of a Merkle tree whose leaves are SHA-256 hashes of secrets,
+    /// e.g. for batched cross-chain settlement, so that revealing any one of these secrets
+    /// through `redeem_merkle` redeems the swap. `secret_hash` then only serves to derive the
+    /// swap account, and may e.g. be set to the root. If unset, `secret_hash` locks the swap.
+    pub secret_merkle_root: Option<[u8; 32]>,
+    /// The id of the Pyth price feed that `price_oracle` must publish, e.g. SOL/USD.
+    /// This must be set along with `price_oracle`, and only then.
+    pub price_feed_id: Option<[u8; 32]>,
+}
+
+/// A child swap to split a swap into through `split_swap`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Split {
+    /// The redeemer of the child swap
+    pub redeemer: Pubkey,
+    /// The part of the swap amount taken over by the child swap
+    pub swap_amount: u64,
+}
+
+/// The share of the swap amount paid out to a recipient of a split redeem
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug, InitSpace)]
+pub struct Share {
+    pub recipient: Pubkey,
+    /// The share of the swap amount, in basis points of `TOTAL_BPS`
+    pub bps: u16,
+}
+
+#[derive(Accounts)]
+// The parameters must have the exact name and order as specified in the underlying function
+// to avoid "seed constraint violation" errors.
+// Refer: https://www.anchor-lang.com/docs/references/account-constraints#instruction-attribute
+#[instruction(redeemer: Pubkey, refundee: Pubkey, secret_hash: [u8; 32], swap_amount: u64, timelock: u64)]
+pub struct Initiate<'info> {
+    /// A PDA that maintains the on-chain state of the atomic swap throughout its lifecycle.
+    /// The choice of seeds is to make the already expensive possibility of frontrunning, more expensive.
+    /// This PDA will be deleted upon completion of the swap and the resulting rent would be returned
+    /// to the rent sponsor.
+    #[account(
+        init,
+        payer = rent_sponsor,
+        seeds = [
+            redeemer.as_ref(),
+            refundee.as_ref(),
+            &secret_hash,
+            &seed_amount(swap_amount).to_le_bytes(),
+            &timelock.to_le_bytes(),
+        ],
+        bump,
+        space = SWAP_ACCOUNT_SIZE,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// A PDA that escrows the SOL involved in this swap, separately from its state.
+    /// Like `swap_account`, this will be deleted upon completion of the swap and the resulting
+    /// rent would be returned to the rent sponsor.
+    #[account(
+        init,
+        payer = rent_sponsor,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump,
+        space = SWAP_VAULT_SIZE,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// The party that deposits the funds to be involved in the atomic swap.
+    /// They must sign this transaction.
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    /// Any entity that pays the PDA rent.
+    /// Upon completion of the swap, the PDA rent refund resulting from the
+    /// deletion of `swap_account` and `vault` will be refunded to this address.
+    /// This holds even if the rent sponsor has since been emptied and no longer exists,
+    /// as the refunded rent always exceeds the rent exemption of an account without data.
+    /// The rent sponsor must be writable, as it pays for the creation of both PDAs. Since Anchor
+    /// creates them before checking any other account, a read-only rent sponsor is rejected
+    /// by the runtime with a writable privilege escalation, rather than a `SwapError`.
+    #[account(mut)]
+    pub rent_sponsor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+
+    /// The recipient of the initiation fee.
+    /// This is only required if the swap is initiated with an `init_fee`.
+    #[account(mut)]
+    pub fee_collector: Option<SystemAccount<'info>>,
+
+    /// CHECK: The wSOL token account of the funder, validated upon unwrapping.
+    /// This is only required by `initiate_from_wsol`, which unwraps it to fund the swap.
+    #[account(mut)]
+    pub wsol_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The SPL token program, closing `wsol_account`.
+    /// This is only required by `initiate_from_wsol`.
+    #[account(address = TOKEN_PROGRAM_ID)]
+    pub token_program: Option<UncheckedAccount<'info>>,
+
+    /// The PDA holding the state information of the swap being amended, closed upon amending.
+    /// This is only required by `amend`, which validates it along with `old_vault`.
+    #[account(mut)]
+    pub old_swap_account: Option<Account<'info, SwapAccount>>,
+
+    /// The PDA escrowing the funds of the swap being amended, closed upon amending.
+    /// This is only required by `amend`.
+    #[account(mut)]
+    pub old_vault: Option<Account<'info, SwapVault>>,
+
+    /// The registry of swaps due for refund, which the swap is registered into.
+    /// This is only required if the swap is initiated with `queue_refund`.
+    #[account(mut, seeds = [REFUND_QUEUE_SEED], bump = refund_queue.bump)]
+    pub refund_queue: Option<Account<'info, RefundQueue>>,
+
+    /// CHECK: The protocol-wide settings, read if created and passed. This must only be
+    /// writable while a TVL cap is configured, as the swap is then locked under it, and is
+    /// otherwise left read-only, so that initiates do not contend for it. It is the last
+    /// account, so that clients predating it need not pass it, in which case the swap is
+    /// neither locked under the TVL cap nor rate limited. It is required by `amend` if the
+    /// swap being amended was locked under the TVL cap.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub protocol_config: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemSiblings<'info> {
+    /// The protocol-wide settings, tracking the total value locked across all active swaps.
+    /// This is only required if the swap was locked under a TVL cap on initiation.
+    #[account(mut, seeds = [CONFIG_SEED], bump = protocol_config.bump)]
+    pub protocol_config: Option<Account<'info, ProtocolConfig>>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+}
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+        close = rent_sponsor,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of the atomic swap.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump = swap_account.vault_bump,
+        close = rent_sponsor,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// CHECK: Verifying the redeemer
+    /// They must sign the redeem of a vesting swap without a relayer.
+    #[account(
+        mut @ SwapError::RedeemerNotWritable,
+        address = swap_account.redeemer @ SwapError::InvalidRedeemer,
+    )]
+    pub redeemer: AccountInfo<'info>,
+
+    /// CHECK: Rent sponsor's address for refunding PDA rent
+    #[account(
+        mut @ SwapError::RentSponsorNotWritable,
+        address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor,
+    )]
+    pub rent_sponsor: AccountInfo<'info>,
+
+    /// The protocol-wide settings, tracking the total value locked across all active swaps.
+    /// This is only required if the swap was locked under a TVL cap on initiation.
+    #[account(mut, seeds = [CONFIG_SEED], bump = protocol_config.bump)]
+    pub protocol_config: Option<Account<'info, ProtocolConfig>>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+
+    /// The relayer triggering the redeem.
+    /// This is only required if the swap was initiated with a relayer.
+    pub relayer: Option<Signer<'info>>,
+
+    /// The redeems of the redeemer within the current window.
+    /// This is only required if a rate limit is in force.
+    #[account(mut)]
+    pub rate_limit: Option<Account<'info, RateLimit>>,
+
+    /// CHECK: The price oracle of the swap, validated upon reading its price.
+    /// This is only required if the swap is pegged to a value.
+    pub price_oracle: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Verifying the refundee
+    /// This is only required if the swap is pegged to a value, to refund the surplus to.
+    #[account(
+        mut @ SwapError::RefundeeNotWritable,
+        address = swap_account.refundee @ SwapError::InvalidRefundee,
+    )]
+    pub refundee: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The recipient of the rebate committed to by the redeemer, validated upon paying
+    /// out. This is only required if the redeemer committed to a rebate.
+    #[account(mut)]
+    pub rebate_recipient: Option<UncheckedAccount<'info>>,
+
+    /// The withdrawal escrow of the redeemer, holding their funds in place of paying them out.
+    /// This is only required by `redeem_to_escrow`.
+    #[account(mut)]
+    pub withdrawal_escrow: Option<Account<'info, WithdrawalEscrow>>,
+
+    /// CHECK: The funder of the swap, to which the unvested part of the payout is refunded.
+    /// This is only required if the swap is vesting and redeemed before its timelock elapsed.
+    #[account(
+        mut @ SwapError::FunderNotWritable,
+        address = swap_account.funder @ SwapError::InvalidFunder,
+    )]
+    pub funder: Option<UncheckedAccount<'info>>,
+
+    /// The registry of swaps due for refund, which the swap is removed from, if registered.
+    #[account(mut, seeds = [REFUND_QUEUE_SEED], bump = refund_queue.bump)]
+    pub refund_queue: Option<Account<'info, RefundQueue>>,
+}
+
+impl Redeem<'_> {
+    /// Pays out the swap amount to the redeemer, once the redeemer has proven knowledge of
+    /// `secret` in the manner required by the swap. `additional_secrets` must be the preimages
+    /// of the `additional_secret_hashes` of the swap, if any. All secrets are revealed
+    /// through `Redeemed`, along with `memo`. The swap amount is split across `recipients`
+    /// instead if the swap has a distribution, which they must match in order.
+    /// For value-pegged swaps, only the lamports worth the target value at the current oracle
+    /// price are paid out, with the surplus of the swap amount refunded to the refundee.
+    /// For vesting swaps, only the vested part of the payout is paid out, with the unvested
+    /// part refunded to the funder, and the redeemer must sign, unless the swap has a relayer,
+    /// failing with `SwapError::RedeemerNotSigner` otherwise. If `to_escrow` is set, the funds
+    /// of the redeemer are held in their withdrawal escrow.
+    fn pay_out(
+        &mut self,
+        recipients: &[AccountInfo],
+        secret: [u8; 32],
+        additional_secrets: Vec<[u8; 32]>,
+        memo: Option<String>,
+        to_escrow: bool,
+    ) -> Result<()> {
+        let SwapAccount {
+            refundee,
+            redeemer,
+            swap_amount,
+            timelock,
+            relayer,
+            suppress_events,
+            swap_id,
+            ..
+        } = *self.swap_account;
+
+        require_keys_eq!(
+            self.swap_account.derive_address()?,
+            self.swap_account.key(),
+            SwapError::SeedMismatch
+        );
+        check_swap_account_info(&self.swap_account.to_account_info())?;
+        let current_slot = current_clock()?.slot;
+        self.swap_account.check_clock(current_slot)?;
+        if let Some(relayer) = relayer {
+            let signer = self.relayer.as_ref().ok_or(SwapError::InvalidRelayer)?;
+            require_keys_eq!(signer.key(), relayer, SwapError::InvalidRelayer);
+        } else if self.swap_account.vesting {
+            // Otherwise, anyone learning the secret could redeem the swap early, at the expense
+            // of the redeemer, refunding the unvested part to the funder
+            require!(self.redeemer.is_signer, SwapError::RedeemerNotSigner);
+        }
+        let SwapAccount {
+            max_redeems_per_window,
+            rate_limit_window_slots,
+            ..
+        } = *self.swap_account;
+        // Only the redeemer can use up their own window. Refer `set_rate_limit`.
+        if max_redeems_per_window > 0 && self.redeemer.is_signer {
+            let rate_limit = self
+                .rate_limit
+                .as_mut()
+                .ok_or(SwapError::InvalidRateLimit)?;
+            require_keys_eq!(rate_limit.redeemer, redeemer, SwapError::InvalidRateLimit);
+            rate_limit.record(
+                current_slot,
+                max_redeems_per_window,
+                rate_limit_window_slots,
+            )?;
+        }
+        require!(
+            verify_additional_secrets(
+                &self.swap_account.additional_secret_hashes,
+                &additional_secrets
+            ),
+            SwapError::InvalidSecret
+        );
+
+        let payout = match self.swap_account.price_oracle {
+            Some(price_oracle) => {
+                let oracle = self
+                    .price_oracle
+                    .as_ref()
+                    .filter(|oracle| oracle.key() == price_oracle)
+                    .ok_or(SwapError::InvalidOracle)?;
+                let price = read_oracle_price(
+                    oracle,
+                    self.swap_account.price_feed_id,
+                    current_clock()?.unix_timestamp,
+                )?;
+                pegged_payout(self.swap_account.target_value, &price)
+                    .ok_or(SwapError::InvalidOracle)?
+                    .min(swap_amount)
+            }
+            None => swap_amount,
+        };
+        let surplus = swap_amount - payout;
+        let vested = if self.swap_account.vesting {
+            vested_amount(
+                payout,
+                self.swap_account.initiated_slot,
+                timelock,
+                current_slot,
+            )
+        } else {
+            payout
+        };
+        let unvested = payout - vested;
+        let rebate = rebate_amount(vested, self.swap_account.rebate_bps);
+        let redeemer_amount = vested - rebate;
+
+        let distribution = self.swap_account.distribution.clone();
+        self.vault.sub_lamports(swap_amount)?;
+        if surplus > 0 {
+            let refundee = self.refundee.as_ref().ok_or(SwapError::InvalidRefundee)?;
+            refundee.add_lamports(surplus)?;
+        }
+        if unvested > 0 {
+            let funder = self.funder.as_ref().ok_or(SwapError::InvalidFunder)?;
+            funder.add_lamports(unvested)?;
+        }
+        if rebate > 0 {
+            let rebate_recipient = self
+                .rebate_recipient
+                .as_ref()
+                .filter(|recipient| Some(recipient.key()) == self.swap_account.rebate_recipient)
+                .ok_or(SwapError::InvalidFunder)?;
+            rebate_recipient.add_lamports(rebate)?;
+        }
+        let mut withdrawable_after = None;
+        if to_escrow {
+            require!(distribution.is_empty(), SwapError::InvalidWithdrawalEscrow);
+            let withdrawal_escrow = self
+                .withdrawal_escrow
+                .as_mut()
+                .filter(|escrow| escrow.redeemer == redeemer)
+                .ok_or(SwapError::InvalidWithdrawalEscrow)?;
+            withdrawal_escrow.add_lamports(redeemer_amount)?;
+            withdrawable_after = Some(withdrawal_escrow.schedule(redeemer_amount, current_slot));
+        } else if distribution.is_empty() {
+            self.redeemer.add_lamports(redeemer_amount)?;
+        } else {
+            require_eq!(
+                recipients.len(),
+                distribution.len(),
+                SwapError::InvalidRecipients
+            );
+            let amounts = split_amount(redeemer_amount, &distribution);
+            for ((recipient, share), amount) in recipients.iter().zip(&distribution).zip(amounts) {
+                require_keys_eq!(
+                    recipient.key(),
+                    share.recipient,
+                    SwapError::InvalidRecipients
+                );
+                recipient.add_lamports(amount)?;
+            }
+        }
+        release_tvl(self.protocol_config.as_deref_mut(), &self.swap_account)?;
+        if let Some(stats) = self.stats.as_deref_mut() {
+            stats.redeemed = stats.redeemed.saturating_add(1);
+        }
+        if let Some(refund_queue) = &mut self.refund_queue {
+            refund_queue.deregister(&self.swap_account.key());
+        }
+
+        if suppress_events {
+            return Ok(());
+        }
+        emit!(Redeemed {
+            redeemer,
+            refundee,
+            secret,
+            swap_amount,
+            timelock,
+            relayer,
+            additional_secrets,
+            memo,
+            distribution,
+            rebate,
+            redeemer_amount,
+            unvested,
+            swap_id,
+        });
+
+        let receipt = SettlementReceipt {
+            redeemer,
+            swap_amount,
+            secret,
+            slot: current_clock()?.slot,
+        };
+        emit!(Settled {
+            receipt_hash: receipt.hash(),
+            receipt,
+            swap_id,
+        });
+        if let Some(withdrawable_after) = withdrawable_after {
+            emit!(WithdrawalScheduled {
+                redeemer,
+                amount: redeemer_amount,
+                withdrawable_after,
+                swap_id,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+        close = rent_sponsor,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of the atomic swap.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump = swap_account.vault_bump,
+        close = rent_sponsor,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// CHECK: The refundee of the swap.
+    /// It may never have been initialized or have since been closed, in which case the refund
+    /// is credited to it as a system account. Should the refund leave it below the rent
+    /// exemption, the runtime rejects the transaction as a whole, so that the funds remain in
+    /// the vault until the refundee is funded by anyone, after which the refund succeeds.
+    #[account(
+        mut @ SwapError::RefundeeNotWritable,
+        address = swap_account.refundee @ SwapError::InvalidRefundee,
+    )]
+    pub refundee: AccountInfo<'info>,
+
+    /// CHECK: Rent sponsor's address for refunding PDA rent
+    #[account(
+        mut @ SwapError::RentSponsorNotWritable,
+        address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor,
+    )]
+    pub rent_sponsor: AccountInfo<'info>,
+
+    /// The protocol-wide settings, tracking the total value locked across all active swaps.
+    /// This is only required if the swap was locked under a TVL cap on initiation.
+    #[account(mut, seeds = [CONFIG_SEED], bump = protocol_config.bump)]
+    pub protocol_config: Option<Account<'info, ProtocolConfig>>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+
+    /// The funder canceling the swap, who receives the swap amount in place of the refundee.
+    /// This is only accepted if the swap was initiated as funder-cancelable.
+    #[account(mut)]
+    pub funder: Option<Signer<'info>>,
+
+    /// The keeper triggering the refund.
+    /// This is only required if the swap was initiated with a refund trigger, until
+    /// `REFUND_TRIGGER_GRACE_SLOTS` slots past expiry.
+    pub refund_trigger: Option<Signer<'info>>,
+
+    /// The keeper triggering the refund, who receives the keeper reward of the swap, if any.
+    #[account(mut)]
+    pub keeper: Option<Signer<'info>>,
+
+    /// The registry of swaps due for refund, which the swap is removed from, if registered.
+    #[account(mut, seeds = [REFUND_QUEUE_SEED], bump = refund_queue.bump)]
+    pub refund_queue: Option<Account<'info, RefundQueue>>,
+}
+
+#[cfg(feature = "instant-refund")]
+#[derive(Accounts)]
+pub struct InstantRefund<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+        close = rent_sponsor,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of the atomic swap.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump = swap_account.vault_bump,
+        close = rent_sponsor,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// CHECK: The refundee of the swap.
+    /// It may never have been initialized or have since been closed, in which case the refund
+    /// is credited to it as a system account. Should the refund leave it below the rent
+    /// exemption, the runtime rejects the transaction as a whole, so that the funds remain in
+    /// the vault until the refundee is funded by anyone, after which the refund succeeds.
+    #[account(
+        mut @ SwapError::RefundeeNotWritable,
+        address = swap_account.refundee @ SwapError::InvalidRefundee,
+    )]
+    pub refundee: AccountInfo<'info>,
+
+    /// The instant refund authority of the swap. They must sign this transaction.
+    #[account(
+        address = swap_account.instant_refund_authority
+            @ SwapError::InvalidInstantRefundAuthority
+    )]
+    pub instant_refund_authority: Signer<'info>,
+
+    /// CHECK: Rent sponsor's address for PDA rent refund
+    #[account(
+        mut @ SwapError::RentSponsorNotWritable,
+        address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor,
+    )]
+    pub rent_sponsor: AccountInfo<'info>,
+
+    /// The protocol-wide settings, tracking the total value locked across all active swaps.
+    /// This is only required if the swap was locked under a TVL cap on initiation.
+    #[account(mut, seeds = [CONFIG_SEED], bump = protocol_config.bump)]
+    pub protocol_config: Option<Account<'info, ProtocolConfig>>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+
+    /// CHECK: Verifying the redeemer, who receives the penalty.
+    /// This is only required if the instant refund pays a penalty.
+    #[account(
+        mut @ SwapError::RedeemerNotWritable,
+        address = swap_account.redeemer @ SwapError::InvalidRedeemer,
+    )]
+    pub redeemer: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct Decline<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+        close = rent_sponsor,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of the atomic swap.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump = swap_account.vault_bump,
+        close = rent_sponsor,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// The redeemer of the swap. They must sign this transaction.
+    #[account(address = swap_account.redeemer @ SwapError::InvalidRedeemer)]
+    pub redeemer: Signer<'info>,
+
+    /// CHECK: The funder of the swap, to which the funds are returned.
+    #[account(mut, address = swap_account.funder @ SwapError::InvalidFunder)]
+    pub funder: AccountInfo<'info>,
+
+    /// CHECK: Rent sponsor's address for PDA rent refund
+    #[account(
+        mut @ SwapError::RentSponsorNotWritable,
+        address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor,
+    )]
+    pub rent_sponsor: AccountInfo<'info>,
+
+    /// The protocol-wide settings, tracking the total value locked across all active swaps.
+    /// This is only required if the swap was locked under a TVL cap on initiation.
+    #[account(mut, seeds = [CONFIG_SEED], bump = protocol_config.bump)]
+    pub protocol_config: Option<Account<'info, ProtocolConfig>>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+}
+
+#[derive(Accounts)]
+pub struct SplitSwap<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+        close = rent_sponsor,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of the atomic swap.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump = swap_account.vault_bump,
+        close = rent_sponsor,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// The funder of the swap, who must be its cancel authority. They must sign this transaction.
+    #[account(
+        address = swap_account.funder @ SwapError::InvalidFunder,
+        constraint = swap_account.cancel_authority == Some(funder.key())
+            @ SwapError::InvalidCancelAuthority,
+    )]
+    pub funder: Signer<'info>,
+
+    /// The rent sponsor of the swap, paying the rent of the children.
+    /// They must sign this transaction.
+    #[account(mut, address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor)]
+    pub rent_sponsor: Signer<'info>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MergeSwaps<'info> {
+    /// The PDA holding the state information of the swap whose terms the merged swap keeps.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+        close = rent_sponsor,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of `swap_account`.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, swap_account.key().as_ref()],
+        bump = swap_account.vault_bump,
+        close = rent_sponsor,
+    )]
+    pub vault: Account<'info, SwapVault>,
+
+    /// The PDA holding the state information of the swap merged into `swap_account`.
+    #[account(
+        mut,
+        seeds = [
+            other_swap_account.original_redeemer.as_ref(),
+            other_swap_account.refundee.key().as_ref(),
+            &other_swap_account.secret_hash,
+            &other_swap_account.amount_seed().to_le_bytes(),
+            &other_swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = other_swap_account.bump,
+        constraint = other_swap_account.key() != swap_account.key() @ SwapError::IncompatibleSwaps,
+        close = other_rent_sponsor,
+    )]
+    pub other_swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA escrowing the funds of `other_swap_account`.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, other_swap_account.key().as_ref()],
+        bump = other_swap_account.vault_bump,
+        close = other_rent_sponsor,
+    )]
+    pub other_vault: Account<'info, SwapVault>,
+
+    /// CHECK: The PDA of the merged swap, derived from the total swap amount by the handler,
+    /// which creates it.
+    #[account(mut)]
+    pub merged_swap_account: UncheckedAccount<'info>,
+
+    /// CHECK: The PDA escrowing the funds of the merged swap, derived and created by the handler.
+    #[account(mut)]
+    pub merged_vault: UncheckedAccount<'info>,
+
+    /// The funder of both swaps, who must be their cancel authority.
+    /// They must sign this transaction.
+    #[account(
+        address = swap_account.funder @ SwapError::InvalidFunder,
+        constraint = swap_account.cancel_authority == Some(funder.key())
+            && other_swap_account.cancel_authority == Some(funder.key())
+            @ SwapError::InvalidCancelAuthority,
+    )]
+    pub funder: Signer<'info>,
+
+    /// The rent sponsor of `swap_account`, paying the rent of the merged swap.
+    /// They must sign this transaction.
+    #[account(mut, address = swap_account.rent_sponsor @ SwapError::InvalidRentSponsor)]
+    pub rent_sponsor: Signer<'info>,
+
+    /// CHECK: The rent sponsor of `other_swap_account`, to which its rent is refunded.
+    #[account(mut, address = other_swap_account.rent_sponsor @ SwapError::InvalidRentSponsor)]
+    pub other_rent_sponsor: UncheckedAccount<'info>,
+
+    /// The protocol-wide activity counters, which are left as is if omitted.
+    #[account(mut, seeds = [STATS_SEED], bump = stats.bump)]
+    pub stats: Option<Account<'info, Stats>>,
+
+    pub system_program: Program<'info, System>,
+
+    /// The registry of swaps due for refund, which the merged swap takes the place of both
+    /// swaps in, if either is registered.
+    #[account(mut, seeds = [REFUND_QUEUE_SEED], bump = refund_queue.bump)]
+    pub refund_queue: Option<Account<'info, RefundQueue>>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendTimelock<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// CHECK: The redeemer of the swap. They must sign this transaction.
+    #[account(address = swap_account.redeemer @ SwapError::InvalidRedeemer)]
+    pub redeemer: Signer<'info>,
+
+    /// CHECK: The refundee of the swap. They must sign this transaction.
+    #[account(address = swap_account.refundee @ SwapError::InvalidRefundee)]
+    pub refundee: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CommitRebate<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The redeemer of the swap. They must sign this transaction.
+    #[account(address = swap_account.redeemer @ SwapError::InvalidRedeemer)]
+    pub redeemer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferRedeemRights<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        mut,
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// CHECK: The current redeemer of the swap. They must sign this transaction.
+    #[account(address = swap_account.redeemer @ SwapError::InvalidRedeemer)]
+    pub redeemer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReallocSwap<'info> {
+    /// CHECK: The PDA holding the state information of the atomic swap, in an older layout.
+    /// This cannot be deserialized as a `SwapAccount` until it has been grown, so its owner,
+    /// discriminator and seeds are verified by the handler instead.
+    #[account(mut)]
+    pub swap_account: UncheckedAccount<'info>,
+
+    /// CHECK: The PDA escrowing the funds of the swap. It is created by the handler for swaps
+    /// initiated before vaults existed, which escrow their funds in `swap_account` instead.
+    #[account(mut, seeds = [VAULT_SEED, swap_account.key().as_ref()], bump)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// The payer of the additional rent, which is returned to the rent sponsor of the swap
+    /// on completion.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CheckSecret<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSecret<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+
+    /// The PDA recording the secret of the swap.
+    /// A secret can only be recorded once, as this fails if it already exists.
+    #[account(
+        init,
+        payer = payer,
+        seeds = [REVEALED_SECRET_SEED, &swap_account.secret_hash],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + RevealedSecret::INIT_SPACE,
+    )]
+    pub revealed_secret: Account<'info, RevealedSecret>,
+
+    /// Any entity that pays the rent of the revealed secret. They must sign this transaction.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SignalExpiryApproaching<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ProgramVersion<'info> {
+    /// Taken as the only account, as the CPI client of an instruction cannot be generated
+    /// for a context without any.
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Snapshot<'info> {
+    /// The PDA holding the state information of the atomic swap.
+    #[account(
+        seeds = [
+            swap_account.original_redeemer.as_ref(),
+            swap_account.refundee.key().as_ref(),
+            &swap_account.secret_hash,
+            &swap_account.amount_seed().to_le_bytes(),
+            &swap_account.timelock_seed().to_le_bytes(),
+        ],
+        bump = swap_account.bump,
+    )]
+    pub swap_account: Account<'info, SwapAccount>,
+}
+
+#[derive(Accounts)]
+// Refer `Initiate` on the naming and order of these parameters.
+#[instruction(redeemer: Pubkey, refundee: Pubkey, secret_hash: [u8; 32], swap_amount: u64, timelock: u64)]
+pub struct TryInitiate<'info> {
+    /// CHECK: The PDA the swap is initiated at, checked for an active swap.
+    /// Like the other accounts written by `initiate`, this is validated by it, and left unchecked
+    /// here, so that this instruction does not write back a stale copy of it.
+    #[account(
+        mut,
+        seeds = [
+            redeemer.as_ref(),
+            refundee.as_ref(),
+            &secret_hash,
+            &seed_amount(swap_amount).to_le_bytes(),
+            &timelock.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub swap_account: UncheckedAccount<'info>,
+
+    /// CHECK: The PDA escrowing the funds of the swap, validated by `initiate`.
+    #[account(mut, seeds = [VAULT_SEED, swap_account.key().as_ref()], bump)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// The party that deposits the funds to be involved in the atomic swap.
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    /// Any entity that pays the PDA rent. Refer `Initiate`.
+    #[account(mut)]
+    pub rent_sponsor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: The protocol-wide activity counters, validated by `initiate`, if passed.
+    #[account(mut, seeds = [STATS_SEED], bump)]
+    pub stats: Option<UncheckedAccount<'info>>,
+
+    /// The recipient of the initiation fee.
+    /// This is only required if the swap is initiated with an `init_fee`.
+    #[account(mut)]
+    pub fee_collector: Option<SystemAccount<'info>>,
+
+    /// This program, invoked to initiate the swap.
+    pub swap_program: Program<'info, program::SolanaNativeSwaps>,
+
+    /// CHECK: The registry of swaps due for refund, validated by `initiate`.
+    /// This is only required if the swap is initiated with `queue_refund`.
+    #[account(mut, seeds = [REFUND_QUEUE_SEED], bump)]
+    pub refund_queue: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The protocol-wide settings, validated by `initiate`, and forwarded to it, as
+    /// writable or not as passed, if passed.
+    #[account(seeds = [CONFIG_SEED], bump)]
+    pub protocol_config: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct RedeemIdempotent<'info> {
+    /// CHECK: The PDA holding the state information of the atomic swap, if still active.
+    /// Like the other accounts written by `redeem`, this is validated by it, and left unchecked
+    /// here, so that this instruction does not write back a stale copy of it.
+    #[account(mut)]
+    pub swap_account: UncheckedAccount<'info>,
+
+    /// CHECK: The PDA escrowing the funds of the swap, validated by `redeem`.
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// CHECK: The redeemer of the swap, validated by `redeem`.
+    #[account(mut)]
+    pub redeemer: UncheckedAccount<'info>,
+
+    /// CHECK: The rent sponsor of the swap, validated by `redeem`.
+    #[account(mut)]
+    pub rent_sponsor: UncheckedAccount<'info>,
+
+    /// CHECK: The protocol-wide settings, validated by `redeem`.
+    /// This is only required if the swap was locked under a TVL cap on initiation.
+    #[account(mut, seeds = [CONFIG_SEED], bump)]
+    pub protocol_config: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The protocol-wide activity counters, validated by `redeem`, if passed.
+    #[account(mut, seeds = [STATS_SEED], bump)]
+    pub stats: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The receipt of the redeem, created upon redeeming and validated upon reading.
+    #[account(mut, seeds = [REDEEM_RECEIPT_SEED, swap_account.key().as_ref()], bump)]
+    pub redeem_receipt: UncheckedAccount<'info>,
+
+    /// Any entity that pays the rent of the receipt. They must sign this transaction.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// This program, invoked to redeem the swap.
+    pub swap_program: Program<'info, program::SolanaNativeSwaps>,
+
+    /// The relayer triggering the redeem.
+    /// This is only required if the swap was initiated with a relayer.
+    pub relayer: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseRedeemReceipt<'info> {
+    /// The receipt being closed.
+    #[account(
+        mut,
+        has_one = payer @ SwapError::InvalidReceiptPayer,
+        close = payer,
+    )]
+    pub redeem_receipt: Account<'info, RedeemReceipt>,
+
+    /// The entity that paid the rent of the receipt. They must sign this transaction.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+// Refer `Initiate` on the naming and order of these parameters.
+#[instruction(redeemer: Pubkey, refundee: Pubkey, secret_hash: [u8; 32], swap_amount: u64, timelock: u64)]
+pub struct AssertNotInitialized<'info> {
+    /// CHECK: The address a swap with the given parameters would have been initiated at.
+    /// This is only checked for the absence of an account.
+    #[account(
+        seeds = [
+            redeemer.as_ref(),
+            refundee.as_ref(),
+            &secret_hash,
+            &seed_amount(swap_amount).to_le_bytes(),
+            &timelock.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub swap_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitProtocolConfig<'info> {
+    /// The singleton PDA holding the protocol-wide settings.
+    #[account(
+        init,
+        payer = admin,
+        seeds = [CONFIG_SEED],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + ProtocolConfig::INIT_SPACE,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// The upgrade authority of the program, who becomes the admin of the config.
+    /// They pay the rent of the config and must sign this transaction.
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// The program data account of this program, holding its upgrade authority.
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ SwapError::InvalidAdmin,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    /// The singleton PDA holding the protocol-wide settings.
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = protocol_config.bump,
+        has_one = admin @ SwapError::InvalidAdmin,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// The admin of the config. They must sign this transaction.
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitStats<'info> {
+    /// The singleton PDA holding the protocol-wide activity counters.
+    #[account(
+        init,
+        payer = payer,
+        seeds = [STATS_SEED],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + Stats::INIT_SPACE,
+    )]
+    pub stats: Account<'info, Stats>,
+
+    /// Any entity that pays the rent of the stats. They must sign this transaction.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitRefundQueue<'info> {
+    /// The singleton PDA holding the registry of swaps due for refund.
+    #[account(
+        init,
+        payer = payer,
+        seeds = [REFUND_QUEUE_SEED],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + RefundQueue::INIT_SPACE,
+    )]
+    pub refund_queue: Account<'info, RefundQueue>,
+
+    /// Any entity that pays the rent of the refund queue. They must sign this transaction.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Deregister<'info> {
+    /// The registry of swaps due for refund, which the swap is removed from.
+    #[account(mut, seeds = [REFUND_QUEUE_SEED], bump = refund_queue.bump)]
+    pub refund_queue: Account<'info, RefundQueue>,
+
+    /// CHECK: The swap account of the swap to remove, which must no longer be active
+    pub swap_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(redeemer: Pubkey)]
+pub struct InitRateLimit<'info> {
+    /// The PDA holding the redeems of `redeemer` within the current window.
+    #[account(
+        init,
+        payer = payer,
+        seeds = [RATE_LIMIT_SEED, redeemer.as_ref()],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + RateLimit::INIT_SPACE,
+    )]
+    pub rate_limit: Account<'info, RateLimit>,
+
+    /// Any entity that pays the rent of the rate limit. They must sign this transaction.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitWithdrawalEscrow<'info> {
+    /// The PDA holding the funds of swaps redeemed to `redeemer` through `redeem_to_escrow`.
+    #[account(
+        init,
+        payer = redeemer,
+        seeds = [WITHDRAWAL_ESCROW_SEED, redeemer.key().as_ref()],
+        bump,
+        space = ANCHOR_DISCRIMINATOR + WithdrawalEscrow::INIT_SPACE,
+    )]
+    pub withdrawal_escrow: Account<'info, WithdrawalEscrow>,
+
+    /// The redeemer holding the escrow, who pays its rent. They must sign this transaction.
+    #[account(mut)]
+    pub redeemer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    /// The PDA holding the funds of the redeemer.
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_ESCROW_SEED, redeemer.key().as_ref()],
+        bump = withdrawal_escrow.bump,
+    )]
+    pub withdrawal_escrow: Account<'info, WithdrawalEscrow>,
+
+    /// The redeemer holding the escrow. They must sign this transaction.
+    #[account(mut)]
+    pub redeemer: Signer<'info>,
+}
+
+/// Represents the initiated state of the swap where the funder has deposited funds into the vault
+#[event]
+pub struct Initiated {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    /// The quantity of native SOL transferred through this atomic swap in base units (aka lamports).  
+    /// E.g: A quantity of 1 SOL will be represented as 1,000,000,000.
+    pub swap_amount: u64,
+    /// `timelock` represents the number of slots (1 slot = 400ms) after which
+    /// (non-instant) refunds are allowed
+    pub timelock: u64,
+    /// Information regarding the destination chain in the atomic swap.
+    pub destination_data: Option<Vec<u8>>,
+    /// The SHA-256 hash of the destination data, when only a commitment to it was provided.
+    pub destination_commitment: Option<[u8; 32]>,
+    /// The party that deposited the funds for the atomic swap.
+    pub funder: Pubkey,
+    /// The fee in lamports charged to the funder upon initiation, if any.
+    pub init_fee: u64,
+    /// The algorithm locking the swap, which its redeem proves knowledge of a preimage under.
+    pub hash_kind: HashKind,
+    /// The identifier of the swap. Refer `compute_swap_id`.
+    pub swap_id: [u8; 32],
+}
+/// Represents the redeemed state of the swap, where the redeemer has withdrawn funds from the vault.
+/// Note that the secret is emitted here, in place of the secret hash.
+#[event]
+pub struct Redeemed {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    /// The relayer that triggered the redeem, if the swap restricts who can trigger it.
+    pub relayer: Option<Pubkey>,
+    /// The preimages of the additional secret hashes of the swap, if any.
+    pub additional_secrets: Vec<[u8; 32]>,
+    /// The memo attached to the redeem, if any.
+    pub memo: Option<String>,
+    /// The shares the swap amount was split into, if any, in place of paying out the redeemer.
+    pub distribution: Vec<Share>,
+    /// The amount rebated to the funder, as committed to by the redeemer.
+    pub rebate: u64,
+    /// The amount paid out to the redeemer, or split across the distribution, after the rebate.
+    pub redeemer_amount: u64,
+    /// The amount refunded to the funder of a vesting swap, as not yet vested.
+    pub unvested: u64,
+    pub swap_id: [u8; 32],
+}
+/// Carries the settlement receipt of a redeemed swap, along with its canonical hash,
+/// so that light clients of other chains can verify the settlement from its inclusion
+#[event]
+pub struct Settled {
+    pub receipt: SettlementReceipt,
+    pub receipt_hash: [u8; 32],
+    pub swap_id: [u8; 32],
+}
+/// Represents the refund state of the swap, where the funds have been refunded past expiry
+#[event]
+pub struct Refunded {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    /// The keeper who triggered the refund, if they signed for the keeper reward.
+    pub keeper: Option<Pubkey>,
+    /// The part of the swap amount paid out to the keeper, the refundee receiving the rest.
+    pub keeper_reward: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the sweep of the funds of a sink swap to its sink, past expiry
+#[event]
+pub struct Swept {
+    pub redeemer: Pubkey,
+    pub sink: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    /// The keeper who triggered the sweep, if they signed for the keeper reward.
+    pub keeper: Option<Pubkey>,
+    /// The part of the swap amount paid out to the keeper, the sink receiving the rest.
+    pub keeper_reward: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the extension of the expiry slot of the swap, with the consent of both parties
+#[event]
+pub struct TimelockExtended {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub expiry_slot: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the commitment of the redeemer to rebate part of the payout to the funder
+#[event]
+pub struct RebateCommitted {
+    pub redeemer: Pubkey,
+    pub rebate_bps: u16,
+    pub rebate_recipient: Pubkey,
+    pub swap_id: [u8; 32],
+}
+/// Represents the transfer of the redeem rights of the swap to a new redeemer
+#[event]
+pub struct RedeemRightsTransferred {
+    pub old_redeemer: Pubkey,
+    pub new_redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the growth of a swap account from an older layout to the current one
+#[event]
+pub struct Realloced {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub swap_id: [u8; 32],
+}
+/// Signals that the swap is about to expire, after which it can be refunded
+#[event]
+pub struct ExpiryApproaching {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub expiry_slot: u64,
+    pub swap_id: [u8; 32],
+}
+/// Holds the full state of an active swap at `slot`, as emitted by `snapshot` for archival
+#[event]
+pub struct SwapSnapshot {
+    pub swap_account: Pubkey,
+    pub swap: SwapAccount,
+    pub slot: u64,
+}
+/// Holds the version of the program and the flags of its optional features, as emitted by
+/// `program_version`. Refer `PROGRAM_VERSION` and `FEATURE_FLAGS`.
+#[event]
+pub struct Version {
+    pub version: String,
+    pub feature_flags: u64,
+}
+/// Represents the funds of the redeemer of a swap being held in their withdrawal escrow,
+/// as redeemed through `redeem_to_escrow`
+#[event]
+pub struct WithdrawalScheduled {
+    pub redeemer: Pubkey,
+    pub amount: u64,
+    /// The slot after which the funds held in the escrow can be withdrawn
+    pub withdrawable_after: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the revelation of the secret of the swap through `record_secret`,
+/// while the swap remains active
+#[event]
+pub struct SecretRevealed {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub secret: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the canceled state of the swap, where the funds have been returned to the funder
+/// of a funder-cancelable swap before it was redeemed
+#[event]
+pub struct Canceled {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub funder: Pubkey,
+    pub swap_id: [u8; 32],
+}
+/// Represents the instant refund state of the swap, where the funds have been refunded
+/// with the consent of the instant refund authority
+#[event]
+pub struct InstantRefunded {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    /// The number of slots remaining until the expiry slot, at the slot of the instant refund.
+    /// This is never negative, as instant refunds are rejected past the expiry slot.
+    pub slots_before_expiry: i64,
+    /// The entity that consented to the instant refund.
+    pub instant_refund_authority: Pubkey,
+    /// The penalty paid out to the redeemer, if any
+    pub penalty: u64,
+    /// The amount refunded to the refundee, i.e. the swap amount less the penalty
+    pub refund_amount: u64,
+    pub swap_id: [u8; 32],
+}
+/// Represents the refund state of the swap, where the funds have been returned to the funder
+/// as the redeemer declined the swap
+#[event]
+pub struct Declined {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+    pub funder: Pubkey,
+    pub swap_id: [u8; 32],
+}
+
+/// Represents the merge of two swaps into the swap `swap_id`, through `merge_swaps`
+#[event]
+pub struct Merged {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    /// The total swap amount of the merged swap
+    pub swap_amount: u64,
+    pub timelock: u64,
+    /// The ids of the swaps merged, which have been closed
+    pub merged_swap_ids: [[u8; 32]; 2],
+    pub swap_id: [u8; 32],
+}
+
+#[error_code]
+pub enum SwapError {
+    #[msg("The provided refundee is incorrect")]
+    InvalidRefundee,
+
+    #[msg("The provided redeemer is not the original redeemer of this swap")]
+    InvalidRedeemer,
+
+    #[msg("The provided secret does not correspond to the secret hash of this swap")]
+    InvalidSecret,
+
+    #[msg("The provided rent sponsor is incorrect")]
+    InvalidRentSponsor,
+
+    #[msg("Attempt to refund before timelock expiry")]
+    RefundBeforeExpiry,
+
+    #[msg("The swap account's address does not correspond to its stored parameters")]
+    SeedMismatch,
+
+    #[msg("The provided destination data does not correspond to the destination commitment")]
+    InvalidDestinationCommitment,
+
+    #[msg("The redeem was not triggered by the relayer of this swap")]
+    InvalidRelayer,
+
+    #[msg("The provided adaptor point is not a valid Ed25519 point")]
+    InvalidAdaptorPoint,
+
+    #[msg("This swap is not locked by an adaptor point")]
+    NotAnAdaptorSwap,
+
+    #[msg("The funder's balance does not cover the swap amount and the fees")]
+    InsufficientFunderBalance,
+
+    #[msg("A fee collector must be provided to charge an initiation fee")]
+    MissingFeeCollector,
+
+    #[msg("The timelock pushes the expiry slot beyond the maximum slot")]
+    TimelockOverflow,
+
+    #[msg("The swap is not cancelable by the provided funder")]
+    InvalidCancelAuthority,
+
+    #[msg("A swap with the given parameters has already been initiated")]
+    SwapAlreadyInitialized,
+
+    #[msg("The extension pushes the expiry slot beyond the cap chosen at initiation")]
+    ExtensionCapExceeded,
+
+    #[msg("The swap is not yet within the window for signaling its expiry")]
+    TooEarlyForSignal,
+
+    #[msg("The swap requires more secret hashes than allowed")]
+    TooManySecretHashes,
+
+    #[msg("The provided instant refund authority is not the one of this swap")]
+    InvalidInstantRefundAuthority,
+
+    #[msg("The current slot precedes the initiation of this swap")]
+    ClockAnomaly,
+
+    #[msg("The swap account is not a swap account of this program")]
+    InvalidSwapAccount,
+
+    #[msg("The swap account is already of the current size")]
+    InvalidRealloc,
+
+    #[msg("The memo exceeds the maximum length")]
+    MemoTooLong,
+
+    #[msg("The expiry slot must be after the slot of initiation")]
+    ExpiryNotInFuture,
+
+    #[msg("The swap amount would exceed the cap on the total value locked")]
+    TvlCapExceeded,
+
+    #[msg("The signer is not the admin of the protocol config")]
+    InvalidAdmin,
+
+    #[msg("Refunds of this swap must be triggered by its refund trigger")]
+    InvalidRefundTrigger,
+
+    #[msg("The redeemer has reached the rate limit on redeems for the current window")]
+    RateLimited,
+
+    #[msg("The rate limit of the redeemer must be passed while a rate limit is in force")]
+    InvalidRateLimit,
+
+    #[msg("The provided token account is not a wSOL account of the funder")]
+    InvalidWsolAccount,
+
+    #[msg("The wSOL account holds less than the swap amount")]
+    InsufficientWsolBalance,
+
+    #[msg("The swap has expired and must be refunded through a regular refund")]
+    UseRegularRefund,
+
+    #[msg("The distribution has more recipients than allowed")]
+    TooManyRecipients,
+
+    #[msg("The shares of the distribution must total 10,000 basis points")]
+    InvalidDistribution,
+
+    #[msg("The provided recipients do not match the distribution of this swap")]
+    InvalidRecipients,
+
+    #[msg("The provided price oracle is not the valid oracle of this swap")]
+    InvalidOracle,
+
+    #[msg("The price of the oracle is too old")]
+    StaleOracle,
+
+    #[msg("The swap being amended must be passed along with its vault")]
+    InvalidAmendedSwap,
+
+    #[msg("The refundee must be passed as a writable account")]
+    RefundeeNotWritable,
+
+    #[msg("The redeemer must be passed as a writable account")]
+    RedeemerNotWritable,
+
+    #[msg("The provided funder is not the funder of this swap")]
+    InvalidFunder,
+
+    #[msg("The rebate must not exceed the total basis points nor lower a committed rebate")]
+    InvalidRebate,
+
+    #[msg("The Clock sysvar could not be read")]
+    ClockUnavailable,
+
+    #[msg("Instant refunds of this swap are not yet allowed")]
+    InstantRefundTooEarly,
+
+    #[msg("The withdrawal escrow of the redeemer must be passed, and the swap not be split")]
+    InvalidWithdrawalEscrow,
+
+    #[msg("The withdrawal delay of the escrow has not yet passed")]
+    WithdrawalTooEarly,
+
+    #[msg("The provided secret equals the secret hash, rather than being its preimage")]
+    SecretLooksLikeHash,
+
+    #[msg("The splits must total the swap amount, of a swap without a distribution or oracle")]
+    InvalidSplits,
+
+    #[msg("A swap cannot be split into more than MAX_SPLITS children")]
+    TooManySplits,
+
+    #[msg("The siblings must be plain swaps, each passed as its swap account, vault, redeemer and rent sponsor")]
+    InvalidSiblings,
+
+    #[msg("The swap is locked by another hash kind, and must be redeemed accordingly")]
+    WrongHashKind,
+
+    #[msg("The keeper reward cannot exceed MAX_KEEPER_REWARD_BPS of the swap amount")]
+    InvalidKeeperReward,
+
+    #[msg("The timelock must be at least MIN_SAFE_TIMELOCK_BPS of the counterparty timelock")]
+    UnsafeTimelock,
+
+    #[msg("A swap with these parameters is already active")]
+    SwapAlreadyActive,
+
+    #[msg("The secret must be hex-encoded as exactly 64 hex digits")]
+    InvalidSecretEncoding,
+
+    #[msg("The swaps must share their secret hash, redeemer, refundee and timelock to be merged")]
+    IncompatibleSwaps,
+
+    #[msg("The rent sponsor must be passed as a writable account")]
+    RentSponsorNotWritable,
+
+    #[msg("The funder must be passed as a writable account")]
+    FunderNotWritable,
+
+    #[msg("The refund queue must be passed to register the swap into it")]
+    MissingRefundQueue,
+
+    #[msg("The refund queue already holds MAX_REFUND_QUEUE_LEN swaps")]
+    RefundQueueFull,
+
+    #[msg("The penalty cannot exceed MAX_PENALTY_BPS of the swap amount")]
+    InvalidPenalty,
+
+    #[msg("The refundee must sign an instant refund paying a penalty")]
+    PenaltyNotConsented,
+
+    #[msg("No swap is active at this address, nor was one redeemed through redeem_idempotent")]
+    SwapNotRedeemed,
+
+    #[msg("Only the payer of the receipt can close it")]
+    InvalidReceiptPayer,
+
+    #[msg("A swap cannot be locked by both an adaptor point and a Merkle root")]
+    ConflictingLocks,
+
+    #[msg("The Merkle proof cannot exceed MAX_MERKLE_PROOF_LEN hashes")]
+    MerkleProofTooLong,
+
+    #[msg("The protocol config must be passed for swaps locked under the TVL cap")]
+    MissingProtocolConfig,
+
+    #[msg("The protocol config must be writable while a TVL cap is configured")]
+    ProtocolConfigNotWritable,
+
+    #[msg("The redeemer must sign the redeem of a vesting swap without a relayer")]
+    RedeemerNotSigner,
+
+    #[msg("The price of the oracle was published on another feed than that of this swap")]
+    OracleFeedMismatch,
+
+    #[msg("The confidence interval of the price of the oracle is too wide")]
+    UncertainOracle,
+}
+
+/// Helpers for tests of timelock behaviour, exposed with the `test-utils` feature so that
+/// dependent crates can use them too. `SimulatedClock` serves `Clock::get` natively, which
+/// otherwise fails outside of the runtime, and `swap_expiring_at` builds a swap to check
+/// against it.
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils {
+    use super::*;
+    use anchor_lang::solana_program::{
+        entrypoint::SUCCESS,
+        program_error::UNSUPPORTED_SYSVAR,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+    };
+    use std::{
+        cell::{Cell, RefCell},
+        sync::Once,
+    };
+
+    thread_local! {
+        // Tests run on separate threads, so that each has a clock of its own
+        static CLOCK: RefCell<Option<Clock>> = const { RefCell::new(None) };
+        static COMPUTE_UNIT_LOGS: Cell<u64> = const { Cell::new(0) };
+    }
+
+    /// Serves `Clock::get` from the simulated clock of the current thread, and fails with
+    /// `UnsupportedSysvar` like the default stubs if there is none. It also counts the
+    /// compute unit logs of the current thread, which the default stubs merely print.
+    struct SimulatedClockStubs;
+
+    impl SyscallStubs for SimulatedClockStubs {
+        fn sol_log_compute_units(&self) {
+            COMPUTE_UNIT_LOGS.set(COMPUTE_UNIT_LOGS.get() + 1);
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            CLOCK.with_borrow(|clock| match clock {
+                // SAFETY: `Clock::get` passes the address of a `Clock`
+                Some(clock) => unsafe {
+                    var_addr.cast::<Clock>().write(clock.clone());
+                    SUCCESS
+                },
+                None => UNSUPPORTED_SYSVAR,
+            })
+        }
+    }
+
+    /// A clock served to `Clock::get` on the current thread, until it is dropped.
+    /// It starts at a chosen slot and is advanced manually, with its timestamp following
+    /// at the nominal slot duration.
+    pub struct SimulatedClock {
+        origin: Clock,
+    }
+
+    impl SimulatedClock {
+        /// Starts a simulated clock at `slot`, replacing any other on the current thread.
+        pub fn at(slot: u64) -> Self {
+            static STUBS: Once = Once::new();
+            STUBS.call_once(|| {
+                set_syscall_stubs(Box::new(SimulatedClockStubs));
+            });
+
+            let origin = Clock {
+                slot,
+                ..Default::default()
+            };
+            CLOCK.set(Some(origin.clone()));
+            Self { origin }
+        }
+
+        /// Advances the clock by `slots` slots.
+        pub fn advance(&self, slots: u64) {
+            CLOCK.with_borrow_mut(|clock| {
+                if let Some(clock) = clock {
+                    clock.slot = clock.slot.saturating_add(slots);
+                    clock.unix_timestamp = estimate_slot_timestamp(clock.slot, &self.origin);
+                }
+            });
+        }
+    }
+
+    impl Drop for SimulatedClock {
+        fn drop(&mut self) {
+            CLOCK.set(None);
+        }
+    }
+
+    /// The number of times the remaining compute units were logged on the current thread
+    /// while a `SimulatedClock` was served, e.g. by handlers built with the `bench` feature.
+    pub fn compute_unit_logs() -> u64 {
+        COMPUTE_UNIT_LOGS.get()
+    }
+
+    /// Builds a swap expiring at `expiry_slot`, with its other fields left empty.
+    pub fn swap_expiring_at(expiry_slot: u64) -> SwapAccount {
+        SwapAccount {
+            expiry_slot,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::*;
+    use super::*;
+
+    #[test]
+    fn discriminators_match_anchor_sighash() {
+        let sighash = |name: &str| {
+            let preimage = format!("global:{name}");
+            hash::hash(preimage.as_bytes()).to_bytes()[..8].to_vec()
+        };
+
+        for (discriminator, derived, name) in [
+            (
+                INITIATE_DISCRIMINATOR,
+                instruction::Initiate::DISCRIMINATOR,
+                "initiate",
+            ),
+            (
+                REDEEM_DISCRIMINATOR,
+                instruction::Redeem::DISCRIMINATOR,
+                "redeem",
+            ),
+            (
+                REFUND_DISCRIMINATOR,
+                instruction::Refund::DISCRIMINATOR,
+                "refund",
+            ),
+            #[cfg(feature = "instant-refund")]
+            (
+                INSTANT_REFUND_DISCRIMINATOR,
+                instruction::InstantRefund::DISCRIMINATOR,
+                "instant_refund",
+            ),
+        ] {
+            assert_eq!(discriminator.to_vec(), sighash(name), "{name}");
+            assert_eq!(discriminator, derived, "{name}");
+        }
+    }
+
+    #[test]
+    fn instructions_keep_their_first_wire_format() {
+        let (redeemer, refundee) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let initiate = instruction::Initiate {
+            redeemer,
+            refundee,
+            secret_hash: [1; 32],
+            swap_amount: 2,
+            timelock: 3,
+            destination_data: None,
+        };
+        let expected = [
+            &INITIATE_DISCRIMINATOR[..],
+            redeemer.as_ref(),
+            refundee.as_ref(),
+            &[1; 32],
+            &2u64.to_le_bytes(),
+            &3u64.to_le_bytes(),
+            &[0],
+        ]
+        .concat();
+        assert_eq!(initiate.data(), expected);
+
+        let redeem = instruction::Redeem { secret: [4; 32] };
+        assert_eq!(
+            redeem.data(),
+            [&REDEEM_DISCRIMINATOR[..], &[4; 32]].concat()
+        );
+
+        #[cfg(feature = "instant-refund")]
+        assert_eq!(
+            instruction::InstantRefund {}.data(),
+            INSTANT_REFUND_DISCRIMINATOR
+        );
+    }
+
+    #[test]
+    fn event_discriminators_match_anchor_derivation() {
+        for (discriminator, derived, name) in [
+            (
+                INITIATED_EVENT_DISCRIMINATOR,
+                Initiated::DISCRIMINATOR,
+                "Initiated",
+            ),
+            (
+                REDEEMED_EVENT_DISCRIMINATOR,
+                Redeemed::DISCRIMINATOR,
+                "Redeemed",
+            ),
+            (
+                SETTLED_EVENT_DISCRIMINATOR,
+                Settled::DISCRIMINATOR,
+                "Settled",
+            ),
+            (
+                REFUNDED_EVENT_DISCRIMINATOR,
+                Refunded::DISCRIMINATOR,
+                "Refunded",
+            ),
+            (SWEPT_EVENT_DISCRIMINATOR, Swept::DISCRIMINATOR, "Swept"),
+            (
+                TIMELOCK_EXTENDED_EVENT_DISCRIMINATOR,
+                TimelockExtended::DISCRIMINATOR,
+                "TimelockExtended",
+            ),
+            (
+                REBATE_COMMITTED_EVENT_DISCRIMINATOR,
+                RebateCommitted::DISCRIMINATOR,
+                "RebateCommitted",
+            ),
+            (
+                REDEEM_RIGHTS_TRANSFERRED_EVENT_DISCRIMINATOR,
+                RedeemRightsTransferred::DISCRIMINATOR,
+                "RedeemRightsTransferred",
+            ),
+            (
+                REALLOCED_EVENT_DISCRIMINATOR,
+                Realloced::DISCRIMINATOR,
+                "Realloced",
+            ),
+            (
+                EXPIRY_APPROACHING_EVENT_DISCRIMINATOR,
+                ExpiryApproaching::DISCRIMINATOR,
+                "ExpiryApproaching",
+            ),
+            (
+                SWAP_SNAPSHOT_EVENT_DISCRIMINATOR,
+                SwapSnapshot::DISCRIMINATOR,
+                "SwapSnapshot",
+            ),
+            (
+                VERSION_EVENT_DISCRIMINATOR,
+                Version::DISCRIMINATOR,
+                "Version",
+            ),
+            (
+                WITHDRAWAL_SCHEDULED_EVENT_DISCRIMINATOR,
+                WithdrawalScheduled::DISCRIMINATOR,
+                "WithdrawalScheduled",
+            ),
+            (
+                SECRET_REVEALED_EVENT_DISCRIMINATOR,
+                SecretRevealed::DISCRIMINATOR,
+                "SecretRevealed",
+            ),
+            (
+                CANCELED_EVENT_DISCRIMINATOR,
+                Canceled::DISCRIMINATOR,
+                "Canceled",
+            ),
+            (
+                INSTANT_REFUNDED_EVENT_DISCRIMINATOR,
+                InstantRefunded::DISCRIMINATOR,
+                "InstantRefunded",
+            ),
+            (
+                DECLINED_EVENT_DISCRIMINATOR,
+                Declined::DISCRIMINATOR,
+                "Declined",
+            ),
+            (MERGED_EVENT_DISCRIMINATOR, Merged::DISCRIMINATOR, "Merged"),
+        ] {
+            let preimage = format!("event:{name}");
+            assert_eq!(
+                discriminator,
+                hash::hash(preimage.as_bytes()).to_bytes()[..8],
+                "{name}"
+            );
+            assert_eq!(discriminator, derived, "{name}");
+        }
+    }
+
+    #[test]
+    fn events_hold_the_redeemer_at_a_fixed_offset() {
+        use anchor_lang::Event;
+
+        let swap = swap_expiring_at(1_000);
+        let receipt = SettlementReceipt {
+            redeemer: swap.redeemer,
+            swap_amount: swap.swap_amount,
+            secret: [0; 32],
+            slot: 0,
+        };
+        let settled = Settled {
+            receipt_hash: receipt.hash(),
+            receipt,
+            swap_id: swap.swap_id,
+        }
+        .data();
+        assert_eq!(settled[8..40], swap.redeemer.to_bytes());
+
+        let snapshot = SwapSnapshot {
+            swap_account: Pubkey::new_unique(),
+            swap: swap.clone(),
+            slot: 0,
+        }
+        .data();
+        assert_eq!(
+            snapshot[SNAPSHOT_REDEEMER_OFFSET..SNAPSHOT_REDEEMER_OFFSET + 32],
+            swap.redeemer.to_bytes()
+        );
+    }
+
+    #[test]
+    fn destination_commitment_rejects_wrong_length_payload() {
+        let destination_data = [7u8; 256];
+        let destination_commitment = hash::hash(&destination_data).to_bytes();
+
+        assert!(verify_destination_commitment(&destination_commitment, &destination_data).is_ok());
+        assert!(
+            verify_destination_commitment(&destination_commitment, &destination_data[..255])
+                .is_err()
+        );
+        assert!(verify_destination_commitment(&destination_commitment, &[7u8; 257]).is_err());
+    }
+
+    #[cfg(feature = "verbose")]
+    #[test]
+    fn initiation_log_shows_secret_hash_hex() {
+        let mut secret_hash = [0xab; 32];
+        secret_hash[0] = 0x01;
+        let swap_account = Pubkey::new_unique();
+
+        let log = initiation_log(&secret_hash, &swap_account);
+        assert!(log.contains(&swap_account.to_string()));
+        assert!(log.ends_with(&format!("01{}", "ab".repeat(31))));
+    }
+
+    #[test]
+    fn expiry_slot_near_max_slot() {
+        assert_eq!(compute_expiry_slot(u64::MAX - 10, 10).unwrap(), u64::MAX);
+        assert_eq!(compute_expiry_slot(u64::MAX, 0).unwrap(), u64::MAX);
+        assert!(compute_expiry_slot(u64::MAX - 10, 11).is_err());
+        assert!(compute_expiry_slot(1, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn slot_timestamps_are_projected_at_400ms() {
+        let clock = Clock {
+            slot: 1_000,
+            unix_timestamp: 1_700_000_000,
+            ..Default::default()
+        };
+
+        assert_eq!(estimate_slot_timestamp(1_000, &clock), 1_700_000_000);
+        assert_eq!(estimate_slot_timestamp(1_150, &clock), 1_700_000_060);
+        // Partial seconds are rounded down, both forwards and backwards
+        assert_eq!(estimate_slot_timestamp(1_001, &clock), 1_700_000_000);
+        assert_eq!(estimate_slot_timestamp(999, &clock), 1_699_999_999);
+        assert_eq!(estimate_slot_timestamp(0, &clock), 1_699_999_600);
+        assert_eq!(
+            estimate_slot_timestamp(2_500_001_000, &clock),
+            2_700_000_000
+        );
+
+        let clock = Clock {
+            slot: 0,
+            unix_timestamp: i64::MAX,
+            ..Default::default()
+        };
+        assert_eq!(estimate_slot_timestamp(u64::MAX, &clock), i64::MAX);
+    }
+
+    #[test]
+    fn expiry_near_max_slot() {
+        let swap = swap_expiring_at(u64::MAX - 1);
+        assert!(!swap.is_expired(u64::MAX - 1));
+        assert!(swap.is_expired(u64::MAX));
+
+        // A swap expiring at the maximum slot never expires
+        let swap = swap_expiring_at(u64::MAX);
+        assert!(!swap.is_expired(u64::MAX));
+        assert!(!swap.is_expired(0));
+    }
+
+    #[test]
+    fn single_boundary_expires_at_expiry_slot() {
+        let swap = SwapAccount {
+            single_boundary: true,
+            ..swap_expiring_at(1_000)
+        };
+        assert!(!swap.is_expired(999));
+        assert!(swap.is_expired(1_000));
+        assert!(!swap_expiring_at(1_000).is_expired(1_000));
+
+        let swap = SwapAccount {
+            single_boundary: true,
+            ..swap_expiring_at(u64::MAX)
+        };
+        assert!(!swap.is_expired(u64::MAX - 1));
+        assert!(swap.is_expired(u64::MAX));
+    }
+
+    #[test]
+    fn instant_refunds_open_at_the_end_of_the_cliff() {
+        let swap = SwapAccount {
+            initiated_slot: 900,
+            instant_refund_cliff_slots: 50,
+            ..swap_expiring_at(1_000)
+        };
+        assert_eq!(
+            swap.check_instant_refund_cliff(949).unwrap_err(),
+            error!(SwapError::InstantRefundTooEarly)
+        );
+        assert!(swap.check_instant_refund_cliff(950).is_ok());
+        assert!(swap.check_instant_refund_cliff(1_000).is_ok());
+
+        // Without a cliff, instant refunds are allowed from the initiation
+        let swap = SwapAccount {
+            instant_refund_cliff_slots: 0,
+            ..swap
+        };
+        assert!(swap.check_instant_refund_cliff(900).is_ok());
+
+        // A cliff reaching past the maximum slot saturates at it
+        let swap = SwapAccount {
+            instant_refund_cliff_slots: u64::MAX,
+            ..swap
+        };
+        assert!(swap.check_instant_refund_cliff(u64::MAX - 1).is_err());
+    }
+
+    #[test]
+    fn redeems_are_possible_until_the_swap_is_closed() {
+        let swap = SwapAccount {
+            initiated_slot: 900,
+            ..swap_expiring_at(1_000)
+        };
+        // Active
+        assert!(swap.can_redeem(900));
+        assert!(swap.can_redeem(1_000));
+        // Expired, yet not refunded
+        assert!(swap.is_expired(1_001));
+        assert!(swap.can_redeem(1_001));
+        assert!(swap.can_redeem(u64::MAX));
+        // A stale clock, preceding the initiation
+        assert!(!swap.can_redeem(899));
+    }
+
+    #[test]
+    fn refunds_open_past_expiry_slot_on_simulated_clock() {
+        let swap = swap_expiring_at(1_000);
+        let clock = SimulatedClock::at(999);
+        let refundable = || swap.is_expired(Clock::get().unwrap().slot);
+
+        assert!(!refundable());
+        clock.advance(1);
+        // The expiry slot itself still belongs to the redeem window
+        assert!(!refundable());
+        clock.advance(1);
+        assert!(refundable());
+
+        // The timestamp follows at 400ms per slot
+        clock.advance(3);
+        assert_eq!(Clock::get().unwrap().unix_timestamp, 2);
+        drop(clock);
+        assert!(Clock::get().is_err());
+    }
+
+    #[test]
+    fn clock_is_unavailable_outside_the_runtime() {
+        assert_eq!(
+            current_clock().unwrap_err(),
+            error!(SwapError::ClockUnavailable)
+        );
+        let _clock = SimulatedClock::at(1_000);
+        assert_eq!(current_clock().unwrap().slot, 1_000);
+    }
+
+    #[test]
+    fn instant_refunds_end_at_expiry() {
+        let swap = swap_expiring_at(1_000);
+        swap.check_instant_refund(999).unwrap();
+        swap.check_instant_refund(1_000).unwrap();
+        assert_eq!(
+            swap.check_instant_refund(1_001).unwrap_err(),
+            error!(SwapError::UseRegularRefund)
+        );
+
+        let swap = SwapAccount {
+            single_boundary: true,
+            ..swap
+        };
+        swap.check_instant_refund(999).unwrap();
+        assert_eq!(
+            swap.check_instant_refund(1_000).unwrap_err(),
+            error!(SwapError::UseRegularRefund)
+        );
+    }
+
+    #[test]
+    fn swap_address_matches_stored_parameters() {
+        let (redeemer, refundee) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let secret_hash = hash::hash(b"secret").to_bytes();
+        let (address, bump) = find_swap_address(&redeemer, &refundee, &secret_hash, 1_000, 100);
+
+        let swap = SwapAccount {
+            bump,
+            original_redeemer: redeemer,
+            refundee,
+            secret_hash,
+            swap_amount: 1_000,
+            timelock: 100,
+            bucketed_seeds: cfg!(feature = "bucketed-seeds"),
+            ..Default::default()
+        };
+        assert_eq!(swap.derive_address().unwrap(), address);
+        assert_ne!(
+            find_swap_address(&redeemer, &refundee, &secret_hash, 1_000, 101).0,
+            address
+        );
+    }
+
+    #[test]
+    fn swaps_keep_the_seeds_they_were_initiated_with() {
+        let (redeemer, refundee) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let secret_hash = hash::hash(b"secret").to_bytes();
+        let seeds = |amount_seed: u64| {
+            Pubkey::find_program_address(
+                &[
+                    redeemer.as_ref(),
+                    refundee.as_ref(),
+                    &secret_hash,
+                    &amount_seed.to_le_bytes(),
+                    &100u64.to_le_bytes(),
+                ],
+                &crate::ID,
+            )
+        };
+        let swap = |bucketed_seeds: bool| {
+            let (_, bump) = seeds(if bucketed_seeds { 512 } else { 1_000 });
+            SwapAccount {
+                bump,
+                original_redeemer: redeemer,
+                refundee,
+                secret_hash,
+                swap_amount: 1_000,
+                timelock: 100,
+                bucketed_seeds,
+                ..Default::default()
+            }
+        };
+
+        // Whichever features the current build has
+        let exact = swap(false);
+        assert_eq!(exact.amount_seed(), 1_000);
+        assert_eq!(exact.derive_address().unwrap(), seeds(1_000).0);
+        let bucketed = swap(true);
+        assert_eq!(bucketed.amount_seed(), 512);
+        assert_eq!(bucketed.derive_address().unwrap(), seeds(512).0);
+        // Children and merged swaps are seeded like their parents
+        assert_eq!(exact.seed_amount_for(3_000), 3_000);
+        assert_eq!(bucketed.seed_amount_for(3_000), 2_048);
+    }
+
+    #[test]
+    fn absolute_expiry_swaps_are_seeded_by_their_expiry_slot() {
+        let (redeemer, refundee) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let secret_hash = hash::hash(b"secret").to_bytes();
+        let (address, bump) = find_swap_address(&redeemer, &refundee, &secret_hash, 1_000, 5_000);
+
+        // Initiated at slot 4_900 to expire at slot 5_000, then extended
+        let swap = SwapAccount {
+            bump,
+            original_redeemer: redeemer,
+            refundee,
+            secret_hash,
+            swap_amount: 1_000,
+            timelock: 100,
+            expiry_slot: 5_050,
+            original_expiry_slot: 5_000,
+            absolute_expiry: true,
+            bucketed_seeds: cfg!(feature = "bucketed-seeds"),
+            ..Default::default()
+        };
+        assert_eq!(swap.timelock_seed(), 5_000);
+        assert_eq!(swap.derive_address().unwrap(), address);
+        assert!(validate_swap_seeds(&swap, &address, &crate::ID).is_ok());
+
+        let relative = SwapAccount {
+            absolute_expiry: false,
+            ..swap
+        };
+        assert_eq!(relative.timelock_seed(), 100);
+        assert!(validate_swap_seeds(&relative, &address, &crate::ID).is_err());
+    }
+
+    #[test]
+    fn validate_swap_seeds_rejects_tampered_accounts() {
+        let (redeemer, refundee) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let secret_hash = hash::hash(b"secret").to_bytes();
+        let (address, bump) = find_swap_address(&redeemer, &refundee, &secret_hash, 1_000, 100);
+        let swap = SwapAccount {
+            bump,
+            original_redeemer: redeemer,
+            refundee,
+            secret_hash,
+            swap_amount: 1_000,
+            timelock: 100,
+            bucketed_seeds: cfg!(feature = "bucketed-seeds"),
+            ..Default::default()
+        };
+        assert!(validate_swap_seeds(&swap, &address, &crate::ID).is_ok());
+
+        let mismatch = error!(SwapError::SeedMismatch);
+        let tampered = SwapAccount {
+            timelock: 101,
+            ..swap.clone()
+        };
+        assert_eq!(
+            validate_swap_seeds(&tampered, &address, &crate::ID).unwrap_err(),
+            mismatch
+        );
+        let tampered = SwapAccount {
+            bump: bump.wrapping_sub(1),
+            ..swap.clone()
+        };
+        assert_eq!(
+            validate_swap_seeds(&tampered, &address, &crate::ID).unwrap_err(),
+            mismatch
+        );
+        assert_eq!(
+            validate_swap_seeds(&swap, &address, &Pubkey::new_unique()).unwrap_err(),
+            mismatch
+        );
+    }
+
+    #[test]
+    fn slots_before_expiry_signs_and_bounds() {
+        assert_eq!(swap_expiring_at(1_000).slots_before_expiry(900), 100);
+        assert_eq!(swap_expiring_at(1_000).slots_before_expiry(1_000), 0);
+        assert_eq!(swap_expiring_at(1_000).slots_before_expiry(1_100), -100);
+        assert_eq!(swap_expiring_at(u64::MAX).slots_before_expiry(0), i64::MAX);
+        assert_eq!(swap_expiring_at(0).slots_before_expiry(u64::MAX), i64::MIN);
+    }
+
+    #[test]
+    fn clock_going_backwards_is_an_anomaly() {
+        let swap = SwapAccount {
+            initiated_slot: 1_000,
+            ..Default::default()
+        };
+
+        assert!(swap.check_clock(1_000).is_ok());
+        assert!(swap.check_clock(u64::MAX).is_ok());
+        assert_eq!(
+            swap.check_clock(999).unwrap_err(),
+            error!(SwapError::ClockAnomaly)
+        );
+    }
+
+    #[test]
+    fn expiry_signal_window_boundary() {
+        let swap = swap_expiring_at(1_000);
+        assert!(!swap.is_expiry_approaching(1_000 - EXPIRY_SIGNAL_WINDOW - 1));
+        assert!(swap.is_expiry_approaching(1_000 - EXPIRY_SIGNAL_WINDOW));
+        assert!(swap.is_expiry_approaching(1_000));
+        assert!(swap.is_expiry_approaching(u64::MAX));
+
+        // Swaps expiring within the window from genesis can be signaled right away
+        assert!(swap_expiring_at(EXPIRY_SIGNAL_WINDOW).is_expiry_approaching(0));
+        assert!(
+            !swap_expiring_at(u64::MAX).is_expiry_approaching(u64::MAX - EXPIRY_SIGNAL_WINDOW - 1)
+        );
+    }
+
+    #[test]
+    fn extension_cap_boundary() {
+        let mut swap = SwapAccount {
+            expiry_slot: 1_000,
+            original_expiry_slot: 1_000,
+            max_extension_slots: 500,
+            ..Default::default()
+        };
+
+        assert_eq!(swap.extended_expiry_slot(500).unwrap(), 1_500);
+        assert!(swap.extended_expiry_slot(501).is_err());
+
+        // The cap is counted from the original expiry slot, across extensions
+        swap.expiry_slot = swap.extended_expiry_slot(300).unwrap();
+        assert_eq!(swap.extended_expiry_slot(200).unwrap(), 1_500);
+        assert!(swap.extended_expiry_slot(201).is_err());
+
+        swap.max_extension_slots = 0;
+        swap.expiry_slot = 1_000;
+        assert_eq!(swap.extended_expiry_slot(0).unwrap(), 1_000);
+        assert!(swap.extended_expiry_slot(1).is_err());
+    }
+
+    #[test]
+    fn extension_cap_near_max_slot() {
+        let swap = SwapAccount {
+            expiry_slot: u64::MAX - 1,
+            original_expiry_slot: u64::MAX - 1,
+            max_extension_slots: u64::MAX,
+            ..Default::default()
+        };
+
+        assert_eq!(swap.extended_expiry_slot(1).unwrap(), u64::MAX);
+        assert!(swap.extended_expiry_slot(2).is_err());
+    }
+
+    #[test]
+    fn unpack_swap_account_decodes_known_blob() {
+        let mut data = vec![53, 126, 9, 14, 14, 197, 105, 182]; // Discriminator
+        data.extend(1_000u64.to_le_bytes()); // Expiry slot
+        data.push(254); // Bump
+        data.extend([1; 32]); // Redeemer
+        data.extend([2; 32]); // Refundee
+        data.extend([3; 32]); // Secret hash
+        data.extend(5_000u64.to_le_bytes()); // Swap amount
+        data.extend([4; 32]); // Rent sponsor
+        data.extend(100u64.to_le_bytes()); // Timelock
+        data.push(0); // No destination commitment
+        data.push(1); // A relayer
+        data.extend([5; 32]);
+        data.extend([0, 0]); // No adaptor point and cancel authority
+        data.extend(900u64.to_le_bytes()); // Original expiry slot
+        data.extend(50u64.to_le_bytes()); // Max extension slots
+        data.extend(1u32.to_le_bytes()); // One additional secret hash
+        data.extend([6; 32]);
+        data.extend([7; 32]); // Instant refund authority
+        data.extend(800u64.to_le_bytes()); // Initiated slot
+        data.extend([8; 32]); // Original redeemer
+        data.push(1); // Single boundary
+        data.push(0); // Not a sink
+        data.push(0); // Events not suppressed
+        data.push(0); // No refund trigger
+        data.extend([9; 32]); // Swap id
+        data.extend(1u32.to_le_bytes()); // One share
+        data.extend([10; 32]);
+        data.extend(TOTAL_BPS.to_le_bytes());
+        data.push(0); // No price oracle
+        data.extend(0u64.to_le_bytes()); // No target value
+        data.extend([11; 32]); // Funder
+        data.extend(0u16.to_le_bytes()); // No rebate
+        data.push(0); // No rebate recipient
+        data.push(0); // Relative expiry
+        data.extend(0u64.to_le_bytes()); // No instant refund cliff
+        data.push(0); // Not vesting
+        data.push(0); // SHA-256 hash kind
+        data.extend(0u64.to_le_bytes()); // No keeper reward
+        data.extend(0u64.to_le_bytes()); // No counterparty timelock
+        data.push(0); // No secret Merkle root
+        data.push(253); // Vault bump
+        data.push(1); // Locked under the TVL cap
+        data.extend(3u64.to_le_bytes()); // Max redeems per window
+        data.extend(150u64.to_le_bytes()); // Rate limit window
+        data.push(0); // No price feed id
+        data.resize(SWAP_ACCOUNT_SIZE, 0);
+
+        let swap = unpack_swap_account(&data).unwrap();
+        assert_eq!(
+            (swap.expiry_slot, swap.bump, swap.vault_bump),
+            (1_000, 254, 253)
+        );
+        assert_eq!(swap.redeemer, Pubkey::new_from_array([1; 32]));
+        assert_eq!(swap.refundee, Pubkey::new_from_array([2; 32]));
+        assert_eq!(swap.secret_hash, [3; 32]);
+        assert_eq!(swap.swap_amount, 5_000);
+        assert_eq!(swap.rent_sponsor, Pubkey::new_from_array([4; 32]));
+        assert_eq!(swap.timelock, 100);
+        assert_eq!(swap.destination_commitment, None);
+        assert_eq!(swap.relayer, Some(Pubkey::new_from_array([5; 32])));
+        assert_eq!((swap.adaptor_point, swap.cancel_authority), (None, None));
+        assert_eq!(
+            (swap.original_expiry_slot, swap.max_extension_slots),
+            (900, 50)
+        );
+        assert_eq!(swap.additional_secret_hashes, vec![[6; 32]]);
+        assert_eq!(
+            swap.instant_refund_authority,
+            Pubkey::new_from_array([7; 32])
+        );
+        assert_eq!(swap.initiated_slot, 800);
+        assert_eq!(swap.original_redeemer, Pubkey::new_from_array([8; 32]));
+        assert!(swap.single_boundary);
+        assert!(!swap.sink);
+        assert!(!swap.suppress_events);
+        assert_eq!(swap.refund_trigger, None);
+        assert_eq!(swap.swap_id, [9; 32]);
+        assert_eq!(swap.funder, Pubkey::new_from_array([11; 32]));
+        assert_eq!(
+            swap.distribution,
+            [Share {
+                recipient: Pubkey::new_from_array([10; 32]),
+                bps: TOTAL_BPS,
+            }]
+        );
+        assert!(swap.tvl_locked);
+        assert_eq!(
+            (swap.max_redeems_per_window, swap.rate_limit_window_slots),
+            (3, 150)
+        );
+
+        data[0] ^= 1;
+        assert!(unpack_swap_account(&data).is_err());
+    }
+
+    #[test]
+    fn swap_account_never_holds_a_secret() {
+        let secret = hash::hash(b"secret").to_bytes();
+        let secret_hash = hash::hash(&secret).to_bytes();
+        let additional_secret = hash::hash(b"additional secret").to_bytes();
+        let swap = SwapAccount {
+            secret_hash,
+            additional_secret_hashes: vec![hash::hash(&additional_secret).to_bytes()],
+            destination_commitment: Some([1; 32]),
+            adaptor_point: Some([2; 32]),
+            swap_id: [3; 32],
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        swap.try_serialize(&mut data).unwrap();
+
+        for secret in [secret, additional_secret] {
+            assert!(!data.windows(32).any(|window| window == secret));
+        }
+    }
+
+    #[test]
+    fn rebates_round_down_in_favor_of_the_redeemer() {
+        assert_eq!(rebate_amount(1_000, 0), 0);
+        assert_eq!(rebate_amount(1_000, 250), 25);
+        assert_eq!(rebate_amount(999, 5_000), 499);
+        assert_eq!(rebate_amount(1_000, TOTAL_BPS), 1_000);
+        assert_eq!(rebate_amount(u64::MAX, TOTAL_BPS), u64::MAX);
+        // Never exceeds the payout, whatever the basis points
+        assert_eq!(rebate_amount(1_000, u16::MAX), 1_000);
+    }
+
+    #[test]
+    fn penalties_round_down_in_favor_of_the_refundee() {
+        assert_eq!(penalty_amount(1_000, 0), 0);
+        assert_eq!(penalty_amount(1_000, 250), 25);
+        assert_eq!(penalty_amount(999, MAX_PENALTY_BPS), 99);
+        assert_eq!(penalty_amount(u64::MAX, TOTAL_BPS), u64::MAX);
+    }
+
+    #[test]
+    fn distribution_splits_the_whole_amount() {
+        let share = |bps| Share {
+            recipient: Pubkey::new_unique(),
+            bps,
+        };
+        validate_distribution(&[]).unwrap();
+        let two_way = [share(5_000), share(5_000)];
+        validate_distribution(&two_way).unwrap();
+        assert_eq!(split_amount(1_001, &two_way), [500, 501]);
+
+        let three_way = [share(3_333), share(3_333), share(3_334)];
+        validate_distribution(&three_way).unwrap();
+        assert_eq!(split_amount(10, &three_way), [3, 3, 4]);
+        assert_eq!(
+            split_amount(u64::MAX, &three_way).iter().sum::<u64>(),
+            u64::MAX
+        );
+
+        assert_eq!(
+            validate_distribution(&[share(5_000), share(4_999)]).unwrap_err(),
+            error!(SwapError::InvalidDistribution)
+        );
+        assert_eq!(
+            validate_distribution(&[share(u16::MAX), share(u16::MAX)]).unwrap_err(),
+            error!(SwapError::InvalidDistribution)
+        );
+        assert_eq!(
+            validate_distribution(&[share(2_000); MAX_RECIPIENTS + 1]).unwrap_err(),
+            error!(SwapError::TooManyRecipients)
+        );
+    }
+
+    /// The id of the feed published by mock price updates
+    const FEED_ID: [u8; 32] = [2; 32];
+
+    /// The data of a mock `PriceUpdateV2` of `FEED_ID`, of the given verification level
+    fn price_update(
+        verification_level: &[u8],
+        price: i64,
+        confidence: u64,
+        exponent: i32,
+        publish_time: i64,
+    ) -> Vec<u8> {
+        let mut data = hash::hash(b"account:PriceUpdateV2").to_bytes()[..8].to_vec();
+        data.extend([1; 32]); // Write authority
+        data.extend(verification_level);
+        data.extend(FEED_ID);
+        data.extend(price.to_le_bytes());
+        data.extend(confidence.to_le_bytes());
+        data.extend(exponent.to_le_bytes());
+        data.extend(publish_time.to_le_bytes());
+        data.extend(publish_time.to_le_bytes()); // Previous publish time
+        data.extend([0; 24]); // EMA price, EMA confidence and posted slot
+        data
+    }
+
+    #[test]
+    fn oracle_price_requires_a_fresh_verified_pyth_update() {
+        let key = Pubkey::new_unique();
+        let read = |owner: &Pubkey, mut data: Vec<u8>, now: i64| {
+            let mut lamports = 0;
+            let info = AccountInfo::new(
+                &key,
+                false,
+                false,
+                &mut lamports,
+                &mut data,
+                owner,
+                false,
+                0,
+            );
+            read_oracle_price(&info, Some(FEED_ID), now)
+        };
+        let data = price_update(&[1], 150_00000000, 1_000, -8, 1_000);
+        let expected = OraclePrice {
+            feed_id: FEED_ID,
+            price: 150_00000000,
+            confidence: 1_000,
+            exponent: -8,
+            publish_time: 1_000,
+        };
+        assert_eq!(
+            read(&PYTH_RECEIVER_ID, data.clone(), 1_000).unwrap(),
+            expected
+        );
+        assert_eq!(
+            read(&PYTH_RECEIVER_ID, data.clone(), 1_000 + MAX_ORACLE_AGE).unwrap(),
+            expected
+        );
+        assert_eq!(
+            read(&PYTH_RECEIVER_ID, data.clone(), 1_001 + MAX_ORACLE_AGE).unwrap_err(),
+            error!(SwapError::StaleOracle)
+        );
+        assert_eq!(
+            read(&Pubkey::new_unique(), data, 1_000).unwrap_err(),
+            error!(SwapError::InvalidOracle)
+        );
+
+        // Partially verified updates are rejected
+        let data = price_update(&[0, 5], 150_00000000, 1_000, -8, 1_000);
+        assert_eq!(
+            read(&PYTH_RECEIVER_ID, data, 1_000).unwrap_err(),
+            error!(SwapError::InvalidOracle)
+        );
+
+        // Prices are only accepted within the maximum confidence interval
+        let max_confidence = 150_00000000 / 10_000 * u64::from(MAX_ORACLE_CONFIDENCE_BPS);
+        let data = price_update(&[1], 150_00000000, max_confidence, -8, 1_000);
+        read(&PYTH_RECEIVER_ID, data, 1_000).unwrap();
+        let data = price_update(&[1], 150_00000000, max_confidence + 1, -8, 1_000);
+        assert_eq!(
+            read(&PYTH_RECEIVER_ID, data, 1_000).unwrap_err(),
+            error!(SwapError::UncertainOracle)
+        );
+    }
+
+    #[test]
+    fn oracle_price_must_be_published_on_the_feed_of_the_swap() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = price_update(&[1], 150_00000000, 1_000, -8, 1_000);
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &PYTH_RECEIVER_ID,
+            false,
+            0,
+        );
+        assert_eq!(
+            read_oracle_price(&info, Some([3; 32]), 1_000).unwrap_err(),
+            error!(SwapError::OracleFeedMismatch)
+        );
+        read_oracle_price(&info, Some(FEED_ID), 1_000).unwrap();
+        // Swaps pegged before feed ids were recorded accept any feed
+        read_oracle_price(&info, None, 1_000).unwrap();
+    }
+
+    #[test]
+    fn pegged_payout_converts_value_to_lamports() {
+        let price = |price, exponent| OraclePrice {
+            feed_id: [0; 32],
+            price,
+            confidence: 0,
+            exponent,
+            publish_time: 0,
+        };
+        // $100 at $150 per SOL
+        assert_eq!(
+            pegged_payout(100_000_000, &price(150_00000000, -8)),
+            Some(666_666_666)
+        );
+        assert_eq!(
+            pegged_payout(100_000_000, &price(150, 0)),
+            Some(666_666_666)
+        );
+        assert_eq!(pegged_payout(100_000_000, &price(15, 1)), Some(666_666_666));
+
+        assert_eq!(pegged_payout(100_000_000, &price(0, -8)), None);
+        assert_eq!(pegged_payout(100_000_000, &price(-1, -8)), None);
+        assert_eq!(pegged_payout(u64::MAX, &price(1, -8)), None);
+        assert_eq!(pegged_payout(1, &price(1, i32::MIN)), None);
+        assert_eq!(pegged_payout(1, &price(1, i32::MAX)), None);
+    }
+
+    #[test]
+    fn funder_balance_covers_amount_and_rent() {
+        let rent = Rent::default();
+        let rent_exemption = rent.minimum_balance(0);
+        assert!(funder_balance_suffices(1_000, 1_000, &rent));
+        assert!(!funder_balance_suffices(999, 1_000, &rent));
+        assert!(funder_balance_suffices(
+            1_000 + rent_exemption,
+            1_000,
+            &rent
+        ));
+        assert!(!funder_balance_suffices(
+            1_000 + rent_exemption - 1,
+            1_000,
+            &rent
+        ));
+        assert!(!funder_balance_suffices(1_001, 1_000, &rent));
+    }
+
+    #[test]
+    fn redeem_return_data_decodes_as_a_tuple() {
+        let swap_account = Pubkey::new_unique();
+        let data = redeem_return_data(&swap_account, 1_000, &[7; 32]).unwrap();
+
+        assert_eq!(data.len(), 32 + 8 + 32);
+        assert_eq!(
+            <(Pubkey, u64, [u8; 32])>::try_from_slice(&data).unwrap(),
+            (swap_account, 1_000, [7; 32])
+        );
+    }
+
+    #[test]
+    fn secret_hex_decodes_only_64_hex_digits() {
+        let mut secret = [0xab; 32];
+        secret[0] = 0x01;
+        let secret_hex = format!("01{}", "ab".repeat(31));
+        assert_eq!(decode_secret_hex(&secret_hex).unwrap(), secret);
+        assert_eq!(
+            decode_secret_hex(&secret_hex.to_uppercase()).unwrap(),
+            secret
+        );
+
+        for malformed in [
+            String::new(),
+            "ab".repeat(31),
+            "ab".repeat(33),
+            format!("0x{}", "ab".repeat(31)),
+            format!("zz{}", "ab".repeat(31)),
+            format!("é{}", "a".repeat(62)),
+            format!("+1{}", "ab".repeat(31)),
+        ] {
+            assert_eq!(
+                decode_secret_hex(&malformed).unwrap_err(),
+                error!(SwapError::InvalidSecretEncoding),
+                "{malformed}"
+            );
+        }
+    }
+
+    #[test]
+    fn secret_equal_to_its_hash_gets_a_hint() {
+        let secret = [1; 32];
+        let secret_hash = hash::hash(&secret).to_bytes();
+
+        assert!(verify_secret(&secret, &secret_hash).is_ok());
+        assert_eq!(
+            verify_secret(&secret_hash, &secret_hash).unwrap_err(),
+            error!(SwapError::SecretLooksLikeHash)
+        );
+        assert_eq!(
+            verify_secret(&[2; 32], &secret_hash).unwrap_err(),
+            error!(SwapError::InvalidSecret)
+        );
+    }
+
+    #[test]
+    fn additional_secrets_require_all_preimages() {
+        let secrets = [[1; 32], [2; 32], [3; 32]];
+        let secret_hashes = secrets.map(|secret| hash::hash(&secret).to_bytes());
+
+        assert!(verify_additional_secrets(&secret_hashes, &secrets));
+        assert!(verify_additional_secrets(&[], &[]));
+        // Partial, reordered and surplus preimages are rejected
+        assert!(!verify_additional_secrets(&secret_hashes, &secrets[..2]));
+        assert!(!verify_additional_secrets(
+            &secret_hashes,
+            &[[1; 32], [3; 32], [2; 32]]
+        ));
+        assert!(!verify_additional_secrets(
+            &secret_hashes,
+            &[[1; 32], [2; 32], [4; 32]]
+        ));
+        assert!(!verify_additional_secrets(&[], &secrets[..1]));
+    }
+
+    #[test]
+    fn settlement_receipt_hash_is_canonical() {
+        let receipt = SettlementReceipt {
+            redeemer: Pubkey::new_from_array([1; 32]),
+            swap_amount: 1_000,
+            secret: [2; 32],
+            slot: 42,
+        };
+        // sha256(0x01 * 32 ‖ be64(1000) ‖ 0x02 * 32 ‖ be64(42))
+        let expected = [
+            0xe5, 0xe4, 0x38, 0x3a, 0x3f, 0x8a, 0x87, 0x72, 0x7c, 0x2a, 0x09, 0x32, 0xb4, 0xd0,
+            0x77, 0xdd, 0x8f, 0xb5, 0xbb, 0x6d, 0x7d, 0xff, 0x5b, 0x19, 0x46, 0xf8, 0x24, 0xd2,
+            0x4d, 0xe0, 0x7d, 0xfd,
+        ];
+
+        assert_eq!(receipt.hash(), expected);
+        assert_ne!(
+            SettlementReceipt {
+                slot: 43,
+                ..receipt
+            }
+            .hash(),
+            expected
+        );
+    }
+
+    #[test]
+    fn swap_id_is_canonical() {
+        let swap_id = |nonce| {
+            compute_swap_id(
+                &Pubkey::new_from_array([1; 32]),
+                &Pubkey::new_from_array([2; 32]),
+                &[3; 32],
+                1_000,
+                100,
+                nonce,
+            )
+        };
+        // sha256(0x01 * 32 ‖ 0x02 * 32 ‖ 0x03 * 32 ‖ be64(1000) ‖ be64(100) ‖ be64(7))
+        let expected = [
+            0x48, 0xa0, 0xb1, 0x3d, 0x0a, 0xe0, 0x7d, 0x63, 0xfd, 0x5a, 0x6c, 0x67, 0x0c, 0xc0,
+            0x0f, 0x3b, 0x62, 0x6b, 0x59, 0xca, 0x2a, 0x76, 0x7f, 0x30, 0xa0, 0x81, 0x2e, 0x32,
+            0x07, 0x2b, 0x0d, 0xf1,
+        ];
+
+        assert_eq!(swap_id(7), expected);
+        assert_eq!(swap_id(7), swap_id(7));
+        assert_ne!(swap_id(8), expected);
+    }
+
+    #[test]
+    fn refunded_rent_revives_an_emptied_sponsor() {
+        let rent = Rent::default();
+        assert!(rent.is_exempt(rent_for_swap(&rent), 0));
+    }
+
+    #[test]
+    fn rent_for_swap_matches_init() {
+        // `init` charges the minimum balance of the `space` of each account it creates,
+        // which must fit the largest swap account
+        let swap = SwapAccount {
+            destination_commitment: Some([0; 32]),
+            relayer: Some(Pubkey::default()),
+            adaptor_point: Some([0; 32]),
+            cancel_authority: Some(Pubkey::default()),
+            additional_secret_hashes: vec![[0; 32]; MAX_ADDITIONAL_SECRET_HASHES],
+            refund_trigger: Some(Pubkey::default()),
+            distribution: vec![
+                Share {
+                    recipient: Pubkey::default(),
+                    bps: 0,
+                };
+                MAX_RECIPIENTS
+            ],
+            price_oracle: Some(Pubkey::default()),
+            rebate_recipient: Some(Pubkey::default()),
+            secret_merkle_root: Some([0; 32]),
+            price_feed_id: Some([0; 32]),
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        swap.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), SWAP_ACCOUNT_SIZE);
+
+        let rent = Rent::default();
+        assert_eq!(
+            rent_for_swap(&rent),
+            rent.minimum_balance(data.len()) + rent.minimum_balance(ANCHOR_DISCRIMINATOR)
+        );
+    }
+
+    #[test]
+    fn swap_account_info_requires_owner_and_discriminator() {
+        let key = Pubkey::new_unique();
+        let check = |owner: &Pubkey, data: &mut [u8]| {
+            let mut lamports = 0;
+            let info = AccountInfo::new(&key, false, true, &mut lamports, data, owner, false, 0);
+            check_swap_account_info(&info)
+        };
+        let mut data = SwapAccount::DISCRIMINATOR.to_vec();
+        data.resize(SWAP_ACCOUNT_SIZE, 0);
+
+        assert!(check(&crate::ID, &mut data).is_ok());
+        // A spoofed account with the right layout, owned by another program
+        assert_eq!(
+            check(&system_program::ID, &mut data).unwrap_err(),
+            error!(SwapError::InvalidSwapAccount)
+        );
+        // An account of this program holding something other than a swap
+        let mut vault_data = SwapVault::DISCRIMINATOR.to_vec();
+        assert_eq!(
+            check(&crate::ID, &mut vault_data).unwrap_err(),
+            error!(SwapError::InvalidSwapAccount)
+        );
+        assert!(check(&crate::ID, &mut []).is_err());
+    }
+
+    #[test]
+    fn system_program_account_rejects_spoofed_programs() {
+        // `Initiate` moves funds through a CPI into its `system_program` account
+        fn check(key: &Pubkey, executable: bool) -> Result<()> {
+            let (mut lamports, mut data) = (1, vec![]);
+            let owner = bpf_loader_upgradeable::ID;
+            let info = AccountInfo::new(
+                key,
+                false,
+                false,
+                &mut lamports,
+                &mut data,
+                &owner,
+                executable,
+                0,
+            );
+            Program::<System>::try_from(&info).map(|_| ())
+        }
+
+        assert!(check(&system_program::ID, true).is_ok());
+        assert_eq!(
+            check(&crate::ID, true).unwrap_err(),
+            error!(ErrorCode::InvalidProgramId)
+        );
+        assert_eq!(
+            check(&Pubkey::new_unique(), true).unwrap_err(),
+            error!(ErrorCode::InvalidProgramId)
+        );
+    }
+
+    #[test]
+    fn migrate_swap_account_decodes_baseline_layout() {
+        // The layout of the first deployment, with the fields up to the timelock
+        let (redeemer, refundee) = (
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        );
+        let (swap_account, bump) = Pubkey::find_program_address(
+            &[
+                redeemer.as_ref(),
+                refundee.as_ref(),
+                &[3; 32],
+                &5_000u64.to_le_bytes(),
+                &100u64.to_le_bytes(),
+            ],
+            &crate::ID,
+        );
+        let mut data = SwapAccount::DISCRIMINATOR.to_vec();
+        data.extend(1_000u64.to_le_bytes()); // Expiry slot
+        data.push(bump);
+        data.extend(redeemer.to_bytes());
+        data.extend(refundee.to_bytes());
+        data.extend([3; 32]); // Secret hash
+        data.extend(5_000u64.to_le_bytes()); // Swap amount
+        data.extend([4; 32]); // Rent sponsor
+        data.extend(100u64.to_le_bytes()); // Timelock
+        assert_eq!(data.len(), BASELINE_SWAP_ACCOUNT_SIZE);
+        assert_eq!(BASELINE_SWAP_ACCOUNT_SIZE, 161);
+
+        let swap = migrate_swap_account(&data).unwrap();
+        assert_eq!((swap.expiry_slot, swap.bump), (1_000, bump));
+        assert_eq!((swap.redeemer, swap.refundee), (redeemer, refundee));
+        assert_eq!(swap.rent_sponsor, Pubkey::new_from_array([4; 32]));
+        assert_eq!((swap.swap_amount, swap.timelock), (5_000, 100));
+        assert_eq!((swap.relayer, swap.cancel_authority), (None, None));
+        assert!(swap.additional_secret_hashes.is_empty());
+        assert!(!swap.single_boundary);
+        // The swap was initiated before bucketed seeds existed
+        assert!(!swap.bucketed_seeds);
+        // Fields which must not be empty are backfilled
+        assert_eq!(swap.original_redeemer, swap.redeemer);
+        assert_eq!(swap.instant_refund_authority, swap.redeemer);
+        assert_eq!(swap.original_expiry_slot, 1_000);
+        assert_eq!(swap.funder, swap.refundee);
+        assert_eq!(
+            swap.swap_id,
+            compute_swap_id(&swap.redeemer, &swap.refundee, &[3; 32], 5_000, 100, 0)
+        );
+        // The migrated swap remains at the address it was initiated at
+        assert_eq!(swap.derive_address().unwrap(), swap_account);
+
+        // The zero-extended baseline decodes alike, as it is a prefix of the current layout
+        let mut extended = data.clone();
+        extended.resize(SWAP_ACCOUNT_SIZE, 0);
+        assert_eq!(
+            migrate_swap_account(&extended)
+                .unwrap()
+                .try_to_vec()
+                .unwrap(),
+            swap.try_to_vec().unwrap()
+        );
+        data[0] ^= 1;
+        assert!(migrate_swap_account(&data).is_err());
+
+        // Swaps of the current layout are left as they are
+        let mut grown = Vec::new();
+        swap.try_serialize(&mut grown).unwrap();
+        grown.resize(SWAP_ACCOUNT_SIZE, 0);
+        let remigrated = migrate_swap_account(&grown).unwrap();
+        assert_eq!(remigrated.original_redeemer, swap.redeemer);
+        assert_eq!(remigrated.initiated_slot, 0);
+    }
+
+    #[test]
+    fn sponsors_are_the_funder_then_the_rent_sponsor() {
+        let (funder, rent_sponsor) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let swap = SwapAccount {
+            funder,
+            rent_sponsor,
+            ..swap_expiring_at(600)
+        };
+        assert_eq!(swap.sponsors(), (funder, rent_sponsor));
+    }
+
+    #[test]
+    fn hash_kind_round_trips_and_gates_redeems() {
+        let (_, adaptor_point) = ADAPTOR_VECTORS[0];
+        for (adaptor_point, hash_kind) in [
+            (None, HashKind::Sha256),
+            (Some(adaptor_point), HashKind::Ed25519Adaptor),
+        ] {
+            let swap = SwapAccount {
+                adaptor_point,
+                hash_kind: HashKind::of(adaptor_point, None),
+                ..swap_expiring_at(600)
+            };
+            let mut data = Vec::new();
+            swap.try_serialize(&mut data).unwrap();
+            assert_eq!(unpack_swap_account(&data).unwrap().hash_kind(), hash_kind);
+            assert!(swap.check_hash_kind(hash_kind).is_ok());
+
+            // Swaps predating the hash kind are backfilled from their adaptor point
+            let legacy = SwapAccount {
+                hash_kind: HashKind::default(),
+                ..swap
+            };
+            let mut data = Vec::new();
+            legacy.try_serialize(&mut data).unwrap();
+            assert_eq!(migrate_swap_account(&data).unwrap().hash_kind(), hash_kind);
+        }
+
+        let swap = swap_expiring_at(600);
+        assert_eq!(
+            swap.check_hash_kind(HashKind::Ed25519Adaptor).unwrap_err(),
+            error!(SwapError::WrongHashKind)
+        );
+    }
+
+    #[test]
+    fn withdrawal_escrow_holds_funds_for_the_delay_after_the_last_redeem() {
+        let mut escrow = WithdrawalEscrow {
+            delay_slots: 100,
+            ..Default::default()
+        };
+        assert_eq!(escrow.schedule(1_000, 500), 600);
+        assert_eq!(
+            escrow.withdraw(600).unwrap_err(),
+            error!(SwapError::WithdrawalTooEarly)
+        );
+        // A later redeem holds back the funds of the earlier one too
+        assert_eq!(escrow.schedule(2_000, 550), 650);
+        assert!(escrow.withdraw(601).is_err());
+        assert_eq!(escrow.withdraw(651).unwrap(), 3_000);
+        assert_eq!(escrow.amount, 0);
+        assert_eq!(escrow.withdraw(651).unwrap(), 0);
+    }
+
+    #[test]
+    fn feature_flags_match_the_build() {
+        assert_eq!(
+            FEATURE_FLAGS & FEATURE_BUCKETED_SEEDS != 0,
+            cfg!(feature = "bucketed-seeds")
+        );
+        assert_eq!(
+            FEATURE_FLAGS & FEATURE_VERBOSE != 0,
+            cfg!(feature = "verbose")
+        );
+        assert_eq!(
+            FEATURE_FLAGS & FEATURE_NO_INSTANT_REFUND != 0,
+            !cfg!(feature = "instant-refund")
+        );
+        assert_eq!(FEATURE_FLAGS & FEATURE_BENCH != 0, cfg!(feature = "bench"));
+        assert_eq!(
+            FEATURE_FLAGS & FEATURE_LITE_REDEEM != 0,
+            cfg!(feature = "lite-redeem")
+        );
+        assert_eq!(
+            FEATURE_FLAGS
+                & !(FEATURE_BUCKETED_SEEDS
+                    | FEATURE_VERBOSE
+                    | FEATURE_NO_INSTANT_REFUND
+                    | FEATURE_BENCH
+                    | FEATURE_LITE_REDEEM),
+            0
+        );
+    }
+
+    #[test]
+    fn instant_refund_is_only_dispatched_with_its_feature() {
+        // Without any account, a dispatched instant refund fails on its accounts instead
+        let fallback_not_found =
+            ProgramError::Custom(anchor_lang::error::ErrorCode::InstructionFallbackNotFound.into());
+        let result = entry(&crate::ID, &[], &INSTANT_REFUND_DISCRIMINATOR);
+        assert!(result.is_err());
+        assert_eq!(
+            result == Err(fallback_not_found),
+            !cfg!(feature = "instant-refund")
+        );
+    }
+
+    #[test]
+    fn lite_redeem_is_only_dispatched_with_its_feature() {
+        // Without any account, a dispatched lite redeem fails on its accounts instead
+        let fallback_not_found =
+            ProgramError::Custom(anchor_lang::error::ErrorCode::InstructionFallbackNotFound.into());
+        let lite_redeem_data = |len| {
+            let mut data = vec![7; len];
+            data[0] = LITE_REDEEM_TAG;
+            data
+        };
+        let result = entry(&crate::ID, &[], &lite_redeem_data(33));
+        assert!(result.is_err());
+        assert_eq!(
+            result == Err(fallback_not_found.clone()),
+            !cfg!(feature = "lite-redeem")
+        );
+        // Only the tag followed by exactly the length of a secret is taken for one
+        for data in [
+            vec![7; 32],
+            vec![7; 33],
+            lite_redeem_data(32),
+            lite_redeem_data(34),
+        ] {
+            assert_eq!(
+                entry(&crate::ID, &[], &data),
+                Err(fallback_not_found.clone())
+            );
+        }
+    }
+
+    #[cfg(feature = "lite-redeem")]
+    #[test]
+    fn lite_redeem_validates_its_accounts() {
+        let _clock = SimulatedClock::at(0);
+        let secret = [7; 32];
+        let (swap_key, redeemer, rent_sponsor) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (vault_key, vault_bump) =
+            Pubkey::find_program_address(&[VAULT_SEED, swap_key.as_ref()], &crate::ID);
+        let swap = SwapAccount {
+            redeemer,
+            rent_sponsor,
+            secret_hash: hash::hash(&secret).to_bytes(),
+            vault_bump,
+            ..swap_expiring_at(100)
+        };
+        let redeem = |secret: [u8; 32], keys: [Pubkey; 4], redeemer_writable: bool| {
+            let mut swap_data = Vec::new();
+            swap.try_serialize(&mut swap_data).unwrap();
+            let mut vault_data = SwapVault::DISCRIMINATOR.to_vec();
+            let (mut lamports, mut redeemer_data, mut rent_sponsor_data) = ([1; 4], vec![], vec![]);
+            let [swap_lamports, vault_lamports, redeemer_lamports, rent_sponsor_lamports] =
+                &mut lamports;
+            let accounts = [
+                AccountInfo::new(
+                    &keys[0],
+                    false,
+                    true,
+                    swap_lamports,
+                    &mut swap_data,
+                    &crate::ID,
+                    false,
+                    0,
+                ),
+                AccountInfo::new(
+                    &keys[1],
+                    false,
+                    true,
+                    vault_lamports,
+                    &mut vault_data,
+                    &crate::ID,
+                    false,
+                    0,
+                ),
+                AccountInfo::new(
+                    &keys[2],
+                    false,
+                    redeemer_writable,
+                    redeemer_lamports,
+                    &mut redeemer_data,
+                    &system_program::ID,
+                    false,
+                    0,
+                ),
+                AccountInfo::new(
+                    &keys[3],
+                    false,
+                    true,
+                    rent_sponsor_lamports,
+                    &mut rent_sponsor_data,
+                    &system_program::ID,
+                    false,
+                    0,
+                ),
+            ];
+            entry(
+                &crate::ID,
+                &accounts,
+                &[&[LITE_REDEEM_TAG], &secret[..]].concat(),
+            )
+        };
+        let keys = [swap_key, vault_key, redeemer, rent_sponsor];
+        let expect = |error: SwapError| Err(ProgramError::from(error!(error)));
+
+        assert_eq!(
+            redeem(secret, keys, false),
+            expect(SwapError::RedeemerNotWritable)
+        );
+        let mut wrong = keys;
+        wrong[2] = Pubkey::new_unique();
+        assert_eq!(
+            redeem(secret, wrong, true),
+            expect(SwapError::InvalidRedeemer)
+        );
+        let mut wrong = keys;
+        wrong[3] = Pubkey::new_unique();
+        assert_eq!(
+            redeem(secret, wrong, true),
+            expect(SwapError::InvalidRentSponsor)
+        );
+        let mut wrong = keys;
+        wrong[1] = Pubkey::new_unique();
+        assert_eq!(redeem(secret, wrong, true), expect(SwapError::SeedMismatch));
+        // The secret is verified like in `redeem`
+        assert_eq!(
+            redeem([8; 32], keys, true),
+            expect(SwapError::InvalidSecret)
+        );
+        // The seeds of the swap account, which Anchor would check upfront, are checked on paying
+        // out, as the swap account of these accounts is no PDA of the swap
+        assert_eq!(redeem(secret, keys, true), expect(SwapError::SeedMismatch));
+    }
+
+    #[test]
+    fn refund_trigger_lapses_after_the_grace_period() {
+        let keeper = Pubkey::new_unique();
+        let swap = SwapAccount {
+            refund_trigger: Some(keeper),
+            ..swap_expiring_at(1_000)
+        };
+        let grace_end = 1_000 + REFUND_TRIGGER_GRACE_SLOTS;
+        for slot in [1_001, grace_end] {
+            assert!(swap.check_refund_trigger(Some(keeper), slot).is_ok());
+            assert_eq!(
+                swap.check_refund_trigger(None, slot).unwrap_err(),
+                error!(SwapError::InvalidRefundTrigger)
+            );
+            assert_eq!(
+                swap.check_refund_trigger(Some(Pubkey::new_unique()), slot)
+                    .unwrap_err(),
+                error!(SwapError::InvalidRefundTrigger)
+            );
+        }
+        // Anyone can trigger the refund once the keeper had their chance
+        assert!(swap.check_refund_trigger(None, grace_end + 1).is_ok());
+        assert!(swap
+            .check_refund_trigger(Some(Pubkey::new_unique()), grace_end + 1)
+            .is_ok());
+        // Unrestricted refunds are open to anyone right away
+        let open = swap_expiring_at(1_000);
+        assert!(open.check_refund_trigger(None, 1_001).is_ok());
+        // A swap expiring at the last slot has no grace period past it
+        let restricted_forever = SwapAccount {
+            refund_trigger: Some(keeper),
+            ..swap_expiring_at(u64::MAX)
+        };
+        assert!(restricted_forever
+            .check_refund_trigger(None, u64::MAX)
+            .is_err());
+    }
+
+    #[test]
+    fn slots_open_count_from_initiation() {
+        let swap = SwapAccount {
+            initiated_slot: 500,
+            ..swap_expiring_at(600)
+        };
+        assert_eq!(swap.initiated_slot(), 500);
+        assert_eq!(swap.slots_open(500), 0);
+        assert_eq!(swap.slots_open(650), 150);
+        assert_eq!(swap.slots_open(400), 0);
+    }
+
+    #[test]
+    fn compute_units_are_only_logged_with_the_bench_feature() {
+        let _clock = SimulatedClock::at(0);
+        let native_loader = pubkey!("NativeLoader1111111111111111111111111111111");
+        let (mut lamports, mut data) = (1, vec![]);
+        let system_program = AccountInfo::new(
+            &system_program::ID,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &native_loader,
+            true,
+            0,
+        );
+        let logs = compute_unit_logs();
+        entry(
+            &crate::ID,
+            &[system_program],
+            instruction::ProgramVersion::DISCRIMINATOR,
+        )
+        .unwrap();
+        // Once at the entry of the handler, and once at its exit
+        let expected = if cfg!(feature = "bench") { 2 } else { 0 };
+        assert_eq!(compute_unit_logs() - logs, expected);
+    }
+
+    #[test]
+    fn splits_must_total_the_swap_amount() {
+        let split = |swap_amount| Split {
+            redeemer: Pubkey::new_unique(),
+            swap_amount,
+        };
+        assert!(validate_splits(&[split(500), split(300), split(200)], 1_000).is_ok());
+        assert!(validate_splits(&[split(1_000)], 1_000).is_ok());
+
+        for splits in [
+            vec![],
+            vec![split(500), split(499)],
+            vec![split(500), split(501)],
+            vec![split(1_000), split(0)],
+            vec![split(u64::MAX), split(1_001)],
+        ] {
+            assert_eq!(
+                validate_splits(&splits, 1_000).unwrap_err(),
+                error!(SwapError::InvalidSplits)
+            );
+        }
+        assert_eq!(
+            validate_splits(&[split(200); MAX_SPLITS + 1], 1_000).unwrap_err(),
+            error!(SwapError::TooManySplits)
+        );
+    }
+
+    #[test]
+    fn child_swaps_keep_the_terms_of_their_parent() {
+        let redeemer = Pubkey::new_unique();
+        let parent = SwapAccount {
+            redeemer,
+            original_redeemer: redeemer,
+            instant_refund_authority: redeemer,
+            swap_amount: 1_000,
+            timelock: 100,
+            rebate_bps: 500,
+            rebate_recipient: Some(Pubkey::new_unique()),
+            ..swap_expiring_at(600)
+        };
+        let split = Split {
+            redeemer: Pubkey::new_unique(),
+            swap_amount: 400,
+        };
+
+        let child = parent.child(&split, 7, 8, 1);
+        assert_eq!(child.redeemer, split.redeemer);
+        assert_eq!(child.original_redeemer, split.redeemer);
+        assert_eq!(child.instant_refund_authority, split.redeemer);
+        assert_eq!(child.swap_amount, 400);
+        assert_eq!((child.bump, child.vault_bump), (7, 8));
+        assert_eq!((child.rebate_bps, child.rebate_recipient), (0, None));
+        assert_eq!(child.expiry_slot, parent.expiry_slot);
+        assert_eq!(child.secret_hash, parent.secret_hash);
+        assert_eq!(child.timelock_seed(), parent.timelock_seed());
+        assert_ne!(child.swap_id, parent.child(&split, 7, 8, 2).swap_id);
+
+        // A custom instant refund authority is kept
+        let authority = Pubkey::new_unique();
+        let parent = SwapAccount {
+            instant_refund_authority: authority,
+            ..parent
+        };
+        assert_eq!(
+            parent.child(&split, 7, 8, 1).instant_refund_authority,
+            authority
+        );
+    }
+
+    #[test]
+    fn only_plain_swaps_are_redeemed_as_siblings() {
+        let mut swap = swap_expiring_at(600);
+        let (swap_account, bump) = Pubkey::find_program_address(
+            &[
+                swap.original_redeemer.as_ref(),
+                swap.refundee.as_ref(),
+                &swap.secret_hash,
+                &swap.amount_seed().to_le_bytes(),
+                &swap.timelock_seed().to_le_bytes(),
+            ],
+            &crate::ID,
+        );
+        let (vault, vault_bump) =
+            Pubkey::find_program_address(&[VAULT_SEED, swap_account.as_ref()], &crate::ID);
+        swap.bump = bump;
+        swap.vault_bump = vault_bump;
+
+        assert!(swap.check_sibling(swap_account, vault, 0).is_ok());
+        assert_eq!(
+            swap.check_sibling(swap_account, Pubkey::new_unique(), 0)
+                .unwrap_err(),
+            error!(SwapError::SeedMismatch)
+        );
+        assert_eq!(
+            swap.check_sibling(Pubkey::new_unique(), vault, 0)
+                .unwrap_err(),
+            error!(SwapError::SeedMismatch)
+        );
+        let with_relayer = SwapAccount {
+            relayer: Some(Pubkey::new_unique()),
+            ..swap.clone()
+        };
+        let with_rebate = SwapAccount {
+            rebate_bps: 100,
+            ..swap.clone()
+        };
+        for swap in [with_relayer, with_rebate] {
+            assert_eq!(
+                swap.check_sibling(swap_account, vault, 0).unwrap_err(),
+                error!(SwapError::InvalidSiblings)
+            );
+        }
+    }
+
+    #[test]
+    fn payout_vests_linearly_over_the_timelock() {
+        let vested = |current_slot| vested_amount(1_000, 500, 100, current_slot);
+        assert_eq!(vested(500), 0);
+        assert_eq!(vested(525), 250);
+        assert_eq!(vested(550), 500);
+        assert_eq!(vested(600), 1_000);
+        assert_eq!(vested(700), 1_000);
+        // Rounded down, and stale clocks vest nothing
+        assert_eq!(vested_amount(10, 500, 3, 501), 3);
+        assert_eq!(vested(499), 0);
+        assert_eq!(
+            vested_amount(u64::MAX, 0, u64::MAX, u64::MAX - 1),
+            u64::MAX - 1
+        );
+    }
+
+    #[test]
+    fn timelock_is_safe_only_outlasting_the_counterparty_leg() {
+        // Safe configurations
+        assert!(assert_safe_timelock(150, 100).is_ok());
+        assert!(assert_safe_timelock(1_000, 100).is_ok());
+        assert!(assert_safe_timelock(2, 1).is_ok());
+        assert!(assert_safe_timelock(u64::MAX, u64::MAX / 2).is_ok());
+
+        // Unsafe configurations
+        for (sol_timelock, counterparty_timelock) in [
+            (149, 100),
+            (100, 100),
+            (50, 100),
+            (1, 1),
+            (u64::MAX, u64::MAX),
+        ] {
+            assert_eq!(
+                assert_safe_timelock(sol_timelock, counterparty_timelock).unwrap_err(),
+                error!(SwapError::UnsafeTimelock)
+            );
+        }
+    }
+
+    #[test]
+    fn merged_swap_sums_amounts_and_keeps_the_later_expiry() {
+        let swap = SwapAccount {
+            swap_amount: 600,
+            keeper_reward_lamports: 6,
+            ..swap_expiring_at(1_000)
+        };
+        let other = SwapAccount {
+            swap_amount: 400,
+            keeper_reward_lamports: 4,
+            rebate_bps: 50,
+            rebate_recipient: Some(swap.funder),
+            ..swap_expiring_at(1_200)
+        };
+
+        let merged = swap.merged(&other, 7, 8).unwrap();
+        assert_eq!(merged.swap_amount, 1_000);
+        assert_eq!(merged.keeper_reward_lamports, 10);
+        assert_eq!(merged.expiry_slot, 1_200);
+        assert_eq!((merged.bump, merged.vault_bump), (7, 8));
+        assert_eq!(merged.rebate_bps, 50);
+        assert_ne!(merged.swap_id, swap.swap_id);
+
+        // Swaps differing in any of the compatibility fields cannot be merged
+        let incompatible = [
+            SwapAccount {
+                secret_hash: [9; 32],
+                ..other.clone()
+            },
+            SwapAccount {
+                redeemer: Pubkey::new_unique(),
+                ..other.clone()
+            },
+            SwapAccount {
+                refundee: Pubkey::new_unique(),
+                ..other.clone()
+            },
+            SwapAccount {
+                timelock: other.timelock + 1,
+                ..other.clone()
+            },
+            SwapAccount {
+                price_oracle: Some(Pubkey::new_unique()),
+                ..other.clone()
+            },
+            SwapAccount {
+                swap_amount: u64::MAX,
+                ..other.clone()
+            },
+            SwapAccount {
+                tvl_locked: true,
+                ..other.clone()
+            },
+            // Merging must not drop the terms of the other swap either
+            SwapAccount {
+                relayer: Some(Pubkey::new_unique()),
+                ..other.clone()
+            },
+            SwapAccount {
+                additional_secret_hashes: vec![[9; 32]],
+                ..other.clone()
+            },
+            SwapAccount {
+                vesting: true,
+                ..other.clone()
+            },
+            SwapAccount {
+                refund_trigger: Some(Pubkey::new_unique()),
+                ..other.clone()
+            },
+            SwapAccount {
+                single_boundary: true,
+                ..other.clone()
+            },
+            SwapAccount {
+                sink: true,
+                ..other.clone()
+            },
+        ];
+        for other in incompatible {
+            assert_eq!(
+                swap.merged(&other, 7, 8).err(),
+                Some(error!(SwapError::IncompatibleSwaps))
+            );
+        }
+    }
+
+    #[test]
+    fn keeper_reward_is_bounded_by_the_swap_amount() {
+        assert_eq!(max_keeper_reward(1_000_000), 10_000);
+        assert_eq!(max_keeper_reward(199), 1);
+        assert_eq!(max_keeper_reward(99), 0);
+        assert_eq!(max_keeper_reward(u64::MAX), u64::MAX / 100);
+
+        // Children of a split swap share the keeper reward of their parent
+        let parent = SwapAccount {
+            swap_amount: 1_000,
+            keeper_reward_lamports: 10,
+            ..swap_expiring_at(600)
+        };
+        let child = |swap_amount| {
+            let split = Split {
+                redeemer: Pubkey::new_unique(),
+                swap_amount,
+            };
+            parent.child(&split, 0, 0, 0).keeper_reward_lamports
+        };
+        assert_eq!((child(500), child(350), child(150)), (5, 3, 1));
+    }
+
+    #[test]
+    fn tvl_is_capped_on_lock_only() {
+        let mut config = ProtocolConfig {
+            max_tvl_lamports: 1_000,
+            ..Default::default()
+        };
+        config.lock(600).unwrap();
+        config.lock(400).unwrap();
+        assert_eq!(config.current_tvl, 1_000);
+        assert_eq!(
+            config.lock(1).unwrap_err(),
+            error!(SwapError::TvlCapExceeded)
+        );
+        assert_eq!(config.current_tvl, 1_000);
+
+        // Releases are never blocked, even past a lowered cap
+        config.max_tvl_lamports = 0;
+        config.release(600);
+        assert_eq!(config.current_tvl, 400);
+        config.release(u64::MAX);
+        assert_eq!(config.current_tvl, 0);
+
+        config.max_tvl_lamports = u64::MAX;
+        config.lock(u64::MAX).unwrap();
+        assert_eq!(
+            config.lock(1).unwrap_err(),
+            error!(SwapError::TvlCapExceeded)
+        );
+    }
+
+    #[test]
+    fn tvl_is_only_released_by_locked_swaps() {
+        let mut config = ProtocolConfig {
+            max_tvl_lamports: 1_000,
+            current_tvl: 1_000,
+            ..Default::default()
+        };
+        let unlocked = SwapAccount {
+            swap_amount: 600,
+            ..swap_expiring_at(1_000)
+        };
+        release_tvl(Some(&mut config), &unlocked).unwrap();
+        release_tvl(None, &unlocked).unwrap();
+        assert_eq!(config.current_tvl, 1_000);
+
+        let locked = SwapAccount {
+            tvl_locked: true,
+            ..unlocked
+        };
+        assert_eq!(
+            release_tvl(None, &locked).unwrap_err(),
+            error!(SwapError::MissingProtocolConfig)
+        );
+        release_tvl(Some(&mut config), &locked).unwrap();
+        assert_eq!(config.current_tvl, 400);
+        assert!(config.is_capped());
+        config.max_tvl_lamports = u64::MAX;
+        assert!(!config.is_capped());
+    }
+
+    #[test]
+    fn rate_limit_resets_after_window() {
+        let mut rate_limit = RateLimit::default();
+        rate_limit.record(100, 2, 10).unwrap();
+        assert_eq!(rate_limit.window_start_slot, 100);
+        rate_limit.record(105, 2, 10).unwrap();
+        assert_eq!(
+            rate_limit.record(109, 2, 10).unwrap_err(),
+            error!(SwapError::RateLimited)
+        );
+        assert_eq!(rate_limit.count, 2);
+
+        // The window elapses exactly `window_slots` after its start
+        rate_limit.record(110, 2, 10).unwrap();
+        assert_eq!(rate_limit.window_start_slot, 110);
+        assert_eq!(rate_limit.count, 1);
+
+        // A clock going backwards never resets the window
+        rate_limit.record(50, 2, 10).unwrap();
+        assert_eq!(rate_limit.window_start_slot, 110);
+        assert_eq!(rate_limit.count, 2);
+    }
+
+    #[test]
+    fn refund_queue_is_bounded_and_deregisters_swaps() {
+        let mut refund_queue = RefundQueue::default();
+        let swap_accounts: Vec<Pubkey> = (0..MAX_REFUND_QUEUE_LEN)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        for (expiry_slot, swap_account) in swap_accounts.iter().enumerate() {
+            refund_queue
+                .register(*swap_account, expiry_slot as u64)
+                .unwrap();
+        }
+        assert_eq!(
+            refund_queue.register(Pubkey::new_unique(), 0).unwrap_err(),
+            error!(SwapError::RefundQueueFull)
+        );
+
+        assert!(refund_queue.deregister(&swap_accounts[1]));
+        assert!(!refund_queue.deregister(&swap_accounts[1]));
+        assert_eq!(refund_queue.entries.len(), MAX_REFUND_QUEUE_LEN - 1);
+        assert!(refund_queue
+            .entries
+            .iter()
+            .all(|entry| entry.swap_account != swap_accounts[1]));
+
+        // Deregistering frees up room for another swap
+        refund_queue.register(Pubkey::new_unique(), 0).unwrap();
+    }
+
+    #[test]
+    fn wsol_amount_requires_an_initialized_wsol_account_of_the_owner() {
+        let owner = Pubkey::new_unique();
+        let mut data = [0; 165];
+        data[..32].copy_from_slice(NATIVE_MINT.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&42u64.to_le_bytes());
+        assert_eq!(wsol_amount(&data, &owner), None);
+        data[108] = 1;
+        assert_eq!(wsol_amount(&data, &owner), Some(42));
+
+        assert_eq!(wsol_amount(&data, &Pubkey::new_unique()), None);
+        assert_eq!(wsol_amount(&data[..164], &owner), None);
+        data[..32].copy_from_slice(Pubkey::new_unique().as_ref());
+        assert_eq!(wsol_amount(&data, &owner), None);
+    }
+
+    #[test]
+    fn bucket_amount_edges() {
+        assert_eq!(bucket_amount(0), 0);
+        assert_eq!(bucket_amount(1), 1);
+        assert_eq!(bucket_amount(2), 2);
+        assert_eq!(bucket_amount(3), 2);
+        assert_eq!(bucket_amount(1023), 512);
+        assert_eq!(bucket_amount(1024), 1024);
+        assert_eq!(bucket_amount(1025), 1024);
+        assert_eq!(bucket_amount(1_000_000_000), 1 << 29);
+        assert_eq!(bucket_amount(u64::MAX), 1 << 63);
+    }
+
+    /// `(t, t·G)` pairs, with `t` as a little-endian scalar and `t·G` as a compressed point
+    const ADAPTOR_VECTORS: [([u8; 32], [u8; 32]); 3] = [
+        (
+            [
+                1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0,
+            ],
+            ED25519_BASEPOINT,
+        ),
+        (
+            [
+                2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0,
+            ],
+            [
+                201, 163, 248, 106, 174, 70, 95, 14, 86, 81, 56, 100, 81, 15, 57, 151, 86, 31, 162,
+                201, 232, 94, 162, 29, 194, 41, 35, 9, 243, 205, 96, 34,
+            ],
+        ),
+        (
+            [
+                0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42,
+                0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42,
+                0x42, 0x42, 0x42, 0x02,
+            ],
+            [
+                27, 231, 226, 2, 152, 23, 106, 120, 159, 181, 111, 220, 23, 173, 104, 167, 117, 46,
+                95, 125, 89, 212, 174, 212, 231, 237, 88, 132, 92, 129, 197, 225,
+            ],
+        ),
+    ];
+
+    #[test]
+    fn adaptor_secret_matches_its_point() {
+        for (adaptor_secret, adaptor_point) in ADAPTOR_VECTORS {
+            assert!(validate_edwards(&PodEdwardsPoint(adaptor_point)));
+            assert!(verify_adaptor_secret(&adaptor_point, &adaptor_secret));
+        }
+    }
+
+    #[test]
+    fn adaptor_secret_rejects_other_points() {
+        let (adaptor_secret, _) = ADAPTOR_VECTORS[1];
+        let (_, other_point) = ADAPTOR_VECTORS[2];
+        assert!(!verify_adaptor_secret(&other_point, &adaptor_secret));
+
+        // Non-canonical scalars (at least the group order) are rejected
+        assert!(!verify_adaptor_secret(&ADAPTOR_VECTORS[2].1, &[0x42; 32]));
+    }
+
+    #[test]
+    fn merkle_proof_verifies_secrets_of_a_four_leaf_tree() {
+        fn node(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+            hash::hashv(&[&a.min(b), &a.max(b)]).to_bytes()
+        }
+        let secrets: Vec<[u8; 32]> = (1..=4).map(|i| [i; 32]).collect();
+        let leaves: Vec<[u8; 32]> = secrets.iter().map(|s| hash::hash(s).to_bytes()).collect();
+        let (left, right) = (node(leaves[0], leaves[1]), node(leaves[2], leaves[3]));
+        let root = node(left, right);
+        let proofs = [
+            [leaves[1], right],
+            [leaves[0], right],
+            [leaves[3], left],
+            [leaves[2], left],
+        ];
+        for (leaf, proof) in leaves.iter().zip(&proofs) {
+            assert!(verify_merkle_proof(*leaf, proof, &root));
+        }
+
+        // Proofs of other leaves, truncated proofs, foreign leaves and other roots are rejected
+        assert!(!verify_merkle_proof(leaves[0], &proofs[2], &root));
+        assert!(!verify_merkle_proof(leaves[0], &proofs[0][..1], &root));
+        assert!(!verify_merkle_proof(
+            hash::hash(&[5; 32]).to_bytes(),
+            &proofs[0],
+            &root
+        ));
+        assert!(!verify_merkle_proof(leaves[0], &proofs[0], &left));
+        // Inner nodes verify against their own subtree only
+        assert!(verify_merkle_proof(left, &[right], &root));
+
+        assert_eq!(HashKind::of(None, Some(root)), HashKind::MerkleSha256);
+        let swap = SwapAccount {
+            secret_merkle_root: Some(root),
+            hash_kind: HashKind::MerkleSha256,
+            ..swap_expiring_at(600)
+        };
+        assert_eq!(
+            swap.check_hash_kind(HashKind::Sha256).unwrap_err(),
+            error!(SwapError::WrongHashKind)
+        );
+    }
 }
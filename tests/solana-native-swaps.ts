@@ -1,27 +1,237 @@
-import { web3, BN, workspace, getProvider, Program } from "@coral-xyz/anchor";
+import {
+  web3,
+  BN,
+  workspace,
+  getProvider,
+  Program,
+  EventParser,
+  AnchorError,
+} from "@coral-xyz/anchor";
 import crypto from "crypto";
 import { expect } from "chai";
 
 import { setTimeout } from "node:timers/promises";
+import { MockRedeemer } from "../target/types/mock_redeemer";
 import { SolanaNativeSwaps } from "../target/types/solana_native_swaps";
 
 // Provider will use the private key at ~/.config/solana/id.json
 const provider = getProvider();
 const connection = provider.connection;
 const program = workspace.SolanaNativeSwaps as Program<SolanaNativeSwaps>;
+const mockRedeemer = workspace.MockRedeemer as Program<MockRedeemer>;
+
+// Options under which a swap behaves as a plain atomic swap
+const defaultSwapOptions = {
+  relayer: null,
+  initFee: new BN(0),
+  funderCancelable: false,
+  maxExtensionSlots: new BN(0),
+  additionalSecretHashes: [],
+  instantRefundAuthority: null,
+  singleBoundary: false,
+  sink: false,
+  suppressEvents: false,
+  refundTrigger: null,
+  nonce: new BN(0),
+  distribution: [],
+  priceOracle: null,
+  targetValue: new BN(0),
+  instantRefundCliffSlots: new BN(0),
+  vesting: false,
+  keeperRewardLamports: new BN(0),
+  counterpartyTimelock: new BN(0),
+  queueRefund: false,
+  secretMerkleRoot: null,
+  priceFeedId: null,
+};
+const eventParser = new EventParser(program.programId, program.coder);
+
+const maxU64 = new BN("18446744073709551615");
+const programData = web3.PublicKey.findProgramAddressSync(
+  [program.programId.toBuffer()],
+  web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+)[0];
+const protocolConfig = web3.PublicKey.findProgramAddressSync(
+  [Buffer.from("config")],
+  program.programId
+)[0];
+const stats = web3.PublicKey.findProgramAddressSync(
+  [Buffer.from("stats")],
+  program.programId
+)[0];
+const refundQueue = web3.PublicKey.findProgramAddressSync(
+  [Buffer.from("refund_queue")],
+  program.programId
+)[0];
+
+// The refund queue must exist before any swap is registered into it. The
+// protocol config and the stats are optional, and created here for the tests
+// configuring or reading them, the former uncapped.
+// The provider wallet is the upgrade authority of the program deployed by `anchor test`.
+before(async () => {
+  await program.methods
+    .initProtocolConfig(maxU64)
+    .accounts({ admin: provider.wallet.publicKey, programData })
+    .rpc({ commitment: "confirmed" });
+  await program.methods
+    .initStats()
+    .accounts({ payer: provider.wallet.publicKey })
+    .rpc({ commitment: "confirmed" });
+  await program.methods
+    .initRefundQueue()
+    .accounts({ payer: provider.wallet.publicKey })
+    .rpc({ commitment: "confirmed" });
+});
+
+const airdrop = async (to: web3.PublicKey, lamports: number) => {
+  const blockHash = await connection.getLatestBlockhash();
+  const signature = await connection.requestAirdrop(to, lamports);
+  await connection.confirmTransaction({ signature, ...blockHash });
+};
+
+const getSwapAccount = (
+  redeemer: web3.PublicKey,
+  refundee: web3.PublicKey,
+  secretHash: Buffer,
+  swapAmount: BN,
+  timelock: BN
+) =>
+  web3.PublicKey.findProgramAddressSync(
+    [
+      redeemer.toBuffer(),
+      refundee.toBuffer(),
+      secretHash,
+      swapAmount.toArrayLike(Buffer, "le", 8),
+      timelock.toArrayLike(Buffer, "le", 8),
+    ],
+    program.programId
+  )[0];
+
+const getVault = (swapAccount: web3.PublicKey) =>
+  web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("vault"), swapAccount.toBuffer()],
+    program.programId
+  )[0];
+
+// The combined balance of a swap account and its vault
+const getSwapBalance = async (swapAccount: web3.PublicKey) =>
+  (await connection.getBalance(swapAccount)) +
+  (await connection.getBalance(getVault(swapAccount)));
+
+// The combined rent of a swap account and its vault, paid by the rent sponsor
+const getRentAmount = async () =>
+  (await connection.getMinimumBalanceForRentExemption(
+    program.account.swapAccount.size
+  )) +
+  (await connection.getMinimumBalanceForRentExemption(
+    program.account.swapVault.size
+  ));
+
+// Alice funds swaps refundable to her, Bob redeems them and a third party
+// sponsors their rent
+type Parties = {
+  alice: web3.Keypair;
+  bob: web3.Keypair;
+  rentSponsor: web3.Keypair;
+};
+
+const newParties = (): Parties => ({
+  alice: web3.Keypair.fromSeed(crypto.randomBytes(32)),
+  bob: web3.Keypair.fromSeed(crypto.randomBytes(32)),
+  rentSponsor: new web3.Keypair(),
+});
+
+// Funds Alice and the rent sponsor for the swaps of a test suite
+const fundParties = async ({ alice, rentSponsor }: Parties) => {
+  await airdrop(alice.publicKey, web3.LAMPORTS_PER_SOL);
+  await airdrop(rentSponsor.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+};
+
+// Initiates a swap from Alice to Bob, unless `redeemer`, `refundee` or
+// `funder` say otherwise, through `initiate_minimal` if `minimal` is set,
+// through `initiate_with_options` with `options` on top of the defaults if any
+// are given, and through `initiate`, keeping its first wire format, otherwise
+const initiateSwap = async ({
+  alice,
+  bob,
+  rentSponsor,
+  secretHash,
+  swapAmount,
+  timelock,
+  redeemer = bob.publicKey,
+  refundee = alice.publicKey,
+  funder = alice,
+  destinationData = null,
+  options = {},
+  minimal = false,
+  accounts = {},
+}: Parties & {
+  secretHash: Buffer;
+  swapAmount: BN;
+  timelock: BN;
+  redeemer?: web3.PublicKey;
+  refundee?: web3.PublicKey;
+  funder?: web3.Keypair;
+  destinationData?: Buffer | null;
+  options?: Partial<typeof defaultSwapOptions>;
+  minimal?: boolean;
+  accounts?: Record<string, web3.PublicKey | null>;
+}) => {
+  const initiate = minimal
+    ? program.methods.initiateMinimal(
+        redeemer,
+        refundee,
+        [...secretHash],
+        swapAmount,
+        timelock
+      )
+    : Object.keys(options).length > 0
+    ? program.methods.initiateWithOptions(
+        redeemer,
+        refundee,
+        [...secretHash],
+        swapAmount,
+        timelock,
+        destinationData,
+        { ...defaultSwapOptions, ...options }
+      )
+    : program.methods.initiate(
+        redeemer,
+        refundee,
+        [...secretHash],
+        swapAmount,
+        timelock,
+        destinationData
+      );
+  const signers = funder === rentSponsor ? [funder] : [funder, rentSponsor];
+  const signature = await initiate
+    .accounts({
+      funder: funder.publicKey,
+      rentSponsor: rentSponsor.publicKey,
+      protocolConfig,
+      ...accounts,
+    })
+    .signers(signers)
+    .rpc({ commitment: "confirmed" });
+  const swapAccount = getSwapAccount(
+    redeemer,
+    refundee,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+  return { signature, swapAccount };
+};
 
 describe("Testing one way swap between Alice and Bob", () => {
   const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
   const timelock = new BN(5); // 2 secs (1 slot = 0.4 secs)
-  // Alice is the refundee here
-  const alice = web3.Keypair.fromSeed(crypto.randomBytes(32));
+  // Alice is the refundee and Bob the redeemer here
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
   const secret = crypto.randomBytes(32);
   const secretHash = crypto.createHash("sha256").update(secret).digest();
 
-  // Bob is the redeemer here
-  const bob = web3.Keypair.fromSeed(crypto.randomBytes(32));
-
-  const rentSponsor = new web3.Keypair();
   const funder = new web3.Keypair();
 
   // SwapAccount PDA
@@ -42,28 +252,18 @@ describe("Testing one way swap between Alice and Bob", () => {
   console.log({ alice: alice.publicKey, bob: bob.publicKey, swapAccount });
 
   const aliceInitiate = async () => {
-    const initSignature = await program.methods
-      .initiate(
-        bob.publicKey,
-        alice.publicKey,
-        [...secretHash],
-        swapAmount,
-        timelock,
-        destinationData
-      )
-      .accounts({
-        funder: alice.publicKey,
-        rentSponsor: rentSponsor.publicKey,
-      })
-      .signers([alice, rentSponsor])
-      .rpc({ commitment: "confirmed" });
+    const { signature: initSignature } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      destinationData,
+    });
     console.log("Alice initiated:", initSignature);
   };
 
   before(async () => {
-    rentAmount = await connection.getMinimumBalanceForRentExemption(
-      program.account.swapAccount.size
-    );
+    rentAmount = await getRentAmount();
 
     const blockHash = await connection.getLatestBlockhash();
     const fund = async (to: web3.PublicKey, qty: number) => {
@@ -88,27 +288,19 @@ describe("Testing one way swap between Alice and Bob", () => {
       rentSponsor.publicKey
     );
 
-    const initiateOnBehalfSignature = await program.methods
-      .initiate(
-        bob.publicKey,
-        alice.publicKey,
-        secretHash,
-        swapAmount,
-        timelock,
-        null
-      )
-      .accounts({
-        funder: funder.publicKey,
-        rentSponsor: rentSponsor.publicKey,
-      })
-      .signers([funder, rentSponsor])
-      .rpc();
+    const { signature: initiateOnBehalfSignature } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      funder,
+    });
     console.log(
       "Funder initiated on behalf of alice:",
       initiateOnBehalfSignature
     );
 
-    const pdaBalance = await connection.getBalance(swapAccount);
+    const pdaBalance = await getSwapBalance(swapAccount);
     expect(pdaBalance).to.equal(rentAmount + swapAmount.toNumber());
 
     const alicePostBalance = await connection.getBalance(alice.publicKey);
@@ -138,6 +330,14 @@ describe("Testing one way swap between Alice and Bob", () => {
         swapAccount,
         rentSponsor: rentSponsor.publicKey,
         redeemer: bob.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
       })
       .rpc();
     console.log("Bob redeemed:", redeemSignature);
@@ -145,7 +345,7 @@ describe("Testing one way swap between Alice and Bob", () => {
     const bobPostBalance = await connection.getBalance(bob.publicKey);
     expect(bobPostBalance).to.equal(bobPreBalance + swapAmount.toNumber());
 
-    const pdaBalance = await connection.getBalance(swapAccount);
+    const pdaBalance = await getSwapBalance(swapAccount);
     expect(pdaBalance).to.equal(0);
 
     const sponsorPostBalance = await connection.getBalance(
@@ -171,6 +371,10 @@ describe("Testing one way swap between Alice and Bob", () => {
         swapAccount,
         refundee: alice.publicKey,
         rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
       })
       .rpc({ commitment: "confirmed" });
     console.log("Alice refunded:", refundSignature);
@@ -178,7 +382,7 @@ describe("Testing one way swap between Alice and Bob", () => {
     const alicePostBalance = await connection.getBalance(alice.publicKey);
     expect(alicePostBalance).to.equal(alicePreBalance + swapAmount.toNumber());
 
-    const pdaBalance = await connection.getBalance(swapAccount);
+    const pdaBalance = await getSwapBalance(swapAccount);
     expect(pdaBalance).to.equal(0);
 
     const sponsorPostBalance = await connection.getBalance(
@@ -195,22 +399,37 @@ describe("Testing one way swap between Alice and Bob", () => {
       rentSponsor.publicKey
     );
 
+    const { expirySlot } = await program.account.swapAccount.fetch(swapAccount);
     const instantRefundSignature = await program.methods
       .instantRefund()
       .accounts({
         swapAccount,
         refundee: alice.publicKey,
-        redeemer: bob.publicKey,
+        instantRefundAuthority: bob.publicKey,
         rentSponsor: rentSponsor.publicKey,
       })
       .signers([bob])
-      .rpc();
+      .rpc({ commitment: "confirmed" });
     console.log("Alice instant-refunded:", instantRefundSignature);
 
+    const tx = await connection.getTransaction(instantRefundSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("instantRefunded");
+    expect(event.data.slotsBeforeExpiry.toNumber()).to.equal(
+      expirySlot.toNumber() - tx.slot
+    );
+    // The redeemer is the instant refund authority by default
+    expect(event.data.instantRefundAuthority.toBase58()).to.equal(
+      bob.publicKey.toBase58()
+    );
+
     const alicePostBalance = await connection.getBalance(alice.publicKey);
     expect(alicePostBalance).to.equal(alicePreBalance + swapAmount.toNumber());
 
-    const pdaBalance = await connection.getBalance(swapAccount);
+    const pdaBalance = await getSwapBalance(swapAccount);
     expect(pdaBalance).to.equal(0);
 
     const sponsorPostBalance = await connection.getBalance(
@@ -219,3 +438,4921 @@ describe("Testing one way swap between Alice and Bob", () => {
     expect(sponsorPostBalance).to.equal(sponsorPreBalance + rentAmount);
   });
 });
+
+describe("Testing a swap where the redeemer is also the rent sponsor", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(5);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+  let rentAmount: number;
+
+  before(async () => {
+    rentAmount = await getRentAmount();
+    await airdrop(alice.publicKey, web3.LAMPORTS_PER_SOL);
+    await airdrop(bob.publicKey, web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test redeem credits both the swap amount and the rent", async () => {
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      rentSponsor: bob,
+    });
+
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+        rentSponsor: bob.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const bobPostBalance = await connection.getBalance(bob.publicKey);
+    expect(bobPostBalance).to.equal(
+      bobPreBalance + swapAmount.toNumber() + rentAmount
+    );
+
+    const pdaBalance = await getSwapBalance(swapAccount);
+    expect(pdaBalance).to.equal(0);
+  });
+});
+
+describe("Testing redeems restricted to a relayer", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const relayer = new web3.Keypair();
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  before(async () => {
+    await airdrop(alice.publicKey, web3.LAMPORTS_PER_SOL);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      rentSponsor: alice,
+      options: { relayer: relayer.publicKey },
+    });
+  });
+
+  it("Test redeem without the relayer fails", async () => {
+    const stranger = new web3.Keypair();
+    try {
+      await program.methods
+        .redeem([...secret])
+        .accounts({
+          swapAccount,
+          redeemer: bob.publicKey,
+          rentSponsor: alice.publicKey,
+          relayer: stranger.publicKey,
+          rateLimit: null,
+          priceOracle: null,
+          refundee: null,
+          rebateRecipient: null,
+          withdrawalEscrow: null,
+          funder: null,
+          refundQueue: null,
+        })
+        .signers([stranger])
+        .rpc();
+      expect.fail("redeem should be restricted to the relayer");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRelayer");
+    }
+  });
+
+  it("Test redeem by the relayer", async () => {
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+
+    const signature = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: alice.publicKey,
+        relayer: relayer.publicKey,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .signers([relayer])
+      .rpc({ commitment: "confirmed" });
+
+    const bobPostBalance = await connection.getBalance(bob.publicKey);
+    expect(bobPostBalance).to.equal(bobPreBalance + swapAmount.toNumber());
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events[0].name).to.equal("redeemed");
+    expect(events[0].data.relayer.toBase58()).to.equal(
+      relayer.publicKey.toBase58()
+    );
+  });
+
+  it("Test redeem without a relayer in open mode", async () => {
+    const openSecret = crypto.randomBytes(32);
+    const openSecretHash = crypto
+      .createHash("sha256")
+      .update(openSecret)
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash: openSecretHash,
+      swapAmount,
+      timelock,
+      rentSponsor: alice,
+    });
+
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+    await program.methods
+      .redeem([...openSecret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: alice.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const bobPostBalance = await connection.getBalance(bob.publicKey);
+    expect(bobPostBalance).to.equal(bobPreBalance + swapAmount.toNumber());
+  });
+});
+
+describe("Testing secret checks through simulation", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  before(async () => {
+    await airdrop(alice.publicKey, web3.LAMPORTS_PER_SOL);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      rentSponsor: alice,
+    });
+  });
+
+  it("Test simulating check_secret with the right secret", async () => {
+    const preBalance = await getSwapBalance(swapAccount);
+    await program.methods
+      .checkSecret([...secret])
+      .accounts({ swapAccount })
+      .simulate();
+
+    // Simulations never change any state
+    const postBalance = await getSwapBalance(swapAccount);
+    expect(postBalance).to.equal(preBalance);
+  });
+
+  it("Test simulating check_secret with a wrong secret", async () => {
+    try {
+      await program.methods
+        .checkSecret([...crypto.randomBytes(32)])
+        .accounts({ swapAccount })
+        .simulate();
+      expect.fail("check_secret should reject a wrong secret");
+    } catch (err) {
+      expect(err.simulationResponse.err).to.not.be.null;
+      expect(err.simulationResponse.logs.join("\n")).to.include(
+        "InvalidSecret"
+      );
+    }
+  });
+
+  it("Test redeeming with the secret hash as the secret", async () => {
+    try {
+      await program.methods
+        .redeem([...secretHash])
+        .accounts({
+          swapAccount,
+          redeemer: bob.publicKey,
+          rentSponsor: alice.publicKey,
+          relayer: null,
+          rateLimit: null,
+          priceOracle: null,
+          refundee: null,
+          rebateRecipient: null,
+          withdrawalEscrow: null,
+          funder: null,
+          refundQueue: null,
+        })
+        .rpc({ commitment: "confirmed" });
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SecretLooksLikeHash");
+    }
+  });
+});
+
+describe("Testing swaps charging an initiation fee", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const initFee = new BN(0.001 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice } = parties;
+  const feeCollector = new web3.Keypair();
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test initiate transfers the fee to the fee collector", async () => {
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    const collectorPreBalance = await connection.getBalance(
+      feeCollector.publicKey
+    );
+
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { initFee },
+      accounts: { feeCollector: feeCollector.publicKey },
+    });
+
+    const collectorPostBalance = await connection.getBalance(
+      feeCollector.publicKey
+    );
+    expect(collectorPostBalance).to.equal(
+      collectorPreBalance + initFee.toNumber()
+    );
+
+    const alicePostBalance = await connection.getBalance(alice.publicKey);
+    expect(alicePostBalance).to.equal(
+      alicePreBalance - swapAmount.toNumber() - initFee.toNumber()
+    );
+
+    const pdaBalance = await getSwapBalance(swapAccount);
+    const rentAmount = await getRentAmount();
+    expect(pdaBalance).to.equal(rentAmount + swapAmount.toNumber());
+  });
+});
+
+describe("Testing swaps cancelable by the funder", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const funder = new web3.Keypair();
+
+  const initiate = async (secretHash: Buffer, funderCancelable: boolean) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      funder,
+      options: { funderCancelable },
+    });
+    return swapAccount;
+  };
+
+  const newSecretHash = () =>
+    crypto.createHash("sha256").update(crypto.randomBytes(32)).digest();
+
+  before(async () => {
+    await airdrop(funder.publicKey, web3.LAMPORTS_PER_SOL);
+    await airdrop(rentSponsor.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test the funder cancels before expiry", async () => {
+    const swapAccount = await initiate(newSecretHash(), true);
+    const funderPreBalance = await connection.getBalance(funder.publicKey);
+
+    const cancelSignature = await program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: funder.publicKey,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .signers([funder])
+      .rpc({ commitment: "confirmed" });
+    console.log("Funder canceled:", cancelSignature);
+
+    const funderPostBalance = await connection.getBalance(funder.publicKey);
+    expect(funderPostBalance).to.equal(
+      funderPreBalance + swapAmount.toNumber()
+    );
+    expect(await connection.getBalance(alice.publicKey)).to.equal(0);
+
+    const tx = await connection.getTransaction(cancelSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events.map((event) => event.name)).to.include("canceled");
+  });
+
+  it("Test a non-cancelable swap cannot be canceled", async () => {
+    const swapAccount = await initiate(newSecretHash(), false);
+
+    try {
+      await program.methods
+        .refund()
+        .accounts({
+          swapAccount,
+          refundee: alice.publicKey,
+          rentSponsor: rentSponsor.publicKey,
+          funder: funder.publicKey,
+          refundTrigger: null,
+          keeper: null,
+          refundQueue: null,
+        })
+        .signers([funder])
+        .rpc();
+      expect.fail("Cancel should have failed");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidCancelAuthority");
+    }
+  });
+
+  it("Test only the funder can cancel", async () => {
+    const swapAccount = await initiate(newSecretHash(), true);
+
+    try {
+      await program.methods
+        .refund()
+        .accounts({
+          swapAccount,
+          refundee: alice.publicKey,
+          rentSponsor: rentSponsor.publicKey,
+          funder: bob.publicKey,
+          refundTrigger: null,
+          keeper: null,
+          refundQueue: null,
+        })
+        .signers([bob])
+        .rpc();
+      expect.fail("Cancel should have failed");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidCancelAuthority");
+    }
+  });
+});
+
+describe("Testing assertions that a swap was never initiated", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const assertNotInitialized = () =>
+    program.methods
+      .assertNotInitialized(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        swapAmount,
+        timelock
+      )
+      .rpc();
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the assertion passes before initiation", async () => {
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+    await assertNotInitialized();
+  });
+
+  it("Test the assertion fails after initiation", async () => {
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+
+    try {
+      await assertNotInitialized();
+      expect.fail("The assertion should have failed");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SwapAlreadyInitialized");
+    }
+  });
+});
+
+describe("Testing extensions of the expiry slot", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const maxExtensionSlots = new BN(100);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const extendTimelock = (extension: number) =>
+    program.methods
+      .extendTimelock(new BN(extension))
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        refundee: alice.publicKey,
+      })
+      .signers([bob, alice])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { maxExtensionSlots },
+    });
+  });
+
+  it("Test extensions up to the cap", async () => {
+    const { expirySlot } = await program.account.swapAccount.fetch(swapAccount);
+
+    await extendTimelock(60);
+    await extendTimelock(40);
+
+    const extended = await program.account.swapAccount.fetch(swapAccount);
+    expect(extended.expirySlot.toNumber()).to.equal(
+      expirySlot.toNumber() + maxExtensionSlots.toNumber()
+    );
+  });
+
+  it("Test extensions beyond the cap are rejected", async () => {
+    try {
+      await extendTimelock(1);
+      expect.fail("The extension should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("ExtensionCapExceeded");
+    }
+  });
+});
+
+describe("Testing minimal initiates", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+
+  // Initiates a swap with a fresh secret, either minimally or in full,
+  // and redeems it, returning Bob's balance gain and the redeem event
+  const initiateAndRedeem = async (minimal: boolean) => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal,
+    });
+
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+    const signature = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        rentSponsor: rentSponsor.publicKey,
+        redeemer: bob.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    const bobPostBalance = await connection.getBalance(bob.publicKey);
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(await getSwapBalance(swapAccount)).to.equal(0);
+    return { gain: bobPostBalance - bobPreBalance, event };
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a minimal swap redeems identically to a full one", async () => {
+    const full = await initiateAndRedeem(false);
+    const minimal = await initiateAndRedeem(true);
+
+    expect(minimal.gain).to.equal(full.gain);
+    expect(minimal.gain).to.equal(swapAmount.toNumber());
+    expect(minimal.event.name).to.equal(full.event.name);
+    expect(minimal.event.data.swapAmount.toNumber()).to.equal(
+      full.event.data.swapAmount.toNumber()
+    );
+    expect(minimal.event.data.relayer).to.be.null;
+  });
+});
+
+describe("Testing the separation of the swap account and its vault", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+  const vault = getVault(swapAccount);
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test initiate escrows the funds in the vault", async () => {
+    const sponsorPreBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+
+    const stateRent = await connection.getMinimumBalanceForRentExemption(
+      program.account.swapAccount.size
+    );
+    const vaultRent = await connection.getMinimumBalanceForRentExemption(
+      program.account.swapVault.size
+    );
+    expect(await connection.getBalance(swapAccount)).to.equal(stateRent);
+    expect(await connection.getBalance(vault)).to.equal(
+      vaultRent + swapAmount.toNumber()
+    );
+
+    const sponsorPostBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+    expect(sponsorPostBalance).to.equal(
+      sponsorPreBalance - stateRent - vaultRent
+    );
+  });
+
+  it("Test redeem closes both the swap account and the vault", async () => {
+    const sponsorPreBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+    expect(await connection.getAccountInfo(vault)).to.be.null;
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+
+    const sponsorPostBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+    expect(sponsorPostBalance).to.equal(
+      sponsorPreBalance + (await getRentAmount())
+    );
+  });
+});
+
+describe("Testing signals of approaching expiries", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const parties = newParties();
+
+  const initiate = async (timelock: BN) => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    return swapAccount;
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test signaling within the window emits the event", async () => {
+    const swapAccount = await initiate(new BN(50)); // Within the 150 slot window
+    const signature = await program.methods
+      .signalExpiryApproaching()
+      .accounts({ swapAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("expiryApproaching");
+    const { expirySlot } = await program.account.swapAccount.fetch(swapAccount);
+    expect(event.data.expirySlot.toNumber()).to.equal(expirySlot.toNumber());
+  });
+
+  it("Test signaling before the window is rejected", async () => {
+    const swapAccount = await initiate(new BN(10_000));
+    try {
+      await program.methods
+        .signalExpiryApproaching()
+        .accounts({ swapAccount })
+        .rpc();
+      expect.fail("The signal should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("TooEarlyForSignal");
+    }
+  });
+});
+
+describe("Testing swaps requiring multiple secrets", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secrets = [...Array(3)].map(() => crypto.randomBytes(32));
+  const [secretHash, ...additionalSecretHashes] = secrets.map((secret) =>
+    crypto.createHash("sha256").update(secret).digest()
+  );
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const redeem = (revealed: Buffer[]) =>
+    program.methods
+      .redeemMulti(revealed.map((secret) => [...secret]))
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: {
+        additionalSecretHashes: additionalSecretHashes.map((h) => [...h]),
+      },
+    });
+  });
+
+  it("Test redeems revealing only some secrets are rejected", async () => {
+    for (const revealed of [secrets.slice(0, 1), secrets.slice(0, 2)]) {
+      try {
+        await redeem(revealed);
+        expect.fail("The redeem should have been rejected");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.equal("InvalidSecret");
+      }
+    }
+
+    try {
+      await program.methods
+        .redeem([...secrets[0]])
+        .accounts({
+          swapAccount,
+          redeemer: bob.publicKey,
+          rentSponsor: rentSponsor.publicKey,
+          relayer: null,
+          rateLimit: null,
+          priceOracle: null,
+          refundee: null,
+          rebateRecipient: null,
+          withdrawalEscrow: null,
+          funder: null,
+          refundQueue: null,
+        })
+        .rpc();
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidSecret");
+    }
+  });
+
+  it("Test redeem revealing all secrets", async () => {
+    const signature = await redeem(secrets);
+
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(Buffer.from(event.data.secret)).to.deep.equal(secrets[0]);
+    expect(
+      event.data.additionalSecrets.map((secret) => Buffer.from(secret))
+    ).to.deep.equal(secrets.slice(1));
+  });
+});
+
+describe("Testing instant refunds with a custom authority", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const approver = new web3.Keypair();
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const instantRefund = (authority: web3.Keypair) =>
+    program.methods
+      .instantRefund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        instantRefundAuthority: authority.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { instantRefundAuthority: approver.publicKey },
+    });
+  });
+
+  it("Test the redeemer cannot instantly refund", async () => {
+    try {
+      await instantRefund(bob);
+      expect.fail("The instant refund should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal(
+        "InvalidInstantRefundAuthority"
+      );
+    }
+  });
+
+  it("Test the custom authority instantly refunds", async () => {
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    const signature = await instantRefund(approver);
+
+    const alicePostBalance = await connection.getBalance(alice.publicKey);
+    expect(alicePostBalance).to.equal(alicePreBalance + swapAmount.toNumber());
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.data.instantRefundAuthority.toBase58()).to.equal(
+      approver.publicKey.toBase58()
+    );
+  });
+});
+
+describe("Testing transfers of redeem rights", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const carol = web3.Keypair.fromSeed(crypto.randomBytes(32));
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  // The swap remains at the address derived from the original redeemer
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const redeem = (redeemer: web3.PublicKey) =>
+    program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+  });
+
+  it("Test Bob transfers the redeem rights to Carol", async () => {
+    const signature = await program.methods
+      .transferRedeemRights(carol.publicKey)
+      .accounts({ swapAccount, redeemer: bob.publicKey })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("redeemRightsTransferred");
+    expect(event.data.oldRedeemer.toBase58()).to.equal(
+      bob.publicKey.toBase58()
+    );
+    expect(event.data.newRedeemer.toBase58()).to.equal(
+      carol.publicKey.toBase58()
+    );
+  });
+
+  it("Test Bob can no longer redeem", async () => {
+    try {
+      await redeem(bob.publicKey);
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRedeemer");
+    }
+  });
+
+  it("Test Carol redeems", async () => {
+    await redeem(carol.publicKey);
+
+    expect(await connection.getBalance(carol.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+    expect(await getSwapBalance(swapAccount)).to.equal(0);
+  });
+});
+
+describe("Testing rent refunds to an emptied rent sponsor", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+
+    // Empty the rent sponsor, deleting its account
+    const transaction = new web3.Transaction().add(
+      web3.SystemProgram.transfer({
+        fromPubkey: rentSponsor.publicKey,
+        toPubkey: alice.publicKey,
+        lamports: await connection.getBalance(rentSponsor.publicKey),
+      })
+    );
+    await provider.sendAndConfirm(transaction, [rentSponsor], {
+      commitment: "confirmed",
+    });
+    expect(await connection.getAccountInfo(rentSponsor.publicKey)).to.be.null;
+  });
+
+  it("Test redeem refunds exactly the rent to the emptied sponsor", async () => {
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await connection.getBalance(rentSponsor.publicKey)).to.equal(
+      await getRentAmount()
+    );
+  });
+});
+
+describe("Testing spoofed swap accounts", () => {
+  const spoofedSwapAccount = new web3.Keypair();
+  const bob = web3.Keypair.fromSeed(crypto.randomBytes(32));
+
+  before(async () => {
+    await airdrop(spoofedSwapAccount.publicKey, web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test redeem rejects a swap account not owned by the program", async () => {
+    try {
+      await program.methods
+        .redeem([...crypto.randomBytes(32)])
+        .accounts({
+          swapAccount: spoofedSwapAccount.publicKey,
+          redeemer: bob.publicKey,
+          rentSponsor: bob.publicKey,
+          relayer: null,
+          rateLimit: null,
+          priceOracle: null,
+          refundee: null,
+          rebateRecipient: null,
+          withdrawalEscrow: null,
+          funder: null,
+          refundQueue: null,
+        })
+        .rpc();
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      // Anchor rejects the account before the handler asserts it again
+      expect(err.error.errorCode.code).to.equal("AccountOwnedByWrongProgram");
+    }
+  });
+});
+
+describe("Testing sweeps of swaps with a sink", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+  const sink = web3.Keypair.fromSeed(crypto.randomBytes(32));
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    sink.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({
+      ...parties,
+      refundee: sink.publicKey,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { sink: true },
+    });
+  });
+
+  it("Test anyone sweeps to the sink past expiry", async () => {
+    console.log("Awaiting timelock for sweep");
+    await setTimeout(timelock.toNumber() * 400 + 1000);
+
+    // Signed only by the provider, which is neither party nor the sink
+    const signature = await program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: sink.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await connection.getBalance(sink.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("swept");
+    expect(event.data.sink.toBase58()).to.equal(sink.publicKey.toBase58());
+  });
+});
+
+describe("Testing swaps whose addresses were prefunded", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  // Above the rent of either the swap account or the vault
+  const prefundAmount = 0.01 * web3.LAMPORTS_PER_SOL;
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  before(async () => {
+    await fundParties(parties);
+    await airdrop(swapAccount, prefundAmount);
+    await airdrop(getVault(swapAccount), prefundAmount);
+  });
+
+  it("Test the prefunded lamports are not paid out as the swap amount", async () => {
+    const sponsorPreBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+    expect(await getSwapBalance(swapAccount)).to.equal(
+      2 * prefundAmount + swapAmount.toNumber()
+    );
+
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+    // The rent sponsor paid no rent, and receives the prefunded lamports
+    expect(await connection.getBalance(rentSponsor.publicKey)).to.equal(
+      sponsorPreBalance + 2 * prefundAmount
+    );
+  });
+});
+
+describe("Testing swaps with suppressed events", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const getEvents = async (signature: string) => {
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    return [...eventParser.parseLogs(tx.meta.logMessages)];
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test initiate emits no events", async () => {
+    const { signature } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { suppressEvents: true },
+    });
+    expect(await getEvents(signature)).to.be.empty;
+  });
+
+  it("Test redeem emits no events", async () => {
+    const signature = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await getEvents(signature)).to.be.empty;
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+  });
+});
+
+describe("Testing memos attached to redeems", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const redeem = (memo: string) =>
+    program.methods
+      .redeemWithMemo([...secret], memo)
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+  });
+
+  it("Test memos longer than the maximum are rejected", async () => {
+    try {
+      await redeem("x".repeat(65));
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("MemoTooLong");
+    }
+  });
+
+  it("Test the memo appears in the event", async () => {
+    const signature = await redeem("order-42");
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("redeemed");
+    expect(event.data.memo).to.equal("order-42");
+  });
+});
+
+describe("Testing the expiry slot at the boundary of the initiation slot", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const parties = newParties();
+
+  const initiate = (timelock: BN) =>
+    initiateSwap({
+      ...parties,
+      secretHash: crypto.randomBytes(32),
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a swap expiring in the initiation slot is rejected", async () => {
+    try {
+      await initiate(new BN(0));
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("ExpiryNotInFuture");
+    }
+  });
+
+  it("Test a swap expiring in the next slot is accepted", async () => {
+    await initiate(new BN(1));
+  });
+});
+
+describe("Testing the cap on the total value locked", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  // The protocol config is read-only in the IDL, and is marked as writable
+  // here, as required while a cap is configured
+  const initiate = async (
+    secretHash: Buffer,
+    configWritable = true,
+    config: web3.PublicKey | null = protocolConfig
+  ) => {
+    const ix = await program.methods
+      .initiateMinimal(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        swapAmount,
+        timelock
+      )
+      .accounts({
+        funder: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        protocolConfig: config,
+      })
+      .instruction();
+    ix.keys
+      .filter((key) => key.pubkey.equals(protocolConfig))
+      .forEach((key) => (key.isWritable = configWritable));
+    try {
+      return await provider.sendAndConfirm(
+        new web3.Transaction().add(ix),
+        [alice, rentSponsor],
+        { commitment: "confirmed" }
+      );
+    } catch (err) {
+      throw AnchorError.parse(err.logs) ?? err;
+    }
+  };
+
+  const redeem = (config: web3.PublicKey | null) =>
+    program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        protocolConfig: config,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  const setMaxTvl = (maxTvl: BN) =>
+    program.methods
+      .setMaxTvl(maxTvl)
+      .accounts({ admin: provider.wallet.publicKey })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    const { currentTvl } = await program.account.protocolConfig.fetch(
+      protocolConfig
+    );
+    await setMaxTvl(currentTvl.add(swapAmount));
+  });
+
+  after(async () => {
+    await setMaxTvl(maxU64);
+  });
+
+  it("Test initiates under a cap require a writable config", async () => {
+    try {
+      await initiate(secretHash, false);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("ProtocolConfigNotWritable");
+    }
+  });
+
+  it("Test initiates up to the cap", async () => {
+    const { currentTvl: preTvl } = await program.account.protocolConfig.fetch(
+      protocolConfig
+    );
+    await initiate(secretHash);
+    const { currentTvl, maxTvlLamports } =
+      await program.account.protocolConfig.fetch(protocolConfig);
+    expect(currentTvl.sub(preTvl).eq(swapAmount)).to.be.true;
+    expect(currentTvl.eq(maxTvlLamports)).to.be.true;
+    const { tvlLocked } = await program.account.swapAccount.fetch(swapAccount);
+    expect(tvlLocked).to.be.true;
+  });
+
+  it("Test initiates beyond the cap are rejected", async () => {
+    const otherSecretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    try {
+      await initiate(otherSecretHash);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("TvlCapExceeded");
+    }
+  });
+
+  it("Test initiates omitting the config are not locked under the cap", async () => {
+    // As clients predating the config do, which pass it as the last account
+    const otherSecretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    await initiate(otherSecretHash, false, null);
+    const { tvlLocked } = await program.account.swapAccount.fetch(
+      getSwapAccount(
+        bob.publicKey,
+        alice.publicKey,
+        otherSecretHash,
+        swapAmount,
+        timelock
+      )
+    );
+    expect(tvlLocked).to.be.false;
+  });
+
+  it("Test redeems of locked swaps require the config", async () => {
+    try {
+      await redeem(null);
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("MissingProtocolConfig");
+    }
+  });
+
+  it("Test redeems still work at the cap", async () => {
+    await redeem(protocolConfig);
+
+    const { currentTvl, maxTvlLamports } =
+      await program.account.protocolConfig.fetch(protocolConfig);
+    expect(maxTvlLamports.sub(currentTvl).eq(swapAmount)).to.be.true;
+  });
+
+  it("Test swaps initiated without a cap need no config", async () => {
+    await setMaxTvl(maxU64);
+    const { currentTvl: preTvl } = await program.account.protocolConfig.fetch(
+      protocolConfig
+    );
+    await initiate(secretHash, false);
+    const { tvlLocked } = await program.account.swapAccount.fetch(swapAccount);
+    expect(tvlLocked).to.be.false;
+    await redeem(null);
+    const { currentTvl } = await program.account.protocolConfig.fetch(
+      protocolConfig
+    );
+    expect(currentTvl.eq(preTvl)).to.be.true;
+  });
+
+  it("Test only the admin can update the cap", async () => {
+    try {
+      await program.methods
+        .setMaxTvl(maxU64)
+        .accounts({ admin: alice.publicKey })
+        .signers([alice])
+        .rpc();
+      expect.fail("The update should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidAdmin");
+    }
+  });
+});
+
+describe("Testing refunds restricted to a trigger", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10);
+  const parties = newParties();
+  const { alice, rentSponsor } = parties;
+  const keeper = web3.Keypair.fromSeed(crypto.randomBytes(32));
+
+  const initiate = async (refundTrigger: web3.PublicKey | null) => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { refundTrigger },
+    });
+    return swapAccount;
+  };
+
+  const refund = (
+    swapAccount: web3.PublicKey,
+    refundTrigger: web3.Keypair | null
+  ) =>
+    program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: refundTrigger?.publicKey ?? null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .signers(refundTrigger ? [refundTrigger] : [])
+      .rpc({ commitment: "confirmed" });
+
+  let restrictedSwap: web3.PublicKey;
+  let openSwap: web3.PublicKey;
+
+  before(async () => {
+    await fundParties(parties);
+    restrictedSwap = await initiate(keeper.publicKey);
+    openSwap = await initiate(null);
+
+    console.log("Awaiting timelock for refunds");
+    await setTimeout(timelock.toNumber() * 400 + 1000);
+  });
+
+  it("Test refunds without the trigger are rejected", async () => {
+    try {
+      await refund(restrictedSwap, null);
+      expect.fail("The refund should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRefundTrigger");
+    }
+  });
+
+  it("Test the trigger refunds to the refundee", async () => {
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    await refund(restrictedSwap, keeper);
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance + swapAmount.toNumber()
+    );
+    expect(await connection.getBalance(keeper.publicKey)).to.equal(0);
+  });
+
+  it("Test anyone refunds in open mode", async () => {
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    await refund(openSwap, null);
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance + swapAmount.toNumber()
+    );
+  });
+});
+
+describe("Testing initiates with a spoofed system program", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test initiate rejects a program other than the system program", async () => {
+    try {
+      await program.methods
+        .initiateMinimal(
+          bob.publicKey,
+          alice.publicKey,
+          [...crypto.randomBytes(32)],
+          swapAmount,
+          new BN(50)
+        )
+        .accountsPartial({
+          funder: alice.publicKey,
+          rentSponsor: rentSponsor.publicKey,
+          // An executable program, standing in for a malicious one
+          systemProgram: program.programId,
+        })
+        .signers([alice, rentSponsor])
+        .rpc();
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidProgramId");
+    }
+  });
+});
+
+describe("Testing the activity counters", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  // Returns the secret of the swap, along with its address. The stats are
+  // optional, and passed unless omitted.
+  const initiate = async (withStats = true) => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+      accounts: { stats: withStats ? stats : null },
+    });
+    return { secret, swapAccount };
+  };
+
+  // The counters as plain numbers, for comparison
+  const getStats = async () => {
+    const { initiated, redeemed, refunded, instantRefunded } =
+      await program.account.stats.fetch(stats);
+    return {
+      initiated: initiated.toNumber(),
+      redeemed: redeemed.toNumber(),
+      refunded: refunded.toNumber(),
+      instantRefunded: instantRefunded.toNumber(),
+    };
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the counters advance through the lifecycle of swaps", async () => {
+    const preStats = await getStats();
+
+    const redeemed = await initiate();
+    const instantlyRefunded = await initiate();
+    const refunded = await initiate();
+    expect(await getStats()).to.deep.equal({
+      ...preStats,
+      initiated: preStats.initiated + 3,
+    });
+
+    await program.methods
+      .redeem([...redeemed.secret])
+      .accounts({
+        swapAccount: redeemed.swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        stats,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    await program.methods
+      .instantRefund()
+      .accounts({
+        swapAccount: instantlyRefunded.swapAccount,
+        refundee: alice.publicKey,
+        instantRefundAuthority: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        stats,
+      })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+    console.log("Awaiting timelock for refund");
+    await setTimeout(timelock.toNumber() * 400 + 1000);
+    await program.methods
+      .refund()
+      .accounts({
+        swapAccount: refunded.swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        stats,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await getStats()).to.deep.equal({
+      initiated: preStats.initiated + 3,
+      redeemed: preStats.redeemed + 1,
+      refunded: preStats.refunded + 1,
+      instantRefunded: preStats.instantRefunded + 1,
+    });
+  });
+
+  it("Test swaps leave the counters as is without the stats", async () => {
+    const preStats = await getStats();
+
+    const { secret, swapAccount } = await initiate(false);
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        stats: null,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await getStats()).to.deep.equal(preStats);
+  });
+});
+
+describe("Testing swap ids", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const nonce = new BN(7);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+
+  // Computed off-chain as documented in `compute_swap_id`
+  const expectedSwapId = crypto
+    .createHash("sha256")
+    .update(bob.publicKey.toBuffer())
+    .update(alice.publicKey.toBuffer())
+    .update(secretHash)
+    .update(swapAmount.toArrayLike(Buffer, "be", 8))
+    .update(timelock.toArrayLike(Buffer, "be", 8))
+    .update(nonce.toArrayLike(Buffer, "be", 8))
+    .digest();
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the swap id matches the off-chain computation", async () => {
+    const { signature, swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { nonce },
+    });
+
+    const { swapId } = await program.account.swapAccount.fetch(swapAccount);
+    expect(Buffer.from(swapId).equals(expectedSwapId)).to.be.true;
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("initiated");
+    expect(Buffer.from(event.data.swapId).equals(expectedSwapId)).to.be.true;
+  });
+});
+
+describe("Testing the rate limit on redeems", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const windowSlots = new BN(10);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+  const rateLimit = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("rate_limit"), bob.publicKey.toBuffer()],
+    program.programId
+  )[0];
+
+  const setRateLimit = (maxRedeemsPerWindow: BN) =>
+    program.methods
+      .setRateLimit(maxRedeemsPerWindow, windowSlots)
+      .accounts({ admin: provider.wallet.publicKey })
+      .rpc({ commitment: "confirmed" });
+
+  // The redeemer is no signer of redeems in the IDL, and is marked as one here
+  // when signing, so that the redeem counts towards the rate limit
+  const initiateAndRedeem = async (
+    rateLimit: web3.PublicKey | null,
+    redeemerSigns = true
+  ) => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    const ix = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .instruction();
+    ix.keys
+      .filter((key) => key.pubkey.equals(bob.publicKey))
+      .forEach((key) => (key.isSigner = redeemerSigns));
+    try {
+      await provider.sendAndConfirm(
+        new web3.Transaction().add(ix),
+        redeemerSigns ? [bob] : [],
+        { commitment: "confirmed" }
+      );
+    } catch (err) {
+      throw AnchorError.parse(err.logs) ?? err;
+    }
+  };
+
+  before(async () => {
+    await fundParties(parties);
+    await program.methods
+      .initRateLimit(bob.publicKey)
+      .accounts({ payer: provider.wallet.publicKey })
+      .rpc({ commitment: "confirmed" });
+    await setRateLimit(new BN(2));
+  });
+
+  after(async () => {
+    await setRateLimit(new BN(0));
+  });
+
+  it("Test redeems without the rate limit are rejected", async () => {
+    try {
+      await initiateAndRedeem(null);
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRateLimit");
+    }
+  });
+
+  it("Test redeems up to the limit", async () => {
+    await initiateAndRedeem(rateLimit);
+    await initiateAndRedeem(rateLimit);
+    const { count } = await program.account.rateLimit.fetch(rateLimit);
+    expect(count.toNumber()).to.equal(2);
+  });
+
+  it("Test redeems beyond the limit are rejected", async () => {
+    try {
+      await initiateAndRedeem(rateLimit);
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("RateLimited");
+    }
+  });
+
+  it("Test redeems not signed by the redeemer are never limited", async () => {
+    // Neither the rate limit is required, nor is its exhausted window in the way
+    await initiateAndRedeem(null, false);
+    await initiateAndRedeem(rateLimit, false);
+    const { count } = await program.account.rateLimit.fetch(rateLimit);
+    expect(count.toNumber()).to.equal(2);
+  });
+
+  it("Test the limit resets after the window", async () => {
+    await setTimeout(windowSlots.toNumber() * 400 + 1000);
+    await initiateAndRedeem(rateLimit);
+    const { count } = await program.account.rateLimit.fetch(rateLimit);
+    expect(count.toNumber()).to.equal(1);
+  });
+});
+
+describe("Testing secrets recorded for the opposite leg", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+  const revealedSecret = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("revealed_secret"), secretHash],
+    program.programId
+  )[0];
+
+  const recordSecret = (secret: Buffer) =>
+    program.methods
+      .recordSecret([...secret])
+      .accountsPartial({
+        swapAccount,
+        revealedSecret,
+        payer: provider.wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await airdrop(alice.publicKey, web3.LAMPORTS_PER_SOL);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      rentSponsor: alice,
+      minimal: true,
+    });
+  });
+
+  it("Test recording a wrong secret is rejected", async () => {
+    try {
+      await recordSecret(crypto.randomBytes(32));
+      expect.fail("record_secret should reject a wrong secret");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidSecret");
+    }
+    const info = await connection.getAccountInfo(revealedSecret);
+    expect(info).to.be.null;
+  });
+
+  it("Test recording the secret without paying out", async () => {
+    const preBalance = await getSwapBalance(swapAccount);
+    const signature = await recordSecret(secret);
+
+    const recorded = await program.account.revealedSecret.fetch(revealedSecret);
+    expect(Buffer.from(recorded.secret).equals(secret)).to.be.true;
+
+    // The swap remains active with its funds
+    const postBalance = await getSwapBalance(swapAccount);
+    expect(postBalance).to.equal(preBalance);
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("secretRevealed");
+    expect(Buffer.from(event.data.secret).equals(secret)).to.be.true;
+  });
+});
+
+describe("Testing initiates funded from wSOL", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const tokenProgram = new web3.PublicKey(
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+  );
+  const nativeMint = new web3.PublicKey(
+    "So11111111111111111111111111111111111111112"
+  );
+  // The size of an SPL token account
+  const tokenAccountSize = 165;
+
+  // Wraps `lamports` into a new wSOL account of `owner`
+  const createWsolAccount = async (owner: web3.Keypair, lamports: BN) => {
+    const wsolAccount = new web3.Keypair();
+    const rent = await connection.getMinimumBalanceForRentExemption(
+      tokenAccountSize
+    );
+    const tx = new web3.Transaction().add(
+      web3.SystemProgram.createAccount({
+        fromPubkey: owner.publicKey,
+        newAccountPubkey: wsolAccount.publicKey,
+        lamports: rent + lamports.toNumber(),
+        space: tokenAccountSize,
+        programId: tokenProgram,
+      }),
+      new web3.TransactionInstruction({
+        programId: tokenProgram,
+        keys: [
+          { pubkey: wsolAccount.publicKey, isSigner: false, isWritable: true },
+          { pubkey: nativeMint, isSigner: false, isWritable: false },
+        ],
+        data: Buffer.concat([Buffer.from([18]), owner.publicKey.toBuffer()]),
+      })
+    );
+    await web3.sendAndConfirmTransaction(connection, tx, [owner, wsolAccount], {
+      commitment: "confirmed",
+    });
+    return wsolAccount.publicKey;
+  };
+
+  const initiateFromWsol = (secretHash: Buffer, wsolAccount: web3.PublicKey) =>
+    program.methods
+      .initiateFromWsol(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        swapAmount,
+        timelock,
+        null,
+        defaultSwapOptions
+      )
+      .accounts({
+        funder: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        wsolAccount,
+      })
+      .signers([alice, rentSponsor])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test wSOL accounts short of the swap amount are rejected", async () => {
+    const wsolAccount = await createWsolAccount(alice, swapAmount.subn(1));
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    try {
+      await initiateFromWsol(secretHash, wsolAccount);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InsufficientWsolBalance");
+    }
+  });
+
+  it("Test unwrapping wSOL into the escrow of a swap", async () => {
+    const wsolAccount = await createWsolAccount(alice, swapAmount);
+    const wsolRent =
+      (await connection.getBalance(wsolAccount)) - swapAmount.toNumber();
+    const preBalance = await connection.getBalance(alice.publicKey);
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    await initiateFromWsol(secretHash, wsolAccount);
+
+    // The wSOL account is closed, its rent is left to the funder and the
+    // wrapped lamports are escrowed
+    expect(await connection.getAccountInfo(wsolAccount)).to.be.null;
+    const postBalance = await connection.getBalance(alice.publicKey);
+    expect(postBalance - preBalance).to.equal(wsolRent);
+    const swapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      swapAmount,
+      timelock
+    );
+    const swapBalance = await getSwapBalance(swapAccount);
+    expect(swapBalance).to.equal(
+      (await getRentAmount()) + swapAmount.toNumber()
+    );
+  });
+});
+
+describe("Testing instant refunds past the expiry slot", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(5);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    console.log("Awaiting timelock for refund");
+    await setTimeout(timelock.toNumber() * 400 + 1000);
+  });
+
+  it("Test instant refunds are rejected after expiry", async () => {
+    try {
+      await program.methods
+        .instantRefund()
+        .accounts({
+          swapAccount,
+          refundee: alice.publicKey,
+          instantRefundAuthority: bob.publicKey,
+          rentSponsor: rentSponsor.publicKey,
+        })
+        .signers([bob])
+        .rpc({ commitment: "confirmed" });
+      expect.fail("The instant refund should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("UseRegularRefund");
+    }
+  });
+
+  it("Test the regular refund remains available", async () => {
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    await program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    const alicePostBalance = await connection.getBalance(alice.publicKey);
+    expect(alicePostBalance).to.equal(alicePreBalance + swapAmount.toNumber());
+  });
+});
+
+describe("Testing redeems split across recipients", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+
+  const initiate = async (
+    distribution: { recipient: web3.PublicKey; bps: number }[]
+  ) => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { distribution },
+    });
+    return { secret, swapAccount };
+  };
+
+  const redeem = (
+    secret: Buffer,
+    swapAccount: web3.PublicKey,
+    recipients: web3.PublicKey[]
+  ) =>
+    program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .remainingAccounts(
+        recipients.map((pubkey) => ({
+          pubkey,
+          isSigner: false,
+          isWritable: true,
+        }))
+      )
+      .rpc({ commitment: "confirmed" });
+
+  // Redeems a swap split across new recipients with the given basis points,
+  // asserting that each receives its share in place of the redeemer
+  const splitRedeem = async (bps: number[]) => {
+    const recipients = bps.map(() => web3.Keypair.generate().publicKey);
+    const { secret, swapAccount } = await initiate(
+      recipients.map((recipient, i) => ({ recipient, bps: bps[i] }))
+    );
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+    await redeem(secret, swapAccount, recipients);
+
+    expect(await connection.getBalance(bob.publicKey)).to.equal(bobPreBalance);
+    const received = await Promise.all(
+      recipients.map((recipient) => connection.getBalance(recipient))
+    );
+    expect(received).to.deep.equal(
+      bps.map((bps) => swapAmount.muln(bps).divn(10_000).toNumber())
+    );
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a 2-way split", async () => {
+    await splitRedeem([7_500, 2_500]);
+  });
+
+  it("Test a 3-way split", async () => {
+    await splitRedeem([5_000, 3_000, 2_000]);
+  });
+
+  it("Test distributions not totalling 10,000 bps are rejected", async () => {
+    try {
+      await initiate([
+        { recipient: web3.Keypair.generate().publicKey, bps: 5_000 },
+        { recipient: web3.Keypair.generate().publicKey, bps: 4_000 },
+      ]);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidDistribution");
+    }
+  });
+
+  it("Test redeems to other recipients are rejected", async () => {
+    const recipients = [0, 1].map(() => web3.Keypair.generate().publicKey);
+    const { secret, swapAccount } = await initiate(
+      recipients.map((recipient) => ({ recipient, bps: 5_000 }))
+    );
+    try {
+      await redeem(secret, swapAccount, [...recipients].reverse());
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRecipients");
+    }
+  });
+});
+
+describe("Testing initiates by an underfunded funder", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { rentSponsor } = parties;
+
+  // Initiates a swap refundable to `funder`, with the rent sponsored by
+  // `rentSponsor` unless given
+  const initiate = (funder: web3.Keypair, sponsor = rentSponsor) =>
+    initiateSwap({
+      ...parties,
+      secretHash: crypto.randomBytes(32),
+      swapAmount,
+      timelock,
+      refundee: funder.publicKey,
+      funder,
+      rentSponsor: sponsor,
+      minimal: true,
+    });
+
+  before(async () => {
+    await airdrop(rentSponsor.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test a funder short of the swap amount is rejected", async () => {
+    const funder = new web3.Keypair();
+    await airdrop(funder.publicKey, swapAmount.toNumber() / 2);
+    try {
+      await initiate(funder);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InsufficientFunderBalance");
+    }
+  });
+
+  it("Test a funder left below rent exemption is rejected", async () => {
+    const funder = new web3.Keypair();
+    await airdrop(funder.publicKey, swapAmount.toNumber() + 1);
+    try {
+      await initiate(funder);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InsufficientFunderBalance");
+    }
+  });
+
+  it("Test a funder sponsoring the rent must also cover it", async () => {
+    const funder = new web3.Keypair();
+    const rentAmount = await getRentAmount();
+    // Covers the swap amount and half of the rent, which is charged first
+    await airdrop(funder.publicKey, swapAmount.toNumber() + rentAmount / 2);
+    try {
+      await initiate(funder, funder);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InsufficientFunderBalance");
+    }
+  });
+});
+
+describe("Testing amendments of swaps", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const funder = new web3.Keypair();
+
+  const initiate = async (secretHash: Buffer, funderCancelable: boolean) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      funder,
+      options: { funderCancelable },
+    });
+    return swapAccount;
+  };
+
+  const amend = (
+    oldSwapAccount: web3.PublicKey,
+    secretHash: Buffer,
+    newSwapAmount: BN,
+    newTimelock: BN
+  ) =>
+    program.methods
+      .amend(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        newSwapAmount,
+        newTimelock,
+        null,
+        { ...defaultSwapOptions, funderCancelable: true }
+      )
+      .accounts({
+        funder: funder.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        oldSwapAccount,
+        oldVault: getVault(oldSwapAccount),
+      })
+      .signers([funder, rentSponsor])
+      .rpc({ commitment: "confirmed" });
+
+  const newSecretHash = () =>
+    crypto.createHash("sha256").update(crypto.randomBytes(32)).digest();
+
+  before(async () => {
+    await airdrop(funder.publicKey, web3.LAMPORTS_PER_SOL);
+    await airdrop(rentSponsor.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test the funder amends the amount and timelock", async () => {
+    const secretHash = newSecretHash();
+    const oldSwapAccount = await initiate(secretHash, true);
+    const newSwapAmount = swapAmount.muln(2);
+    const newTimelock = timelock.muln(2);
+    const funderPreBalance = await connection.getBalance(funder.publicKey);
+
+    const amendSignature = await amend(
+      oldSwapAccount,
+      secretHash,
+      newSwapAmount,
+      newTimelock
+    );
+    console.log("Amended:", amendSignature);
+
+    expect(await connection.getAccountInfo(oldSwapAccount)).to.be.null;
+    expect(await connection.getAccountInfo(getVault(oldSwapAccount))).to.be
+      .null;
+    const newSwapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      newSwapAmount,
+      newTimelock
+    );
+    const swap = await program.account.swapAccount.fetch(newSwapAccount);
+    expect(swap.swapAmount.eq(newSwapAmount)).to.be.true;
+    expect(swap.timelock.eq(newTimelock)).to.be.true;
+    expect(await getSwapBalance(newSwapAccount)).to.equal(
+      (await getRentAmount()) + newSwapAmount.toNumber()
+    );
+    // The funder only tops up the difference in the swap amounts
+    const funderPostBalance = await connection.getBalance(funder.publicKey);
+    expect(funderPostBalance).to.equal(
+      funderPreBalance - newSwapAmount.sub(swapAmount).toNumber()
+    );
+
+    const tx = await connection.getTransaction(amendSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events.map((event) => event.name)).to.deep.equal([
+      "refunded",
+      "initiated",
+    ]);
+  });
+
+  it("Test a non-cancelable swap cannot be amended", async () => {
+    const secretHash = newSecretHash();
+    const oldSwapAccount = await initiate(secretHash, false);
+
+    try {
+      await amend(oldSwapAccount, secretHash, swapAmount, timelock.muln(2));
+      expect.fail("Amend should have failed");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidCancelAuthority");
+    }
+  });
+});
+
+describe("Testing payees passed as read-only accounts", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiate = async (secretHash: Buffer) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    return swapAccount;
+  };
+
+  // Sends the instruction with `payee` marked read-only, returning the error code
+  const sendReadOnly = async (
+    ix: web3.TransactionInstruction,
+    payee: web3.PublicKey
+  ) => {
+    ix.keys
+      .filter((key) => key.pubkey.equals(payee))
+      .forEach((key) => (key.isWritable = false));
+    try {
+      await provider.sendAndConfirm(new web3.Transaction().add(ix));
+      expect.fail("The transaction should have failed");
+    } catch (err) {
+      return AnchorError.parse(err.logs).error.errorCode.code;
+    }
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test refund rejects a read-only refundee", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const swapAccount = await initiate(secretHash);
+    const ix = await program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .instruction();
+    expect(await sendReadOnly(ix, alice.publicKey)).to.equal(
+      "RefundeeNotWritable"
+    );
+  });
+
+  it("Test redeem rejects a read-only redeemer", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const swapAccount = await initiate(secretHash);
+    const ix = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .instruction();
+    expect(await sendReadOnly(ix, bob.publicKey)).to.equal(
+      "RedeemerNotWritable"
+    );
+  });
+
+  it("Test redeem rejects a read-only rent sponsor or funder", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const swapAccount = await initiate(secretHash);
+    const redeem = () =>
+      program.methods
+        .redeem([...secret])
+        .accounts({
+          swapAccount,
+          redeemer: bob.publicKey,
+          rentSponsor: rentSponsor.publicKey,
+          relayer: null,
+          rateLimit: null,
+          priceOracle: null,
+          refundee: null,
+          rebateRecipient: null,
+          withdrawalEscrow: null,
+          funder: alice.publicKey,
+          refundQueue: null,
+        })
+        .instruction();
+    expect(await sendReadOnly(await redeem(), rentSponsor.publicKey)).to.equal(
+      "RentSponsorNotWritable"
+    );
+    expect(await sendReadOnly(await redeem(), alice.publicKey)).to.equal(
+      "FunderNotWritable"
+    );
+  });
+
+  it("Test initiate rejects a read-only rent sponsor", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const ix = await program.methods
+      .initiateMinimal(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        swapAmount,
+        timelock
+      )
+      .accounts({
+        funder: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .instruction();
+    ix.keys
+      .filter((key) => key.pubkey.equals(rentSponsor.publicKey))
+      .forEach((key) => (key.isWritable = false));
+    try {
+      await provider.sendAndConfirm(new web3.Transaction().add(ix), [
+        alice,
+        rentSponsor,
+      ]);
+      expect.fail("initiate should require a writable rent sponsor");
+    } catch (err) {
+      expect(err.logs.join("\n")).to.include(
+        `${rentSponsor.publicKey.toBase58()}'s writable privilege escalated`
+      );
+    }
+
+    // The initiate failed as a whole, leaving no swap behind
+    const swapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      swapAmount,
+      timelock
+    );
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+  });
+});
+
+describe("Testing swaps declined by the redeemer", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const funder = new web3.Keypair();
+
+  before(async () => {
+    await airdrop(funder.publicKey, web3.LAMPORTS_PER_SOL);
+    await airdrop(rentSponsor.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test the redeemer declines and the funds return to the funder", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      funder,
+      minimal: true,
+    });
+    const funderPreBalance = await connection.getBalance(funder.publicKey);
+
+    const declineSignature = await program.methods
+      .decline()
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        funder: funder.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+    console.log("Bob declined:", declineSignature);
+
+    const funderPostBalance = await connection.getBalance(funder.publicKey);
+    expect(funderPostBalance).to.equal(
+      funderPreBalance + swapAmount.toNumber()
+    );
+    expect(await connection.getBalance(alice.publicKey)).to.equal(0);
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+
+    const tx = await connection.getTransaction(declineSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events.map((event) => event.name)).to.include("declined");
+  });
+});
+
+describe("Testing rebates committed to by the redeemer", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiate = async (secretHash: Buffer) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    return swapAccount;
+  };
+
+  const commitRebate = (
+    swapAccount: web3.PublicKey,
+    rebateBps: number,
+    rebateRecipient: web3.PublicKey
+  ) =>
+    program.methods
+      .commitRebate(rebateBps, rebateRecipient)
+      .accounts({ swapAccount, redeemer: bob.publicKey })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test redeem rebates the committed share to the funder", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const swapAccount = await initiate(secretHash);
+    await commitRebate(swapAccount, 1_000, alice.publicKey);
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+
+    const redeemSignature = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: alice.publicKey,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const rebate = swapAmount.toNumber() / 10;
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance + rebate
+    );
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      bobPreBalance + swapAmount.toNumber() - rebate
+    );
+
+    const tx = await connection.getTransaction(redeemSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    const redeemed = events.find((event) => event.name === "redeemed");
+    expect(redeemed.data.rebate.toNumber()).to.equal(rebate);
+    expect(redeemed.data.redeemerAmount.toNumber()).to.equal(
+      swapAmount.toNumber() - rebate
+    );
+  });
+
+  it("Test a committed rebate cannot be lowered", async () => {
+    const swapAccount = await initiate(crypto.randomBytes(32));
+    await commitRebate(swapAccount, 1_000, alice.publicKey);
+    try {
+      await commitRebate(swapAccount, 500, alice.publicKey);
+      expect.fail("The commitment should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRebate");
+    }
+  });
+
+  it("Test the rebate must go to the funder", async () => {
+    const swapAccount = await initiate(crypto.randomBytes(32));
+    try {
+      await commitRebate(swapAccount, 1_000, bob.publicKey);
+      expect.fail("The commitment should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidFunder");
+    }
+  });
+});
+
+describe("Testing initiates at an absolute expiry slot", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiateAt = async (secretHash: Buffer, expirySlot: BN) => {
+    await program.methods
+      .initiateAt(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        swapAmount,
+        expirySlot,
+        null,
+        defaultSwapOptions
+      )
+      .accounts({
+        funder: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .signers([alice, rentSponsor])
+      .rpc({ commitment: "confirmed" });
+    // The expiry slot takes the place of the timelock in the seeds
+    return getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      swapAmount,
+      expirySlot
+    );
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the stored expiry slot is the requested one", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const expirySlot = new BN((await connection.getSlot()) + 100);
+    const swapAccount = await initiateAt(secretHash, expirySlot);
+
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    expect(swap.expirySlot.eq(expirySlot)).to.be.true;
+    expect(swap.absoluteExpiry).to.be.true;
+    expect(swap.timelock.eq(expirySlot.sub(swap.initiatedSlot))).to.be.true;
+
+    // The swap remains addressable by its expiry slot
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+  });
+
+  it("Test an expiry slot in the past is rejected", async () => {
+    const secretHash = crypto.randomBytes(32);
+    const expirySlot = new BN(await connection.getSlot());
+    try {
+      await initiateAt(secretHash, expirySlot);
+      expect.fail("The initiate should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("ExpiryNotInFuture");
+    }
+  });
+});
+
+describe("Testing duplicate initiates", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { alice, rentSponsor } = parties;
+  const secretHash = crypto.randomBytes(32);
+
+  const initiate = () =>
+    initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a duplicate initiate moves no lamports", async () => {
+    await initiate();
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    const rentSponsorPreBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+
+    try {
+      await initiate();
+      expect.fail("The duplicate initiate should have failed");
+    } catch (err) {
+      expect(err.logs.join("\n")).to.include("already in use");
+    }
+
+    // The provider wallet pays the fees, so that neither balance moves at all
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance
+    );
+    expect(await connection.getBalance(rentSponsor.publicKey)).to.equal(
+      rentSponsorPreBalance
+    );
+  });
+});
+
+describe("Testing secrets are never persisted", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test no account of the program holds a redeemed secret", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const accounts = await connection.getProgramAccounts(program.programId, {
+      commitment: "confirmed",
+    });
+    for (const { account } of accounts) {
+      expect(account.data.includes(secret)).to.be.false;
+    }
+  });
+});
+
+describe("Testing the cliff before instant refunds", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiate = async (instantRefundCliffSlots: BN) => {
+    const secretHash = crypto.randomBytes(32);
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { instantRefundCliffSlots },
+    });
+    return swapAccount;
+  };
+
+  const instantRefund = (swapAccount: web3.PublicKey) =>
+    program.methods
+      .instantRefund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        instantRefundAuthority: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test an instant refund within the cliff is rejected", async () => {
+    const swapAccount = await initiate(new BN(5_000));
+    try {
+      await instantRefund(swapAccount);
+      expect.fail("The instant refund should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InstantRefundTooEarly");
+    }
+  });
+
+  it("Test an instant refund past the cliff is allowed", async () => {
+    const cliff = new BN(2);
+    const swapAccount = await initiate(cliff);
+    const { initiatedSlot } = await program.account.swapAccount.fetch(
+      swapAccount
+    );
+    const cliffEnd = initiatedSlot.add(cliff).toNumber();
+    while ((await connection.getSlot("confirmed")) < cliffEnd) {
+      await setTimeout(400);
+    }
+
+    await instantRefund(swapAccount);
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+  });
+});
+
+describe("Testing snapshots of swaps", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the snapshot matches the swap account", async () => {
+    const secretHash = crypto.randomBytes(32);
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+    const snapshotSignature = await program.methods
+      .snapshot()
+      .accounts({ swapAccount })
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await connection.getTransaction(snapshotSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("swapSnapshot");
+    expect(event.data.swapAccount.equals(swapAccount)).to.be.true;
+    expect(event.data.slot.toNumber()).to.equal(tx.slot);
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    expect(JSON.stringify(event.data.swap)).to.equal(JSON.stringify(swap));
+  });
+});
+
+describe("Testing redeems into a withdrawal escrow", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const delaySlots = new BN(4);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+  const withdrawalEscrow = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("withdrawal_escrow"), bob.publicKey.toBuffer()],
+    program.programId
+  )[0];
+
+  const withdraw = () =>
+    program.methods
+      .withdraw()
+      .accounts({ redeemer: bob.publicKey })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await airdrop(bob.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+    await program.methods
+      .initWithdrawalEscrow(delaySlots)
+      .accounts({ redeemer: bob.publicKey })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Test funds are withdrawable only after the delay", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    const escrowPreBalance = await connection.getBalance(withdrawalEscrow);
+
+    const redeemSignature = await program.methods
+      .redeemToEscrow([...secret], null)
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow,
+        funder: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    expect(await connection.getBalance(withdrawalEscrow)).to.equal(
+      escrowPreBalance + swapAmount.toNumber()
+    );
+    const tx = await connection.getTransaction(redeemSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events.some((event) => event.name === "redeemed")).to.be.true;
+    const scheduled = events.find(
+      (event) => event.name === "withdrawalScheduled"
+    );
+    expect(scheduled.data.redeemer.equals(bob.publicKey)).to.be.true;
+    expect(scheduled.data.amount.toNumber()).to.equal(swapAmount.toNumber());
+    const withdrawableAfter = scheduled.data.withdrawableAfter.toNumber();
+    expect(withdrawableAfter).to.equal(tx.slot + delaySlots.toNumber());
+
+    try {
+      await withdraw();
+      expect.fail("The withdrawal should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("WithdrawalTooEarly");
+    }
+
+    while ((await connection.getSlot("confirmed")) <= withdrawableAfter) {
+      await setTimeout(400);
+    }
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+    await withdraw();
+    expect(await connection.getBalance(withdrawalEscrow)).to.equal(
+      escrowPreBalance
+    );
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      bobPreBalance + swapAmount.toNumber()
+    );
+  });
+});
+
+describe("Testing refunds to a refundee that was never initialized", () => {
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+
+  // Initiates a swap of `swapAmount` refunding to a fresh address
+  const initiate = async (swapAmount: BN) => {
+    const refundee = web3.Keypair.generate().publicKey;
+    const secretHash = crypto.randomBytes(32);
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      refundee,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { instantRefundAuthority: bob.publicKey },
+    });
+    return { refundee, swapAccount };
+  };
+
+  const instantRefund = (
+    swapAccount: web3.PublicKey,
+    refundee: web3.PublicKey
+  ) =>
+    program.methods
+      .instantRefund()
+      .accounts({
+        swapAccount,
+        refundee,
+        instantRefundAuthority: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the refund lands in a fresh system account", async () => {
+    const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+    const { refundee, swapAccount } = await initiate(swapAmount);
+    expect(await connection.getAccountInfo(refundee)).to.be.null;
+
+    await instantRefund(swapAccount, refundee);
+
+    const refundeeInfo = await connection.getAccountInfo(refundee);
+    expect(refundeeInfo.lamports).to.equal(swapAmount.toNumber());
+    expect(refundeeInfo.owner.equals(web3.SystemProgram.programId)).to.be.true;
+    expect(refundeeInfo.data.length).to.equal(0);
+    expect(await getSwapBalance(swapAccount)).to.equal(0);
+  });
+
+  it("Test a refund below the rent exemption waits for the refundee to be funded", async () => {
+    const swapAmount = new BN(1_000);
+    const { refundee, swapAccount } = await initiate(swapAmount);
+    const swapBalance = await getSwapBalance(swapAccount);
+
+    try {
+      await instantRefund(swapAccount, refundee);
+      expect.fail("The refund should have been rejected");
+    } catch (err) {
+      expect(err.message).to.include("insufficient funds for rent");
+    }
+    expect(await getSwapBalance(swapAccount)).to.equal(swapBalance);
+
+    const rentExemption = await connection.getMinimumBalanceForRentExemption(0);
+    await airdrop(refundee, rentExemption);
+    await instantRefund(swapAccount, refundee);
+    expect(await connection.getBalance(refundee)).to.equal(
+      rentExemption + swapAmount.toNumber()
+    );
+  });
+});
+
+describe("Testing the program version", () => {
+  it("Test the version is emitted", async () => {
+    const signature = await program.methods
+      .programVersion()
+      .rpc({ commitment: "confirmed" });
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("version");
+    expect(event.data.version).to.match(/^\d+\.\d+\.\d+$/);
+    // The tests run against a build without optional features, except for
+    // `lite-redeem`, whose compute units are compared with those of `redeem`
+    const liteRedeemFlag = 1 << 4;
+    expect(event.data.featureFlags.toNumber() & ~liteRedeemFlag).to.equal(0);
+  });
+});
+
+describe("Testing splits of swaps into child swaps", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, rentSponsor } = parties;
+  const redeemers = [...Array(3)].map(() => web3.Keypair.generate());
+  const splitAmounts = [
+    new BN(0.05 * web3.LAMPORTS_PER_SOL),
+    new BN(0.03 * web3.LAMPORTS_PER_SOL),
+    new BN(0.02 * web3.LAMPORTS_PER_SOL),
+  ];
+
+  const initiate = async (secretHash: Buffer, funderCancelable: boolean) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { funderCancelable },
+    });
+    return swapAccount;
+  };
+
+  const splitSwap = (swapAccount: web3.PublicKey, secretHash: Buffer) => {
+    const children = redeemers.map((redeemer, i) =>
+      getSwapAccount(
+        redeemer.publicKey,
+        alice.publicKey,
+        secretHash,
+        splitAmounts[i],
+        timelock
+      )
+    );
+    const splits = redeemers.map((redeemer, i) => ({
+      redeemer: redeemer.publicKey,
+      swapAmount: splitAmounts[i],
+    }));
+    const remainingAccounts = children.flatMap((child) =>
+      [child, getVault(child)].map((pubkey) => ({
+        pubkey,
+        isSigner: false,
+        isWritable: true,
+      }))
+    );
+    return {
+      children,
+      split: () =>
+        program.methods
+          .splitSwap(splits)
+          .accounts({
+            swapAccount,
+            funder: alice.publicKey,
+            rentSponsor: rentSponsor.publicKey,
+          })
+          .remainingAccounts(remainingAccounts)
+          .signers([alice, rentSponsor])
+          .rpc({ commitment: "confirmed" }),
+    };
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the funder splits a swap into three children", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const swapAccount = await initiate(secretHash, true);
+    const { children, split } = splitSwap(swapAccount, secretHash);
+
+    const splitSignature = await split();
+
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+    expect(await connection.getAccountInfo(getVault(swapAccount))).to.be.null;
+    const rentAmount = await getRentAmount();
+    for (const [i, child] of children.entries()) {
+      expect(await getSwapBalance(child)).to.equal(
+        rentAmount + splitAmounts[i].toNumber()
+      );
+      const swap = await program.account.swapAccount.fetch(child);
+      expect(swap.redeemer.equals(redeemers[i].publicKey)).to.be.true;
+      expect(swap.swapAmount.eq(splitAmounts[i])).to.be.true;
+      expect(Buffer.from(swap.secretHash).equals(secretHash)).to.be.true;
+      expect(swap.timelock.eq(timelock)).to.be.true;
+    }
+
+    const tx = await connection.getTransaction(splitSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events.map((event) => event.name)).to.deep.equal(
+      Array(3).fill("initiated")
+    );
+
+    // Each child is redeemed with the secret of the swap
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount: children[0],
+        redeemer: redeemers[0].publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getBalance(redeemers[0].publicKey)).to.equal(
+      splitAmounts[0].toNumber()
+    );
+  });
+
+  it("Test a swap not cancelable by the funder cannot be split", async () => {
+    const secretHash = crypto.randomBytes(32);
+    const swapAccount = await initiate(secretHash, false);
+    try {
+      await splitSwap(swapAccount, secretHash).split();
+      expect.fail("The split should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidCancelAuthority");
+    }
+  });
+});
+
+describe("Testing redeems of sibling swaps", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { rentSponsor } = parties;
+
+  // Initiates a swap of `secretHash` to a fresh redeemer
+  const initiate = async (secretHash: Buffer) => {
+    const redeemer = web3.Keypair.generate().publicKey;
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      redeemer,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+    return { redeemer, swapAccount };
+  };
+
+  const redeemSiblings = (
+    secret: Buffer,
+    siblings: { redeemer: web3.PublicKey; swapAccount: web3.PublicKey }[]
+  ) =>
+    program.methods
+      .redeemSiblings([...secret])
+      .remainingAccounts(
+        siblings.flatMap(({ redeemer, swapAccount }) =>
+          [
+            swapAccount,
+            getVault(swapAccount),
+            redeemer,
+            rentSponsor.publicKey,
+          ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }))
+        )
+      )
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test three siblings are redeemed at once", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const siblings = [
+      await initiate(secretHash),
+      await initiate(secretHash),
+      await initiate(secretHash),
+    ];
+
+    const signature = await redeemSiblings(secret, siblings);
+
+    for (const { redeemer, swapAccount } of siblings) {
+      expect(await connection.getBalance(redeemer)).to.equal(
+        swapAmount.toNumber()
+      );
+      expect(await getSwapBalance(swapAccount)).to.equal(0);
+    }
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const redeemed = [...eventParser.parseLogs(tx.meta.logMessages)].filter(
+      (event) => event.name === "redeemed"
+    );
+    expect(redeemed).to.have.lengthOf(3);
+    for (const [i, event] of redeemed.entries()) {
+      expect(event.data.redeemer.equals(siblings[i].redeemer)).to.be.true;
+      expect(Buffer.from(event.data.secret).equals(secret)).to.be.true;
+    }
+  });
+
+  it("Test a sibling with another secret hash fails the redeem", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const sibling = await initiate(secretHash);
+    const stranger = await initiate(crypto.randomBytes(32));
+
+    try {
+      await redeemSiblings(secret, [sibling, stranger]);
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidSecret");
+    }
+    // No sibling is redeemed
+    expect(await connection.getBalance(sibling.redeemer)).to.equal(0);
+    expect(await getSwapBalance(sibling.swapAccount)).to.be.greaterThan(0);
+  });
+});
+
+describe("Testing vesting swaps", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(40);
+  const parties = newParties();
+  const { alice, rentSponsor } = parties;
+
+  // Initiates a fresh vesting swap, returning its secret and redeemer
+  const initiate = async () => {
+    const bob = web3.Keypair.generate();
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      redeemer: bob.publicKey,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { vesting: true },
+    });
+    return { bob, secret, swapAccount };
+  };
+
+  // The redeemer is no signer of redeems in the IDL, and is marked as one here
+  // when signing, as required for vesting swaps
+  const redeem = async (
+    bob: web3.Keypair,
+    secret: Buffer,
+    swapAccount: web3.PublicKey,
+    redeemerSigns: boolean
+  ) => {
+    const ix = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: alice.publicKey,
+        refundQueue: null,
+      })
+      .instruction();
+    ix.keys
+      .filter((key) => key.pubkey.equals(bob.publicKey))
+      .forEach((key) => (key.isSigner = redeemerSigns));
+    try {
+      return await provider.sendAndConfirm(
+        new web3.Transaction().add(ix),
+        redeemerSigns ? [bob] : [],
+        { commitment: "confirmed" }
+      );
+    } catch (err) {
+      throw AnchorError.parse(err.logs) ?? err;
+    }
+  };
+
+  // Redeems a fresh vesting swap once `fraction` of its timelock has elapsed
+  const redeemAt = async (fraction: number) => {
+    const { bob, secret, swapAccount } = await initiate();
+    const { initiatedSlot } = await program.account.swapAccount.fetch(
+      swapAccount
+    );
+    const vestingPoint =
+      initiatedSlot.toNumber() + fraction * timelock.toNumber();
+    while ((await connection.getSlot("confirmed")) < vestingPoint) {
+      await setTimeout(400);
+    }
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+
+    const redeemSignature = await redeem(bob, secret, swapAccount, true);
+
+    const tx = await connection.getTransaction(redeemSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const elapsed = Math.min(
+      tx.slot - initiatedSlot.toNumber(),
+      timelock.toNumber()
+    );
+    const vested = swapAmount.muln(elapsed).div(timelock).toNumber();
+    const unvested = swapAmount.toNumber() - vested;
+    expect(await connection.getBalance(bob.publicKey)).to.equal(vested);
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance + unvested
+    );
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    const redeemed = events.find((event) => event.name === "redeemed");
+    expect(redeemed.data.redeemerAmount.toNumber()).to.equal(vested);
+    expect(redeemed.data.unvested.toNumber()).to.equal(unvested);
+    return { vested, unvested };
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a redeem at 25% vesting refunds the rest", async () => {
+    const { vested } = await redeemAt(0.25);
+    expect(vested).to.be.at.least(swapAmount.toNumber() / 4);
+  });
+
+  it("Test a redeem at 50% vesting refunds the rest", async () => {
+    const { vested } = await redeemAt(0.5);
+    expect(vested).to.be.at.least(swapAmount.toNumber() / 2);
+  });
+
+  it("Test a redeem at 100% vesting pays out in full", async () => {
+    const { vested, unvested } = await redeemAt(1);
+    expect(vested).to.equal(swapAmount.toNumber());
+    expect(unvested).to.equal(0);
+  });
+
+  it("Test an early redeem by anyone but the redeemer is rejected", async () => {
+    const { bob, secret, swapAccount } = await initiate();
+    try {
+      await redeem(bob, secret, swapAccount, false);
+      expect.fail("The redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("RedeemerNotSigner");
+    }
+    await redeem(bob, secret, swapAccount, true);
+  });
+});
+
+describe("Testing the hash kind of swaps", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a swap locked by a secret hash is redeemed by its secret", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { signature, swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+    const { hashKind } = await program.account.swapAccount.fetch(swapAccount);
+    expect(hashKind).to.deep.equal({ sha256: {} });
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [initiated] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(initiated.data.hashKind).to.deep.equal(hashKind);
+
+    // The adaptor redeem does not apply to this kind, unlike the SHA-256 one
+    const redeemAccounts = {
+      swapAccount,
+      redeemer: bob.publicKey,
+      rentSponsor: rentSponsor.publicKey,
+      relayer: null,
+      rateLimit: null,
+      priceOracle: null,
+      refundee: null,
+      rebateRecipient: null,
+      withdrawalEscrow: null,
+      funder: null,
+      refundQueue: null,
+    };
+    try {
+      await program.methods
+        .redeemAdaptor([...secret])
+        .accounts(redeemAccounts)
+        .rpc({ commitment: "confirmed" });
+      expect.fail("The adaptor redeem should have been rejected");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("NotAnAdaptorSwap");
+    }
+    await program.methods
+      .redeem([...secret])
+      .accounts(redeemAccounts)
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+  });
+});
+
+describe("Testing the return data of redeems", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test redeem returns the swap account, amount and secret", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+    const redeemSignature = await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    // This is the return data a program redeeming through CPI reads back
+    const tx = await connection.getTransaction(redeemSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const { programId, data } = tx.meta.returnData;
+    expect(new web3.PublicKey(programId).equals(program.programId)).to.be.true;
+    const returnData = Buffer.from(data[0], "base64");
+    expect(returnData).to.have.lengthOf(32 + 8 + 32);
+    expect(new web3.PublicKey(returnData.subarray(0, 32)).equals(swapAccount))
+      .to.be.true;
+    expect(new BN(returnData.subarray(32, 40), "le").eq(swapAmount)).to.be
+      .true;
+    expect(returnData.subarray(40).equals(secret)).to.be.true;
+  });
+});
+
+describe("Testing keeper rewards", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const keeperRewardLamports = swapAmount.divn(100);
+  const timelock = new BN(20);
+  const parties = newParties();
+  const { alice, rentSponsor } = parties;
+  const keeper = web3.Keypair.generate();
+
+  before(async () => {
+    await fundParties(parties);
+    await airdrop(keeper.publicKey, 0.01 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test a keeper reward above the bound is rejected", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    try {
+      await initiateSwap({
+        ...parties,
+        secretHash,
+        swapAmount,
+        timelock,
+        options: { keeperRewardLamports: keeperRewardLamports.addn(1) },
+      });
+      expect.fail("initiate should bound the keeper reward");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidKeeperReward");
+    }
+  });
+
+  it("Test the keeper triggering a refund receives the reward", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { keeperRewardLamports },
+    });
+
+    console.log("Awaiting timelock for refund");
+    await setTimeout(timelock.toNumber() * 400 + 1000);
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    const keeperPreBalance = await connection.getBalance(keeper.publicKey);
+
+    const refundSignature = await program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: null,
+        keeper: keeper.publicKey,
+        refundQueue: null,
+      })
+      .signers([keeper])
+      .rpc({ commitment: "confirmed" });
+
+    expect(await connection.getBalance(keeper.publicKey)).to.equal(
+      keeperPreBalance + keeperRewardLamports.toNumber()
+    );
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance + swapAmount.sub(keeperRewardLamports).toNumber()
+    );
+    const tx = await connection.getTransaction(refundSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    const refunded = events.find((event) => event.name === "refunded");
+    expect(refunded.data.keeper.toBase58()).to.equal(
+      keeper.publicKey.toBase58()
+    );
+    expect(refunded.data.keeperReward.toNumber()).to.equal(
+      keeperRewardLamports.toNumber()
+    );
+  });
+});
+
+describe("Testing redeems into a PDA of another program", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { rentSponsor } = parties;
+  const carol = web3.Keypair.generate();
+  const [redeemer] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("redeemer")],
+    mockRedeemer.programId
+  );
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the payout lands in the PDA and is spendable by its program", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      redeemer,
+      secretHash,
+      swapAmount,
+      timelock,
+    });
+    const redeemerPreBalance = await connection.getBalance(redeemer);
+
+    // Redeems require no signature of the redeemer, which a PDA cannot give
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getBalance(redeemer)).to.equal(
+      redeemerPreBalance + swapAmount.toNumber()
+    );
+    const redeemerInfo = await connection.getAccountInfo(redeemer);
+    expect(redeemerInfo.owner.toBase58()).to.equal(
+      web3.SystemProgram.programId.toBase58()
+    );
+
+    // The program spends the payout through a transfer signed by the PDA
+    await mockRedeemer.methods
+      .spend(swapAmount)
+      .accounts({ recipient: carol.publicKey })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getBalance(carol.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+    expect(await connection.getBalance(redeemer)).to.equal(
+      redeemerPreBalance
+    );
+  });
+});
+
+describe("Testing timelocks safe against the counterparty leg", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const counterpartyTimelock = new BN(1_000);
+  const parties = newParties();
+
+  const initiate = async (timelock: BN) => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { counterpartyTimelock },
+    });
+    return swapAccount;
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test a timelock outlasting the counterparty leg is accepted", async () => {
+    const swapAccount = await initiate(counterpartyTimelock.muln(3).divn(2));
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    expect(swap.counterpartyTimelock.toNumber()).to.equal(
+      counterpartyTimelock.toNumber()
+    );
+  });
+
+  it("Test a timelock too close to the counterparty leg is rejected", async () => {
+    try {
+      await initiate(counterpartyTimelock.muln(3).divn(2).subn(1));
+      expect.fail("initiate should reject an unsafe timelock");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("UnsafeTimelock");
+    }
+  });
+});
+
+describe("Testing initiates checking for an active swap", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secretHash = crypto
+    .createHash("sha256")
+    .update(crypto.randomBytes(32))
+    .digest();
+
+  const tryInitiate = () =>
+    program.methods
+      .tryInitiate(
+        bob.publicKey,
+        alice.publicKey,
+        [...secretHash],
+        swapAmount,
+        timelock,
+        null,
+        defaultSwapOptions
+      )
+      .accounts({
+        funder: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        protocolConfig,
+      })
+      .signers([alice, rentSponsor])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the first initiate succeeds and the second fails", async () => {
+    await tryInitiate();
+    const swapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      swapAmount,
+      timelock
+    );
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    expect(swap.swapAmount.toNumber()).to.equal(swapAmount.toNumber());
+
+    try {
+      await tryInitiate();
+      expect.fail("try_initiate should reject an active swap");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SwapAlreadyActive");
+    }
+  });
+});
+
+describe("Testing redeems with a hex-encoded secret", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  const redeemHex = (secretHex: string) =>
+    program.methods
+      .redeemHex(secretHex)
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+  });
+
+  for (const [malformed, secretHex] of [
+    ["too short", secret.toString("hex").slice(2)],
+    ["prefixed", "0x" + secret.toString("hex").slice(2)],
+    ["non-hex", "zz" + secret.toString("hex").slice(2)],
+  ]) {
+    it(`Test a ${malformed} hex secret is rejected`, async () => {
+      try {
+        await redeemHex(secretHex);
+        expect.fail("redeem_hex should reject a malformed hex secret");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.equal("InvalidSecretEncoding");
+      }
+    });
+  }
+
+  it("Test a valid hex secret redeems the swap", async () => {
+    await redeemHex(secret.toString("hex").toUpperCase());
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+  });
+});
+
+describe("Testing merges of compatible swaps", () => {
+  const amounts = [
+    new BN(0.03 * web3.LAMPORTS_PER_SOL),
+    new BN(0.02 * web3.LAMPORTS_PER_SOL),
+  ];
+  const mergedAmount = amounts[0].add(amounts[1]);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiate = async (
+    secretHash: Buffer,
+    swapAmount: BN,
+    queueRefund = false
+  ) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { funderCancelable: true, queueRefund },
+      accounts: { refundQueue: queueRefund ? refundQueue : null },
+    });
+    return swapAccount;
+  };
+
+  const mergeSwaps = (
+    swapAccount: web3.PublicKey,
+    otherSwapAccount: web3.PublicKey,
+    mergedSwapAccount: web3.PublicKey,
+    withQueue = false
+  ) =>
+    program.methods
+      .mergeSwaps()
+      .accounts({
+        swapAccount,
+        otherSwapAccount,
+        mergedSwapAccount,
+        mergedVault: getVault(mergedSwapAccount),
+        funder: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        otherRentSponsor: rentSponsor.publicKey,
+        refundQueue: withQueue ? refundQueue : null,
+      })
+      .signers([alice, rentSponsor])
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test two swaps are merged and the merged swap redeemed", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const swapAccounts = [
+      await initiate(secretHash, amounts[0]),
+      await initiate(secretHash, amounts[1]),
+    ];
+    const mergedSwapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      mergedAmount,
+      timelock
+    );
+
+    const mergeSignature = await mergeSwaps(
+      swapAccounts[0],
+      swapAccounts[1],
+      mergedSwapAccount
+    );
+
+    for (const swapAccount of swapAccounts) {
+      expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+      expect(await connection.getAccountInfo(getVault(swapAccount))).to.be
+        .null;
+    }
+    const merged = await program.account.swapAccount.fetch(mergedSwapAccount);
+    expect(merged.swapAmount.eq(mergedAmount)).to.be.true;
+    expect(await getSwapBalance(mergedSwapAccount)).to.equal(
+      (await getRentAmount()) + mergedAmount.toNumber()
+    );
+    const tx = await connection.getTransaction(mergeSignature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(events.map((event) => event.name)).to.deep.equal(["merged"]);
+    expect(events[0].data.swapAmount.eq(mergedAmount)).to.be.true;
+
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount: mergedSwapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      mergedAmount.toNumber()
+    );
+  });
+
+  it("Test the merged swap takes the place of the merged swaps in the refund queue", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const swapAccounts = [
+      await initiate(secretHash, amounts[0], true),
+      await initiate(secretHash, amounts[1]),
+    ];
+    const mergedSwapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHash,
+      mergedAmount,
+      timelock
+    );
+
+    await mergeSwaps(swapAccounts[0], swapAccounts[1], mergedSwapAccount, true);
+
+    const { entries } = await program.account.refundQueue.fetch(refundQueue);
+    const queued = entries.map((entry) => entry.swapAccount.toBase58());
+    expect(queued).to.not.include(swapAccounts[0].toBase58());
+    expect(queued).to.include(mergedSwapAccount.toBase58());
+  });
+
+  it("Test swaps with different secret hashes cannot be merged", async () => {
+    const secretHashes = [...Array(2)].map(() =>
+      crypto.createHash("sha256").update(crypto.randomBytes(32)).digest()
+    );
+    const swapAccounts = [
+      await initiate(secretHashes[0], amounts[0]),
+      await initiate(secretHashes[1], amounts[1]),
+    ];
+    const mergedSwapAccount = getSwapAccount(
+      bob.publicKey,
+      alice.publicKey,
+      secretHashes[0],
+      mergedAmount,
+      timelock
+    );
+
+    try {
+      await mergeSwaps(swapAccounts[0], swapAccounts[1], mergedSwapAccount);
+      expect.fail("merge_swaps should reject incompatible swaps");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("IncompatibleSwaps");
+    }
+  });
+});
+
+describe("Testing errors of accounts not matching the swap", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(10_000); // Far from expiring during the tests
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const stranger = web3.Keypair.generate();
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+
+  // Returns the error code of `tx`, which must fail
+  const errorCode = async (tx: Promise<string>) => {
+    try {
+      await tx;
+      expect.fail("The transaction should have failed");
+    } catch (err) {
+      return err.error.errorCode.code;
+    }
+  };
+
+  const redeem = (redeemer: web3.PublicKey, rentSponsor: web3.PublicKey) =>
+    program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer,
+        rentSponsor,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc();
+
+  const refund = (refundee: web3.PublicKey, rentSponsor: web3.PublicKey) =>
+    program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee,
+        rentSponsor,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue: null,
+      })
+      .rpc();
+
+  const instantRefund = (
+    refundee: web3.PublicKey,
+    rentSponsor: web3.PublicKey
+  ) =>
+    program.methods
+      .instantRefund()
+      .accounts({
+        swapAccount,
+        refundee,
+        instantRefundAuthority: bob.publicKey,
+        rentSponsor,
+      })
+      .signers([bob])
+      .rpc();
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+  });
+
+  it("Test redeem rejects a wrong redeemer or rent sponsor", async () => {
+    expect(
+      await errorCode(redeem(stranger.publicKey, rentSponsor.publicKey))
+    ).to.equal("InvalidRedeemer");
+    expect(await errorCode(redeem(bob.publicKey, stranger.publicKey))).to.equal(
+      "InvalidRentSponsor"
+    );
+  });
+
+  it("Test refund rejects a wrong refundee or rent sponsor", async () => {
+    expect(
+      await errorCode(refund(stranger.publicKey, rentSponsor.publicKey))
+    ).to.equal("InvalidRefundee");
+    expect(
+      await errorCode(refund(alice.publicKey, stranger.publicKey))
+    ).to.equal("InvalidRentSponsor");
+  });
+
+  it("Test instant refund rejects a wrong refundee or rent sponsor", async () => {
+    expect(
+      await errorCode(instantRefund(stranger.publicKey, rentSponsor.publicKey))
+    ).to.equal("InvalidRefundee");
+    expect(
+      await errorCode(instantRefund(alice.publicKey, stranger.publicKey))
+    ).to.equal("InvalidRentSponsor");
+  });
+});
+
+describe("Testing the refund queue", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(20);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiate = async (secretHash: Buffer, withQueue = true) => {
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      options: { queueRefund: true },
+      accounts: { refundQueue: withQueue ? refundQueue : null },
+    });
+    return swapAccount;
+  };
+
+  const getQueuedExpiry = async (swapAccount: web3.PublicKey) => {
+    const { entries } = await program.account.refundQueue.fetch(refundQueue);
+    return entries.find((entry) => entry.swapAccount.equals(swapAccount))
+      ?.expirySlot;
+  };
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test initiating registers the swap and redeeming deregisters it", async () => {
+    const secret = crypto.randomBytes(32);
+    const secretHash = crypto.createHash("sha256").update(secret).digest();
+    const swapAccount = await initiate(secretHash);
+
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    const expirySlot = await getQueuedExpiry(swapAccount);
+    expect(expirySlot.eq(swap.expirySlot)).to.be.true;
+
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await getQueuedExpiry(swapAccount)).to.be.undefined;
+  });
+
+  it("Test refunding deregisters the swap", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const swapAccount = await initiate(secretHash);
+    expect(await getQueuedExpiry(swapAccount)).to.not.be.undefined;
+
+    console.log("Awaiting timelock for refund");
+    await setTimeout(timelock.toNumber() * 400 + 1000);
+    await program.methods
+      .refund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        funder: null,
+        refundTrigger: null,
+        keeper: null,
+        refundQueue,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await getQueuedExpiry(swapAccount)).to.be.undefined;
+  });
+
+  it("Test swaps settled otherwise are deregistered by anyone", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const swapAccount = await initiate(secretHash);
+
+    try {
+      await program.methods
+        .deregister()
+        .accounts({ swapAccount })
+        .rpc({ commitment: "confirmed" });
+      expect.fail("deregister should reject an active swap");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SwapAlreadyActive");
+    }
+
+    await program.methods
+      .instantRefund()
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        instantRefundAuthority: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+      })
+      .signers([bob])
+      .rpc({ commitment: "confirmed" });
+    expect(await getQueuedExpiry(swapAccount)).to.not.be.undefined;
+
+    await program.methods
+      .deregister()
+      .accounts({ swapAccount })
+      .rpc({ commitment: "confirmed" });
+    expect(await getQueuedExpiry(swapAccount)).to.be.undefined;
+  });
+
+  it("Test registering requires the refund queue", async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    try {
+      await initiate(secretHash, false);
+      expect.fail("initiate should require the refund queue");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("MissingRefundQueue");
+    }
+  });
+});
+
+describe("Testing penalties of instant refunds", () => {
+  const swapAmount = new BN(0.1 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(1000);
+  const penaltyBps = 500;
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+
+  const initiate = async () => {
+    const secretHash = crypto
+      .createHash("sha256")
+      .update(crypto.randomBytes(32))
+      .digest();
+    const { swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+    });
+    return swapAccount;
+  };
+
+  // The refundee is no signer of instant refunds in the IDL, and is marked as
+  // one here when consenting to the penalty
+  const instantRefund = async (
+    swapAccount: web3.PublicKey,
+    penaltyBps: number,
+    refundeeSigns: boolean
+  ) => {
+    const ix = await program.methods
+      .instantRefundWithPenalty(penaltyBps)
+      .accounts({
+        swapAccount,
+        refundee: alice.publicKey,
+        instantRefundAuthority: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        redeemer: bob.publicKey,
+      })
+      .instruction();
+    ix.keys
+      .filter((key) => key.pubkey.equals(alice.publicKey))
+      .forEach((key) => (key.isSigner = refundeeSigns));
+    try {
+      return await provider.sendAndConfirm(
+        new web3.Transaction().add(ix),
+        refundeeSigns ? [alice, bob] : [bob],
+        { commitment: "confirmed" }
+      );
+    } catch (err) {
+      throw AnchorError.parse(err.logs) ?? err;
+    }
+  };
+
+  before(async () => {
+    await fundParties(parties);
+    await airdrop(bob.publicKey, 0.01 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test the penalty is paid to the redeemer", async () => {
+    const swapAccount = await initiate();
+    const alicePreBalance = await connection.getBalance(alice.publicKey);
+    const bobPreBalance = await connection.getBalance(bob.publicKey);
+
+    const signature = await instantRefund(swapAccount, penaltyBps, true);
+
+    const penalty = swapAmount.muln(penaltyBps).divn(10_000);
+    const refundAmount = swapAmount.sub(penalty);
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      bobPreBalance + penalty.toNumber()
+    );
+    expect(await connection.getBalance(alice.publicKey)).to.equal(
+      alicePreBalance + refundAmount.toNumber()
+    );
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const [event] = [...eventParser.parseLogs(tx.meta.logMessages)];
+    expect(event.name).to.equal("instantRefunded");
+    expect(event.data.penalty.eq(penalty)).to.be.true;
+    expect(event.data.refundAmount.eq(refundAmount)).to.be.true;
+  });
+
+  it("Test a penalty is rejected without the refundee's signature", async () => {
+    const swapAccount = await initiate();
+    try {
+      await instantRefund(swapAccount, penaltyBps, false);
+      expect.fail("instant refund should require the refundee to sign");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("PenaltyNotConsented");
+    }
+  });
+
+  it("Test a penalty above the bound is rejected", async () => {
+    const swapAccount = await initiate();
+    try {
+      await instantRefund(swapAccount, 1_001, true);
+      expect.fail("instant refund should bound the penalty");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidPenalty");
+    }
+  });
+});
+
+describe("Testing idempotent redeems", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(1000);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const secret = crypto.randomBytes(32);
+  const secretHash = crypto.createHash("sha256").update(secret).digest();
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    secretHash,
+    swapAmount,
+    timelock
+  );
+  const redeemReceipt = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("redeem_receipt"), swapAccount.toBuffer()],
+    program.programId
+  )[0];
+
+  const redeemIdempotent = (
+    secret: Buffer,
+    swap: web3.PublicKey = swapAccount
+  ) =>
+    program.methods
+      .redeemIdempotent([...secret])
+      .accounts({
+        swapAccount: swap,
+        vault: getVault(swap),
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        payer: provider.wallet.publicKey,
+        relayer: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({ ...parties, secretHash, swapAmount, timelock });
+  });
+
+  it("Test a duplicate redeem succeeds without paying out again", async () => {
+    await redeemIdempotent(secret);
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+    const bobBalance = await connection.getBalance(bob.publicKey);
+    expect(bobBalance).to.equal(swapAmount.toNumber());
+
+    const receipt = await program.account.redeemReceipt.fetch(redeemReceipt);
+    expect(Buffer.from(receipt.secret).equals(secret)).to.be.true;
+
+    await redeemIdempotent(secret);
+    expect(await connection.getBalance(bob.publicKey)).to.equal(bobBalance);
+  });
+
+  it("Test a duplicate redeem with another secret fails", async () => {
+    try {
+      await redeemIdempotent(crypto.randomBytes(32));
+      expect.fail("the secret should match the recorded one");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidSecret");
+    }
+  });
+
+  it("Test a redeem of a swap never redeemed fails", async () => {
+    try {
+      await redeemIdempotent(secret, web3.Keypair.generate().publicKey);
+      expect.fail("nothing was redeemed at this address");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SwapNotRedeemed");
+    }
+  });
+
+  it("Test the payer reclaims the rent of the receipt", async () => {
+    await program.methods
+      .closeRedeemReceipt()
+      .accounts({ redeemReceipt, payer: provider.wallet.publicKey })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getAccountInfo(redeemReceipt)).to.be.null;
+
+    try {
+      await redeemIdempotent(secret);
+      expect.fail("the receipt was closed");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SwapNotRedeemed");
+    }
+  });
+});
+
+describe("Testing swaps locked by a Merkle root of secrets", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(1000);
+  const parties = newParties();
+  const { alice, bob, rentSponsor } = parties;
+  const sha256 = (...data: Buffer[]) =>
+    crypto.createHash("sha256").update(Buffer.concat(data)).digest();
+  // Parents hash their children in ascending order
+  const node = (a: Buffer, b: Buffer) =>
+    Buffer.compare(a, b) <= 0 ? sha256(a, b) : sha256(b, a);
+  const secrets = [0, 1, 2, 3].map(() => crypto.randomBytes(32));
+  const leaves = secrets.map((secret) => sha256(secret));
+  const left = node(leaves[0], leaves[1]);
+  const right = node(leaves[2], leaves[3]);
+  const root = node(left, right);
+  const swapAccount = getSwapAccount(
+    bob.publicKey,
+    alice.publicKey,
+    root,
+    swapAmount,
+    timelock
+  );
+  const redeemAccounts = {
+    swapAccount,
+    redeemer: bob.publicKey,
+    rentSponsor: rentSponsor.publicKey,
+    relayer: null,
+    rateLimit: null,
+    priceOracle: null,
+    refundee: null,
+    rebateRecipient: null,
+    withdrawalEscrow: null,
+    funder: null,
+    refundQueue: null,
+  };
+  const redeemMerkle = (secret: Buffer, proof: Buffer[]) =>
+    program.methods
+      .redeemMerkle([...secret], proof.map((hash) => [...hash]))
+      .accounts(redeemAccounts)
+      .rpc({ commitment: "confirmed" });
+
+  before(async () => {
+    await fundParties(parties);
+    await initiateSwap({
+      ...parties,
+      secretHash: root,
+      swapAmount,
+      timelock,
+      options: { secretMerkleRoot: [...root] },
+    });
+  });
+
+  it("Test a plain redeem of a Merkle swap fails", async () => {
+    const { hashKind } = await program.account.swapAccount.fetch(swapAccount);
+    expect(hashKind).to.deep.equal({ merkleSha256: {} });
+    try {
+      await program.methods
+        .redeem([...secrets[0]])
+        .accounts(redeemAccounts)
+        .rpc({ commitment: "confirmed" });
+      expect.fail("the swap is not locked by a secret hash");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("WrongHashKind");
+    }
+  });
+
+  it("Test a redeem with an invalid proof fails", async () => {
+    try {
+      await redeemMerkle(secrets[0], [leaves[2], right]);
+      expect.fail("the proof does not lead to the root");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidSecret");
+    }
+  });
+
+  it("Test a redeem with a proof too long fails", async () => {
+    try {
+      await redeemMerkle(secrets[0], Array(17).fill(leaves[1]));
+      expect.fail("the proof exceeds MAX_MERKLE_PROOF_LEN");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("MerkleProofTooLong");
+    }
+  });
+
+  it("Test any secret of the tree redeems the swap once", async () => {
+    await redeemMerkle(secrets[2], [leaves[3], left]);
+    expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      swapAmount.toNumber()
+    );
+
+    try {
+      await redeemMerkle(secrets[1], [leaves[0], right]);
+      expect.fail("the swap was already redeemed");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("AccountNotInitialized");
+    }
+  });
+});
+
+describe("Testing the initiation slot of swaps", () => {
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(1000);
+  const parties = newParties();
+
+  before(async () => {
+    await fundParties(parties);
+  });
+
+  it("Test the stored slot is the slot of the initiate", async () => {
+    const secretHash = crypto.randomBytes(32);
+    const { signature, swapAccount } = await initiateSwap({
+      ...parties,
+      secretHash,
+      swapAmount,
+      timelock,
+      minimal: true,
+    });
+
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    expect(swap.initiatedSlot.toNumber()).to.equal(tx.slot);
+    expect(swap.expirySlot.sub(swap.initiatedSlot).eq(timelock)).to.be.true;
+  });
+});
+
+describe("Testing migrations of swaps of the first deployment", () => {
+  // Refer the baseline swap account fixture in Anchor.toml
+  const redeemer = web3.Keypair.fromSeed(Buffer.alloc(32, 1)).publicKey;
+  const rentSponsor = web3.Keypair.fromSeed(Buffer.alloc(32, 3));
+  const secret = Buffer.alloc(32, 4);
+  const swapAmount = 10_000_000;
+  const swapAccount = new web3.PublicKey(
+    "A5vrV3uERZx5A2D9qUmUXgaL2sUEmYv32bAERQcBmqFx"
+  );
+  // Any party can pay for the migration, without the rent sponsor
+  const payer = new web3.Keypair();
+
+  before(async () => {
+    await airdrop(payer.publicKey, 0.1 * web3.LAMPORTS_PER_SOL);
+  });
+
+  it("Test a migrated swap escrows in its vault and is redeemable", async () => {
+    await program.methods
+      .reallocSwap()
+      .accounts({
+        swapAccount,
+        vault: getVault(swapAccount),
+        payer: payer.publicKey,
+      })
+      .signers([payer])
+      .rpc({ commitment: "confirmed" });
+
+    const swap = await program.account.swapAccount.fetch(swapAccount);
+    expect(swap.redeemer.equals(redeemer)).to.be.true;
+    expect(swap.swapAmount.toNumber()).to.equal(swapAmount);
+    // The swap account only holds its rent, and the vault the swap amount
+    const stateRent = await connection.getMinimumBalanceForRentExemption(
+      program.account.swapAccount.size
+    );
+    const vaultRent = await connection.getMinimumBalanceForRentExemption(
+      program.account.swapVault.size
+    );
+    expect(await connection.getBalance(swapAccount)).to.equal(stateRent);
+    expect(await connection.getBalance(getVault(swapAccount))).to.equal(
+      vaultRent + swapAmount
+    );
+
+    const rentSponsorPreBalance = await connection.getBalance(
+      rentSponsor.publicKey
+    );
+    await program.methods
+      .redeem([...secret])
+      .accounts({
+        swapAccount,
+        redeemer,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+    expect(await connection.getBalance(redeemer)).to.equal(swapAmount);
+    // The rent paid by the payer is returned to the rent sponsor
+    expect(await connection.getBalance(rentSponsor.publicKey)).to.equal(
+      rentSponsorPreBalance + stateRent + vaultRent
+    );
+  });
+});
+
+describe("Testing discriminator-free lite redeems", () => {
+  // The flag of the `lite-redeem` feature in the `featureFlags` of `Version`
+  const liteRedeemFlag = 1 << 4;
+  // The byte lite redeems start with, `LITE_REDEEM_TAG`
+  const liteRedeemTag = 0x4c;
+  const swapAmount = new BN(0.01 * web3.LAMPORTS_PER_SOL);
+  const timelock = new BN(50);
+  const parties = newParties();
+  const { bob, rentSponsor } = parties;
+  const secrets = [crypto.randomBytes(32), crypto.randomBytes(32)];
+  const swapAccounts: web3.PublicKey[] = [];
+
+  const getComputeUnitsAndEvents = async (signature: string) => {
+    const tx = await connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const events = [...eventParser.parseLogs(tx.meta.logMessages)];
+    return { computeUnits: tx.meta.computeUnitsConsumed, events };
+  };
+
+  before(async function () {
+    const signature = await program.methods
+      .programVersion()
+      .rpc({ commitment: "confirmed" });
+    const {
+      events: [version],
+    } = await getComputeUnitsAndEvents(signature);
+    // Lite redeems are only dispatched by builds with the `lite-redeem` feature, which CI
+    // builds with `EXPECT_LITE_REDEEM` set, so that these tests can never be skipped there
+    if ((version.data.featureFlags.toNumber() & liteRedeemFlag) === 0) {
+      expect(process.env.EXPECT_LITE_REDEEM, "lite-redeem build expected").to.not
+        .be.ok;
+      this.skip();
+    }
+
+    await fundParties(parties);
+    for (const secret of secrets) {
+      const secretHash = crypto.createHash("sha256").update(secret).digest();
+      const { swapAccount } = await initiateSwap({
+        ...parties,
+        secretHash,
+        swapAmount,
+        timelock,
+        minimal: true,
+      });
+      swapAccounts.push(swapAccount);
+    }
+  });
+
+  it("Test a lite redeem matches a redeem for fewer compute units", async () => {
+    const redeemSignature = await program.methods
+      .redeem([...secrets[0]])
+      .accounts({
+        swapAccount: swapAccounts[0],
+        redeemer: bob.publicKey,
+        rentSponsor: rentSponsor.publicKey,
+        relayer: null,
+        rateLimit: null,
+        priceOracle: null,
+        refundee: null,
+        rebateRecipient: null,
+        withdrawalEscrow: null,
+        funder: null,
+        refundQueue: null,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    // The instruction data is the tag and the secret, without any discriminator
+    const writable = (pubkey: web3.PublicKey) => ({
+      pubkey,
+      isSigner: false,
+      isWritable: true,
+    });
+    const liteRedeem = new web3.TransactionInstruction({
+      programId: program.programId,
+      keys: [
+        writable(swapAccounts[1]),
+        writable(getVault(swapAccounts[1])),
+        writable(bob.publicKey),
+        writable(rentSponsor.publicKey),
+      ],
+      data: Buffer.concat([Buffer.from([liteRedeemTag]), secrets[1]]),
+    });
+    const liteRedeemSignature = await provider.sendAndConfirm(
+      new web3.Transaction().add(liteRedeem),
+      [],
+      { commitment: "confirmed" }
+    );
+
+    const redeem = await getComputeUnitsAndEvents(redeemSignature);
+    const lite = await getComputeUnitsAndEvents(liteRedeemSignature);
+    expect(lite.events.map((event) => event.name)).to.deep.equal(
+      redeem.events.map((event) => event.name)
+    );
+    const [redeemed, liteRedeemed] = [redeem.events[0], lite.events[0]];
+    expect(Buffer.from(liteRedeemed.data.secret).equals(secrets[1])).to.be.true;
+    expect(liteRedeemed.data.redeemerAmount.toString()).to.equal(
+      redeemed.data.redeemerAmount.toString()
+    );
+    for (const swapAccount of swapAccounts) {
+      expect(await connection.getAccountInfo(swapAccount)).to.be.null;
+      expect(await connection.getAccountInfo(getVault(swapAccount))).to.be
+        .null;
+    }
+    expect(await connection.getBalance(bob.publicKey)).to.equal(
+      2 * swapAmount.toNumber()
+    );
+    expect(lite.computeUnits).to.be.lessThan(redeem.computeUnits);
+  });
+});
@@ -1,6 +1,7 @@
 use crate::fuzz_accounts::FuzzAccounts;
 use crate::types::*;
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_sdk::{native_token::LAMPORTS_PER_SOL, system_program};
 use trident_fuzz::fuzzing::*;
 
 #[derive(TridentInstruction, Default)]
@@ -19,6 +20,9 @@ pub struct InitiateInstructionAccounts {
     #[account(mut)]
     pub swap_account: TridentAccount,
 
+    #[account(mut)]
+    pub vault: TridentAccount,
+
     #[account(mut, signer)]
     pub funder: TridentAccount,
 
@@ -27,6 +31,9 @@ pub struct InitiateInstructionAccounts {
 
     #[account(address = "11111111111111111111111111111111")]
     pub system_program: TridentAccount,
+
+    #[account(mut)]
+    pub stats: TridentAccount,
 }
 
 /// Instruction Data
@@ -55,4 +62,70 @@ pub struct InitiateInstructionData {
 /// Docs: https://ackee.xyz/trident/docs/latest/start-fuzzing/writting-fuzz-test/
 impl InstructionHooks for InitiateInstruction {
     type IxAccounts = FuzzAccounts;
+
+    fn set_data(&mut self, trident: &mut Trident, fuzz_accounts: &mut Self::IxAccounts) {
+        let redeemer =
+            fuzz_accounts
+                .redeemer
+                .get_or_create(trident.gen_range(0..3), trident, None, None);
+        let refundee =
+            fuzz_accounts
+                .refundee
+                .get_or_create(trident.gen_range(0..3), trident, None, None);
+        let secret = pooled_secret(trident.gen_range(0..SECRET_POOL_SIZE));
+
+        self.data.redeemer.set_pubkey(redeemer);
+        self.data.refundee.set_pubkey(refundee);
+        self.data.secret_hash = solana_sdk::hash::hash(&secret).to_bytes();
+        self.data.swap_amount = trident.gen_range(1..LAMPORTS_PER_SOL);
+        self.data.timelock = trident.gen_range(1..100);
+    }
+
+    fn set_accounts(&mut self, trident: &mut Trident, fuzz_accounts: &mut Self::IxAccounts) {
+        self.accounts.stats.set_address(stats_address());
+
+        let swap_account = fuzz_accounts.swap_account.get_or_create(
+            trident.gen_range(0..5),
+            trident,
+            Some(PdaSeeds::new(
+                &[
+                    self.data.redeemer.get_pubkey().as_ref(),
+                    self.data.refundee.get_pubkey().as_ref(),
+                    &self.data.secret_hash,
+                    &self.data.swap_amount.to_le_bytes(),
+                    &self.data.timelock.to_le_bytes(),
+                ],
+                PROGRAM_ID,
+            )),
+            None,
+        );
+        self.accounts.swap_account.set_address(swap_account);
+        self.accounts
+            .vault
+            .set_address(vault_address(&swap_account));
+
+        let funder = fuzz_accounts.funder.get_or_create(
+            trident.gen_range(0..3),
+            trident,
+            None,
+            Some(AccountMetadata::new(
+                10 * LAMPORTS_PER_SOL,
+                0,
+                system_program::ID,
+            )),
+        );
+        self.accounts.funder.set_address(funder);
+
+        let rent_sponsor = fuzz_accounts.rent_sponsor.get_or_create(
+            trident.gen_range(0..3),
+            trident,
+            None,
+            Some(AccountMetadata::new(
+                LAMPORTS_PER_SOL,
+                0,
+                system_program::ID,
+            )),
+        );
+        self.accounts.rent_sponsor.set_address(rent_sponsor);
+    }
 }
@@ -19,14 +19,23 @@ pub struct InstantRefundInstructionAccounts {
     #[account(mut)]
     pub swap_account: TridentAccount,
 
+    #[account(mut)]
+    pub vault: TridentAccount,
+
     #[account(mut)]
     pub refundee: TridentAccount,
 
     #[account(signer)]
-    pub redeemer: TridentAccount,
+    pub instant_refund_authority: TridentAccount,
 
     #[account(mut)]
     pub rent_sponsor: TridentAccount,
+
+    #[account(mut)]
+    pub protocol_config: TridentAccount,
+
+    #[account(mut)]
+    pub stats: TridentAccount,
 }
 
 /// Instruction Data
@@ -43,4 +52,11 @@ pub struct InstantRefundInstructionData {}
 /// Docs: https://ackee.xyz/trident/docs/latest/start-fuzzing/writting-fuzz-test/
 impl InstructionHooks for InstantRefundInstruction {
     type IxAccounts = FuzzAccounts;
+
+    fn set_accounts(&mut self, _trident: &mut Trident, _fuzz_accounts: &mut Self::IxAccounts) {
+        self.accounts
+            .protocol_config
+            .set_address(protocol_config_address());
+        self.accounts.stats.set_address(stats_address());
+    }
 }
@@ -19,11 +19,20 @@ pub struct RedeemInstructionAccounts {
     #[account(mut)]
     pub swap_account: TridentAccount,
 
+    #[account(mut)]
+    pub vault: TridentAccount,
+
     #[account(mut)]
     pub redeemer: TridentAccount,
 
     #[account(mut)]
     pub rent_sponsor: TridentAccount,
+
+    #[account(mut)]
+    pub protocol_config: TridentAccount,
+
+    #[account(mut)]
+    pub stats: TridentAccount,
 }
 
 /// Instruction Data
@@ -42,4 +51,36 @@ pub struct RedeemInstructionData {
 /// Docs: https://ackee.xyz/trident/docs/latest/start-fuzzing/writting-fuzz-test/
 impl InstructionHooks for RedeemInstruction {
     type IxAccounts = FuzzAccounts;
+
+    fn set_data(&mut self, trident: &mut Trident, _fuzz_accounts: &mut Self::IxAccounts) {
+        self.data.secret = pooled_secret(trident.gen_range(0..SECRET_POOL_SIZE));
+    }
+
+    fn set_accounts(&mut self, trident: &mut Trident, fuzz_accounts: &mut Self::IxAccounts) {
+        self.accounts
+            .protocol_config
+            .set_address(protocol_config_address());
+        self.accounts.stats.set_address(stats_address());
+
+        let swap_account =
+            fuzz_accounts
+                .swap_account
+                .get_or_create(trident.gen_range(0..5), trident, None, None);
+        self.accounts.swap_account.set_address(swap_account);
+        self.accounts
+            .vault
+            .set_address(vault_address(&swap_account));
+
+        // Route the payout to the parties recorded in the swap, if it exists
+        if let Some(state) =
+            SwapAccount::unpack(trident.get_client().get_account(&swap_account).data())
+        {
+            self.accounts
+                .redeemer
+                .set_address(state.redeemer.get_pubkey());
+            self.accounts
+                .rent_sponsor
+                .set_address(state.rent_sponsor.get_pubkey());
+        }
+    }
 }
@@ -5,6 +5,7 @@ mod instructions;
 mod transactions;
 mod types;
 pub use transactions::*;
+use types::*;
 
 #[derive(FuzzTestMethods)]
 struct FuzzTest {
@@ -25,20 +26,77 @@ impl FuzzTest {
 
     #[init]
     fn start(&mut self) {
-        // perform any initialization here, this method will be executed
-        // at start of each iteration
+        // Stand in for `init_protocol_config`, which requires the upgrade authority of the
+        // program, with no cap on the total value locked, and for `init_stats`
+        let config = ProtocolConfig {
+            max_tvl_lamports: u64::MAX,
+            bump: Pubkey::find_program_address(&[CONFIG_SEED], &PROGRAM_ID).1,
+            ..Default::default()
+        };
+        let stats = Stats {
+            bump: Pubkey::find_program_address(&[STATS_SEED], &PROGRAM_ID).1,
+            ..Default::default()
+        };
+        let client = self.trident.get_client();
+        client.set_account_custom(
+            &protocol_config_address(),
+            &program_account("ProtocolConfig", &config),
+        );
+        client.set_account_custom(&stats_address(), &program_account("Stats", &stats));
     }
 
     #[flow]
-    fn flow1(&mut self) {
-        // perform logic which is meant to be fuzzed
-        // this flow is selected randomly from other flows
+    fn initiate_then_redeem(&mut self) {
+        let mut initiate = InitiateTransaction::build(&mut self.trident, &mut self.fuzz_accounts);
+        self.trident
+            .execute_transaction(&mut initiate, Some("Initiate"));
+
+        let mut redeem = RedeemTransaction::build(&mut self.trident, &mut self.fuzz_accounts);
+        self.trident
+            .execute_transaction(&mut redeem, Some("Redeem"));
+    }
+
+    #[flow]
+    fn initiate_then_refund(&mut self) {
+        let mut initiate = InitiateTransaction::build(&mut self.trident, &mut self.fuzz_accounts);
+        self.trident
+            .execute_transaction(&mut initiate, Some("Initiate"));
+
+        let slot = self.trident.gen_range(0..200);
+        self.trident.get_client().warp_to_slot(slot);
+        let mut refund = RefundTransaction::build(&mut self.trident, &mut self.fuzz_accounts);
+        self.trident
+            .execute_transaction(&mut refund, Some("Refund"));
     }
 
     #[flow]
-    fn flow2(&mut self) {
-        // perform logic which is meant to be fuzzed
-        // this flow is selected randomly from other flows
+    fn distinct_parameters_derive_distinct_swaps(&mut self) {
+        let params = gen_swap_params(&mut self.trident, &mut self.fuzz_accounts);
+        let mut other = params.clone();
+        // Perturb the parameters only slightly, if at all, as near-identical parameter sets
+        // are the likeliest to collide, e.g. by shifting bytes between adjacent seeds
+        match self.trident.gen_range(0..7) {
+            0 => other = gen_swap_params(&mut self.trident, &mut self.fuzz_accounts),
+            1 => other.redeemer = params.refundee,
+            2 => (other.redeemer, other.refundee) = (params.refundee, params.redeemer),
+            3 => other.secret_hash[self.trident.gen_range(0..32)] ^= 1,
+            4 => other.swap_amount = params.swap_amount.wrapping_add(1),
+            5 => (other.swap_amount, other.timelock) = (params.timelock, params.swap_amount),
+            _ => {}
+        }
+
+        // The seeds have fixed widths, so no two parameter sets share the concatenation
+        // of their seeds, which the PDA is derived from
+        assert_eq!(
+            params == other,
+            params.seeds().concat() == other.seeds().concat(),
+            "seed ambiguity between {params:?} and {other:?}"
+        );
+        assert_eq!(
+            params == other,
+            params.address() == other.address(),
+            "PDA collision between {params:?} and {other:?}"
+        );
     }
 
     #[end]
@@ -48,6 +106,27 @@ impl FuzzTest {
     }
 }
 
+/// Generates the parameters of a swap, between parties drawn from `fuzz_accounts`
+fn gen_swap_params(trident: &mut Trident, fuzz_accounts: &mut FuzzAccounts) -> SwapParams {
+    let redeemer =
+        fuzz_accounts
+            .redeemer
+            .get_or_create(trident.gen_range(0..3), trident, None, None);
+    let refundee =
+        fuzz_accounts
+            .refundee
+            .get_or_create(trident.gen_range(0..3), trident, None, None);
+    let mut secret_hash = [0; 32];
+    trident.fill_bytes(&mut secret_hash);
+    SwapParams {
+        redeemer,
+        refundee,
+        secret_hash,
+        swap_amount: trident.gen_range(0..=u64::MAX),
+        timelock: trident.gen_range(0..=u64::MAX),
+    }
+}
+
 fn main() {
     FuzzTest::fuzz(1000, 100);
 }
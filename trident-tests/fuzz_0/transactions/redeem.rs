@@ -1,5 +1,6 @@
 use crate::fuzz_accounts::FuzzAccounts;
 use crate::instructions::*;
+use crate::types::*;
 use trident_fuzz::fuzzing::*;
 
 /// Customize transaction behavior by adding more instructions.
@@ -19,4 +20,16 @@ pub struct RedeemTransaction {
 /// Docs: https://ackee.xyz/trident/docs/latest/trident-advanced/trident-transactions/transaction-methods/
 impl TransactionHooks for RedeemTransaction {
     type IxAccounts = FuzzAccounts;
+
+    fn transaction_invariant_check(&self) -> Result<(), FuzzingError> {
+        let accounts = &self.instruction.accounts;
+        check_seed_consistency(&accounts.swap_account)?;
+        check_redeem_payout(&accounts.swap_account, &accounts.vault, &accounts.redeemer)?;
+        check_lamport_conservation(&[
+            &accounts.swap_account,
+            &accounts.vault,
+            &accounts.redeemer,
+            &accounts.rent_sponsor,
+        ])
+    }
 }
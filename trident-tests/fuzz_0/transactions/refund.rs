@@ -1,5 +1,6 @@
 use crate::fuzz_accounts::FuzzAccounts;
 use crate::instructions::*;
+use crate::types::*;
 use trident_fuzz::fuzzing::*;
 
 /// Customize transaction behavior by adding more instructions.
@@ -19,4 +20,15 @@ pub struct RefundTransaction {
 /// Docs: https://ackee.xyz/trident/docs/latest/trident-advanced/trident-transactions/transaction-methods/
 impl TransactionHooks for RefundTransaction {
     type IxAccounts = FuzzAccounts;
+
+    fn transaction_invariant_check(&self) -> Result<(), FuzzingError> {
+        let accounts = &self.instruction.accounts;
+        check_seed_consistency(&accounts.swap_account)?;
+        check_lamport_conservation(&[
+            &accounts.swap_account,
+            &accounts.vault,
+            &accounts.refundee,
+            &accounts.rent_sponsor,
+        ])
+    }
 }
@@ -1,4 +1,5 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_sdk::pubkey;
 use trident_fuzz::fuzzing::*;
 
 /// File containing all custom types which can be used
@@ -6,6 +7,23 @@ use trident_fuzz::fuzzing::*;
 ///
 /// You can define your own custom types here.
 
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct Canceled {
+    pub redeemer: TridentPubkey,
+
+    pub refundee: TridentPubkey,
+
+    pub secret_hash: [u8; 32],
+
+    pub swap_amount: u64,
+
+    pub timelock: u64,
+
+    pub funder: TridentPubkey,
+
+    pub swap_id: [u8; 32],
+}
+
 #[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
 pub struct Initiated {
     pub redeemer: TridentPubkey,
@@ -20,7 +38,15 @@ pub struct Initiated {
 
     pub destination_data: Option<Vec<u8>>,
 
+    pub destination_commitment: Option<[u8; 32]>,
+
     pub funder: TridentPubkey,
+
+    pub init_fee: u64,
+
+    pub hash_kind: HashKind,
+
+    pub swap_id: [u8; 32],
 }
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
@@ -34,6 +60,31 @@ pub struct InstantRefunded {
     pub swap_amount: u64,
 
     pub timelock: u64,
+
+    pub slots_before_expiry: i64,
+
+    pub instant_refund_authority: TridentPubkey,
+
+    pub penalty: u64,
+
+    pub refund_amount: u64,
+
+    pub swap_id: [u8; 32],
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct ProtocolConfig {
+    pub admin: TridentPubkey,
+
+    pub max_tvl_lamports: u64,
+
+    pub current_tvl: u64,
+
+    pub bump: u8,
+
+    pub max_redeems_per_window: u64,
+
+    pub rate_limit_window_slots: u64,
 }
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
@@ -47,6 +98,22 @@ pub struct Redeemed {
     pub swap_amount: u64,
 
     pub timelock: u64,
+
+    pub relayer: Option<TridentPubkey>,
+
+    pub additional_secrets: Vec<[u8; 32]>,
+
+    pub memo: Option<String>,
+
+    pub distribution: Vec<Share>,
+
+    pub rebate: u64,
+
+    pub redeemer_amount: u64,
+
+    pub unvested: u64,
+
+    pub swap_id: [u8; 32],
 }
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
@@ -60,6 +127,52 @@ pub struct Refunded {
     pub swap_amount: u64,
 
     pub timelock: u64,
+
+    pub keeper: Option<TridentPubkey>,
+
+    pub keeper_reward: u64,
+
+    pub swap_id: [u8; 32],
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct SettlementReceipt {
+    pub redeemer: TridentPubkey,
+
+    pub swap_amount: u64,
+
+    pub secret: [u8; 32],
+
+    pub slot: u64,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct Settled {
+    pub receipt: SettlementReceipt,
+
+    pub receipt_hash: [u8; 32],
+
+    pub swap_id: [u8; 32],
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct Share {
+    pub recipient: TridentPubkey,
+
+    pub bps: u16,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
+pub struct Stats {
+    pub initiated: u64,
+
+    pub redeemed: u64,
+
+    pub refunded: u64,
+
+    pub instant_refunded: u64,
+
+    pub bump: u8,
 }
 
 #[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Default)]
@@ -79,4 +192,256 @@ pub struct SwapAccount {
     pub rent_sponsor: TridentPubkey,
 
     pub timelock: u64,
+
+    pub destination_commitment: Option<[u8; 32]>,
+
+    pub relayer: Option<TridentPubkey>,
+
+    pub adaptor_point: Option<[u8; 32]>,
+
+    pub cancel_authority: Option<TridentPubkey>,
+
+    pub original_expiry_slot: u64,
+
+    pub max_extension_slots: u64,
+
+    pub additional_secret_hashes: Vec<[u8; 32]>,
+
+    pub instant_refund_authority: TridentPubkey,
+
+    pub initiated_slot: u64,
+
+    pub original_redeemer: TridentPubkey,
+
+    pub single_boundary: bool,
+
+    pub sink: bool,
+
+    pub suppress_events: bool,
+
+    pub refund_trigger: Option<TridentPubkey>,
+
+    pub swap_id: [u8; 32],
+
+    pub distribution: Vec<Share>,
+
+    pub price_oracle: Option<TridentPubkey>,
+
+    pub target_value: u64,
+
+    pub funder: TridentPubkey,
+
+    pub rebate_bps: u16,
+
+    pub rebate_recipient: Option<TridentPubkey>,
+
+    pub absolute_expiry: bool,
+
+    pub instant_refund_cliff_slots: u64,
+
+    pub vesting: bool,
+
+    pub hash_kind: HashKind,
+
+    pub keeper_reward_lamports: u64,
+
+    pub counterparty_timelock: u64,
+
+    pub secret_merkle_root: Option<[u8; 32]>,
+
+    pub vault_bump: u8,
+
+    pub tvl_locked: bool,
+
+    pub max_redeems_per_window: u64,
+
+    pub rate_limit_window_slots: u64,
+
+    pub price_feed_id: Option<[u8; 32]>,
+
+    pub bucketed_seeds: bool,
+}
+
+#[derive(Debug, BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Default)]
+pub enum HashKind {
+    #[default]
+    Sha256,
+
+    Ed25519Adaptor,
+
+    MerkleSha256,
+}
+
+/// The address of the program under test, as configured in `Trident.toml`
+pub const PROGRAM_ID: Pubkey = pubkey!("6eksgdCnSjUaGQWZ6iYvauv1qzvYPF33RTGTM1ZuyENx");
+
+/// The seed prefix of the vault PDA escrowing the funds of a swap
+pub const VAULT_SEED: &[u8] = b"vault";
+
+/// The seed of the singleton PDA holding the `ProtocolConfig`
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// The seed of the singleton PDA holding the `Stats`
+pub const STATS_SEED: &[u8] = b"stats";
+
+/// The size of Anchor's internal discriminator in a PDA's memory
+pub const ANCHOR_DISCRIMINATOR: usize = 8;
+
+/// Secrets are drawn from a small fixed pool, so that redeems generated independently
+/// of initiates still have a fair chance of revealing a valid preimage.
+pub const SECRET_POOL_SIZE: u8 = 4;
+
+pub fn pooled_secret(index: u8) -> [u8; 32] {
+    [index; 32]
+}
+
+impl SwapAccount {
+    /// Decodes the state of a swap from raw account data, if it holds one
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let mut data = data.get(ANCHOR_DISCRIMINATOR..)?;
+        Self::deserialize(&mut data).ok()
+    }
+
+    /// Re-derives the PDA address from the stored parameters, exactly as the program does
+    pub fn derive_address(&self) -> Option<Pubkey> {
+        Pubkey::create_program_address(
+            &[
+                self.original_redeemer.get_pubkey().as_ref(),
+                self.refundee.get_pubkey().as_ref(),
+                &self.secret_hash,
+                &self.swap_amount.to_le_bytes(),
+                &self.timelock.to_le_bytes(),
+                &[self.bump],
+            ],
+            &PROGRAM_ID,
+        )
+        .ok()
+    }
+}
+
+/// Derives the address of the vault escrowing the funds of `swap_account`
+pub fn vault_address(swap_account: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[VAULT_SEED, swap_account.as_ref()], &PROGRAM_ID).0
+}
+
+/// The parameters a swap PDA is derived from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapParams {
+    pub redeemer: Pubkey,
+    pub refundee: Pubkey,
+    pub secret_hash: [u8; 32],
+    pub swap_amount: u64,
+    pub timelock: u64,
+}
+
+impl SwapParams {
+    /// The seeds of the swap PDA, exactly as the program derives them
+    pub fn seeds(&self) -> [Vec<u8>; 5] {
+        [
+            self.redeemer.to_bytes().to_vec(),
+            self.refundee.to_bytes().to_vec(),
+            self.secret_hash.to_vec(),
+            self.swap_amount.to_le_bytes().to_vec(),
+            self.timelock.to_le_bytes().to_vec(),
+        ]
+    }
+
+    /// Derives the address of the swap PDA
+    pub fn address(&self) -> Pubkey {
+        let seeds = self.seeds();
+        let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+        Pubkey::find_program_address(&seeds, &PROGRAM_ID).0
+    }
+}
+
+/// Derives the address of the singleton PDA holding the `ProtocolConfig`
+pub fn protocol_config_address() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], &PROGRAM_ID).0
+}
+
+/// Derives the address of the singleton PDA holding the `Stats`
+pub fn stats_address() -> Pubkey {
+    Pubkey::find_program_address(&[STATS_SEED], &PROGRAM_ID).0
+}
+
+/// Encodes `state` as the program would store it as an account named `name`, i.e. prefixed
+/// with its Anchor discriminator and held in a rent-exempt account of the program
+pub fn program_account(name: &str, state: &impl BorshSerialize) -> AccountSharedData {
+    let preimage = format!("account:{name}");
+    let mut data =
+        solana_sdk::hash::hash(preimage.as_bytes()).to_bytes()[..ANCHOR_DISCRIMINATOR].to_vec();
+    state.serialize(&mut data).unwrap();
+    AccountSharedData::create(
+        solana_sdk::rent::Rent::default().minimum_balance(data.len()),
+        data,
+        PROGRAM_ID,
+        false,
+        0,
+    )
+}
+
+/// Asserts that a swap account consumed by a successful transaction is located exactly
+/// at the address derived from its stored `swap_amount` and `timelock` (among other seeds).
+/// A violation would mean that a PDA whose seeds do not match its data was accepted.
+pub fn check_seed_consistency(swap_account: &TridentAccount) -> Result<(), FuzzingError> {
+    let Some(state) = SwapAccount::unpack(swap_account.get_snapshot_before().data()) else {
+        return Err(FuzzingError::Custom(1));
+    };
+    if state.derive_address() != Some(swap_account.pubkey()) {
+        return Err(FuzzingError::Custom(2));
+    }
+    Ok(())
+}
+
+/// Asserts that a successful redeem paid out exactly the swap amount to the redeemer,
+/// and that both the swap account and its vault were fully drained and closed in the process.
+/// If the redeemer is also the rent sponsor, it receives the rent of both accounts as well.
+pub fn check_redeem_payout(
+    swap_account: &TridentAccount,
+    vault: &TridentAccount,
+    redeemer: &TridentAccount,
+) -> Result<(), FuzzingError> {
+    let Some(state) = SwapAccount::unpack(swap_account.get_snapshot_before().data()) else {
+        return Err(FuzzingError::Custom(1));
+    };
+    for account in [swap_account, vault] {
+        let after = account.get_snapshot_after();
+        if after.lamports() != 0 || !after.data().is_empty() {
+            return Err(FuzzingError::Custom(3));
+        }
+    }
+
+    let expected_payout = if redeemer.pubkey() == state.rent_sponsor.get_pubkey() {
+        swap_account.get_snapshot_before().lamports() + vault.get_snapshot_before().lamports()
+    } else {
+        state.swap_amount
+    };
+    let payout = redeemer
+        .get_snapshot_after()
+        .lamports()
+        .checked_sub(redeemer.get_snapshot_before().lamports());
+    if payout != Some(expected_payout) {
+        return Err(FuzzingError::Custom(4));
+    }
+    Ok(())
+}
+
+/// Asserts that a transaction neither created nor destroyed any lamports, i.e. that the total
+/// balance of all accounts it involves is the same before and after it. Transaction fees are
+/// paid by Trident's own payer, which is none of these accounts, so no allowance is made for them.
+/// Accounts passed more than once are only counted once.
+pub fn check_lamport_conservation(accounts: &[&TridentAccount]) -> Result<(), FuzzingError> {
+    let mut seen = std::collections::HashSet::new();
+    let (mut total_before, mut total_after) = (0u128, 0u128);
+    for account in accounts
+        .iter()
+        .filter(|account| seen.insert(account.pubkey()))
+    {
+        total_before += u128::from(account.get_snapshot_before().lamports());
+        total_after += u128::from(account.get_snapshot_after().lamports());
+    }
+    if total_before != total_after {
+        return Err(FuzzingError::Custom(5));
+    }
+    Ok(())
 }
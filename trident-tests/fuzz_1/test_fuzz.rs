@@ -0,0 +1,133 @@
+use anchor_lang::{
+    solana_program::{account_info::AccountInfo, pubkey::Pubkey},
+    system_program, Discriminator,
+};
+use solana_native_swaps::{instruction, process_instruction, ID};
+use trident_fuzz::fuzzing::*;
+
+/// The maximum number of accounts passed to a single raw instruction
+const MAX_ACCOUNTS: usize = 8;
+/// The maximum size of the raw instruction data, and of each account's data
+const MAX_DATA_LEN: usize = 512;
+
+/// Raw-bytes fuzzing does not reuse any accounts across iterations
+#[derive(Default)]
+struct FuzzAccounts;
+
+/// Feeds arbitrary bytes and accounts into the natively linked program entrypoint.
+/// This reaches the deserialization of instruction data and accounts, which the typed
+/// harness in `fuzz_0` never exercises with malformed inputs.
+/// Any panic within the program fails the fuzz test.
+#[derive(FuzzTestMethods)]
+struct FuzzTest {
+    /// for fuzzing
+    trident: Trident,
+    /// for storing fuzzing accounts
+    fuzz_accounts: FuzzAccounts,
+}
+
+#[flow_executor]
+impl FuzzTest {
+    fn new() -> Self {
+        Self {
+            trident: Trident::default(),
+            fuzz_accounts: FuzzAccounts,
+        }
+    }
+
+    #[flow]
+    fn arbitrary_instruction(&mut self) {
+        let data = self.gen_instruction_data();
+        let mut keys = Vec::new();
+        let mut owners = Vec::new();
+        let mut balances = Vec::new();
+        let mut datas = Vec::new();
+        for _ in 0..self.trident.gen_range(0..MAX_ACCOUNTS) {
+            keys.push(Pubkey::new_from_array(self.trident.gen_pubkey().to_bytes()));
+            owners.push(match self.trident.gen_range(0..3) {
+                0 => ID,
+                1 => system_program::ID,
+                _ => Pubkey::new_from_array(self.trident.gen_pubkey().to_bytes()),
+            });
+            balances.push(self.trident.gen_range(0..u64::MAX));
+            datas.push(self.gen_bytes());
+        }
+
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(owners.iter())
+            .zip(balances.iter_mut())
+            .zip(datas.iter_mut())
+            .map(|(((key, owner), lamports), data)| {
+                AccountInfo::new(key, true, true, lamports, data, owner, false, 0)
+            })
+            .collect();
+
+        // Errors are expected for nearly all inputs; only panics indicate a bug
+        let _ = process_instruction(&ID, &accounts, &data);
+    }
+}
+
+impl FuzzTest {
+    /// Generates instruction data which, most of the time, starts with the discriminator of
+    /// an actual instruction, so that fuzzing reaches past the instruction dispatch.
+    fn gen_instruction_data(&mut self) -> Vec<u8> {
+        let discriminators = [
+            instruction::Initiate::DISCRIMINATOR,
+            instruction::InitiateWithCommitment::DISCRIMINATOR,
+            instruction::InitiateAdaptor::DISCRIMINATOR,
+            instruction::InitiateMinimal::DISCRIMINATOR,
+            instruction::TryInitiate::DISCRIMINATOR,
+            instruction::Redeem::DISCRIMINATOR,
+            instruction::RedeemHex::DISCRIMINATOR,
+            instruction::RedeemAdaptor::DISCRIMINATOR,
+            instruction::RedeemMulti::DISCRIMINATOR,
+            instruction::Refund::DISCRIMINATOR,
+            instruction::InstantRefund::DISCRIMINATOR,
+            instruction::Decline::DISCRIMINATOR,
+            instruction::SplitSwap::DISCRIMINATOR,
+            instruction::MergeSwaps::DISCRIMINATOR,
+            instruction::ExtendTimelock::DISCRIMINATOR,
+            instruction::CommitRebate::DISCRIMINATOR,
+            instruction::TransferRedeemRights::DISCRIMINATOR,
+            instruction::SignalExpiryApproaching::DISCRIMINATOR,
+            instruction::Snapshot::DISCRIMINATOR,
+            instruction::ProgramVersion::DISCRIMINATOR,
+            instruction::CheckSecret::DISCRIMINATOR,
+            instruction::AssertNotInitialized::DISCRIMINATOR,
+            instruction::ReallocSwap::DISCRIMINATOR,
+            instruction::InitProtocolConfig::DISCRIMINATOR,
+            instruction::SetMaxTvl::DISCRIMINATOR,
+            instruction::InitStats::DISCRIMINATOR,
+            instruction::SetRateLimit::DISCRIMINATOR,
+            instruction::InitRateLimit::DISCRIMINATOR,
+            instruction::RecordSecret::DISCRIMINATOR,
+            instruction::InitiateFromWsol::DISCRIMINATOR,
+            instruction::InitiateAt::DISCRIMINATOR,
+            instruction::Amend::DISCRIMINATOR,
+            instruction::RedeemToEscrow::DISCRIMINATOR,
+            instruction::RedeemSiblings::DISCRIMINATOR,
+            instruction::InitWithdrawalEscrow::DISCRIMINATOR,
+            instruction::Withdraw::DISCRIMINATOR,
+            instruction::InitRefundQueue::DISCRIMINATOR,
+            instruction::Deregister::DISCRIMINATOR,
+            instruction::RedeemIdempotent::DISCRIMINATOR,
+            instruction::CloseRedeemReceipt::DISCRIMINATOR,
+            instruction::RedeemMerkle::DISCRIMINATOR,
+        ];
+        let index = self.trident.gen_range(0..discriminators.len() + 1);
+        let mut data = discriminators.get(index).map_or(vec![], |d| d.to_vec());
+        data.extend(self.gen_bytes());
+        data
+    }
+
+    fn gen_bytes(&mut self) -> Vec<u8> {
+        let mut bytes = vec![0; self.trident.gen_range(0..MAX_DATA_LEN)];
+        self.trident.fill_bytes(&mut bytes);
+        bytes
+    }
+}
+
+fn main() {
+    FuzzTest::fuzz(1000, 100);
+}